@@ -0,0 +1,40 @@
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+#![forbid(unsafe_code)]
+// Bevy systems take their parameters by value
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::audio::AudioPlugin;
+use bevy::prelude::*;
+use sonant::{SonantPlugin, Song};
+use std::time::Duration;
+
+fn main() {
+    // Run headless at 60 updates per second, loading assets from the `examples` directory
+    let runner = ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0));
+    let assets = AssetPlugin {
+        file_path: "examples".into(),
+        ..default()
+    };
+
+    App::new()
+        .add_plugins((MinimalPlugins.set(runner), assets, AudioPlugin::default()))
+        .add_plugins(SonantPlugin)
+        .add_systems(Startup, play)
+        .add_systems(Update, exit_when_done)
+        .run();
+}
+
+fn play(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let song: Handle<Song> = asset_server.load("poseidon.snt");
+
+    // The entity is despawned when the song ends
+    commands.spawn((AudioPlayer(song), PlaybackSettings::DESPAWN));
+}
+
+fn exit_when_done(players: Query<(), With<AudioPlayer<Song>>>, mut exit: MessageWriter<AppExit>) {
+    if players.is_empty() {
+        exit.write(AppExit::Success);
+    }
+}
@@ -0,0 +1,30 @@
+// An AudioWorklet processor that plays a Sonant song.
+//
+// The compiled WebAssembly module and the song data are passed in `processorOptions`, because the
+// worklet cannot fetch them itself.
+
+import { initSync, Renderer, Song } from "./pkg/sonant.js";
+
+class SonantProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+
+    const { module, data } = options.processorOptions;
+    initSync({ module });
+
+    // The renderer has its own copy of the song
+    const song = new Song(data);
+    this.renderer = new Renderer(song, sampleRate, 0n, 1n);
+    song.free();
+  }
+
+  process(inputs, outputs) {
+    const [left, right] = outputs[0];
+    const frames = this.renderer.render(left, right);
+
+    // Stop processing when the song ends
+    return frames === left.length;
+  }
+}
+
+registerProcessor("sonant", SonantProcessor);
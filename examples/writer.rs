@@ -3,13 +3,12 @@
 #![allow(clippy::cast_possible_truncation)]
 #![forbid(unsafe_code)]
 
-use arrayvec::ArrayVec;
 use byteorder::{ByteOrder as _, NativeEndian};
 use colored::Colorize;
 use error_iter::ErrorIter as _;
-use riff_wave::{WaveWriter, WriteError};
-use sonant::{Error as SonantError, Song, Synth};
-use std::{fs::File, io::BufWriter, process::ExitCode};
+use sonant::export::wav::{self, Options};
+use sonant::{Error as SonantError, Song};
+use std::{fs::File, process::ExitCode};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -25,9 +24,6 @@ pub enum Error {
 
     #[error("I/O error")]
     Io(#[from] std::io::Error),
-
-    #[error("Wave writer error")]
-    Writer(#[from] WriteError),
 }
 
 fn main() -> ExitCode {
@@ -61,19 +57,16 @@ fn writer() -> Result<(), Error> {
         NativeEndian::read_u64(&seed[8..16]),
     );
 
-    // Load a sonant song and create a synth
+    // Load a sonant song
     let song = Song::from_slice(&data)?;
-    let synth = Synth::new(&song, seed, 44100.0);
 
     // Write the wav file
     let file = File::create(wav_filename)?;
-    let writer = BufWriter::new(file);
-    let mut wave_writer = WaveWriter::new(2, 44100, 16, writer)?;
-
-    for sample in synth.flat_map(ArrayVec::from) {
-        let sample = (sample * f32::from(i16::MAX)).round() as i16;
-        wave_writer.write_sample_i16(sample)?;
-    }
+    let options = Options {
+        seed,
+        ..Options::default()
+    };
+    wav::write(&song, file, &options)?;
 
     Ok(())
 }
@@ -0,0 +1,73 @@
+#ifndef SONANT_H
+#define SONANT_H
+
+/* This file is generated by cbindgen. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A song loaded with `sonant_song_from_bytes`.
+typedef struct SonantSong SonantSong;
+
+// A synth created with `sonant_synth_new`. It has its own copy of the song.
+typedef struct SonantSynth SonantSynth;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Load a song from the `len` bytes of a `.snt` file at `data`.
+//
+// Returns `NULL` if the song cannot be parsed. The song must be freed with `sonant_song_free`.
+//
+// # Safety
+//
+// `data` must be `NULL`, or point to `len` readable bytes.
+struct SonantSong *sonant_song_from_bytes(const uint8_t *data, size_t len);
+
+// Free a song. Synths created from the song are not affected.
+//
+// # Safety
+//
+// `song` must be `NULL`, or a song returned by `sonant_song_from_bytes` that has not been freed.
+void sonant_song_free(struct SonantSong *song);
+
+// Create a synth that plays `song` at `sample_rate` Hz. The seed is used for the noise
+// generator.
+//
+// Returns `NULL` if `song` is `NULL`. The synth must be freed with `sonant_synth_free`.
+//
+// # Safety
+//
+// `song` must be `NULL`, or a song returned by `sonant_song_from_bytes` that has not been freed.
+struct SonantSynth *sonant_synth_new(const struct SonantSong *song,
+                                     uint64_t seed0,
+                                     uint64_t seed1,
+                                     float sample_rate);
+
+// Render interleaved stereo frames into the `len` floats at `buffer`.
+//
+// Returns the number of frames written, which is less than `len / 2` only when the song has
+// ended.
+//
+// # Safety
+//
+// `synth` must be `NULL`, or a synth returned by `sonant_synth_new` that has not been freed.
+// `buffer` must be `NULL`, or point to `len` writable floats.
+size_t sonant_synth_render(struct SonantSynth *synth, float *buffer, size_t len);
+
+// Free a synth.
+//
+// # Safety
+//
+// `synth` must be `NULL`, or a synth returned by `sonant_synth_new` that has not been freed.
+void sonant_synth_free(struct SonantSynth *synth);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SONANT_H */
@@ -0,0 +1,111 @@
+//! A C API for the Sonant synth, declared in `include/sonant.h`.
+//!
+//! Build it as a shared and static library with `cargo build --release -p sonant-capi`, and link
+//! C programs with `-lsonant_capi`.
+
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+// The raw pointers that cross the C ABI are checked for null, and are otherwise trusted
+#![allow(unsafe_code)]
+
+use sonant::{Song, Synth};
+
+/// A song loaded with `sonant_song_from_bytes`.
+pub struct SonantSong(Song);
+
+/// A synth created with `sonant_synth_new`. It has its own copy of the song.
+pub struct SonantSynth(Synth<Song>);
+
+/// Load a song from the `len` bytes of a `.snt` file at `data`.
+///
+/// Returns `NULL` if the song cannot be parsed. The song must be freed with `sonant_song_free`.
+///
+/// # Safety
+///
+/// `data` must be `NULL`, or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sonant_song_from_bytes(data: *const u8, len: usize) -> *mut SonantSong {
+    if data.is_null() {
+        return core::ptr::null_mut();
+    }
+
+    let slice = core::slice::from_raw_parts(data, len);
+    match Song::from_slice(slice) {
+        Ok(song) => Box::into_raw(Box::new(SonantSong(song))),
+        Err(_) => core::ptr::null_mut(),
+    }
+}
+
+/// Free a song. Synths created from the song are not affected.
+///
+/// # Safety
+///
+/// `song` must be `NULL`, or a song returned by `sonant_song_from_bytes` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sonant_song_free(song: *mut SonantSong) {
+    if !song.is_null() {
+        drop(Box::from_raw(song));
+    }
+}
+
+/// Create a synth that plays `song` at `sample_rate` Hz. The seed is used for the noise
+/// generator.
+///
+/// Returns `NULL` if `song` is `NULL`. The synth must be freed with `sonant_synth_free`.
+///
+/// # Safety
+///
+/// `song` must be `NULL`, or a song returned by `sonant_song_from_bytes` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sonant_synth_new(
+    song: *const SonantSong,
+    seed0: u64,
+    seed1: u64,
+    sample_rate: f32,
+) -> *mut SonantSynth {
+    match song.as_ref() {
+        Some(SonantSong(song)) => {
+            let synth = Synth::new(song.clone(), (seed0, seed1), sample_rate);
+            Box::into_raw(Box::new(SonantSynth(synth)))
+        }
+        None => core::ptr::null_mut(),
+    }
+}
+
+/// Render interleaved stereo frames into the `len` floats at `buffer`.
+///
+/// Returns the number of frames written, which is less than `len / 2` only when the song has
+/// ended.
+///
+/// # Safety
+///
+/// `synth` must be `NULL`, or a synth returned by `sonant_synth_new` that has not been freed.
+/// `buffer` must be `NULL`, or point to `len` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn sonant_synth_render(
+    synth: *mut SonantSynth,
+    buffer: *mut f32,
+    len: usize,
+) -> usize {
+    let Some(SonantSynth(synth)) = synth.as_mut() else {
+        return 0;
+    };
+    if buffer.is_null() {
+        return 0;
+    }
+
+    let buffer = core::slice::from_raw_parts_mut(buffer, len);
+    synth.render(buffer)
+}
+
+/// Free a synth.
+///
+/// # Safety
+///
+/// `synth` must be `NULL`, or a synth returned by `sonant_synth_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sonant_synth_free(synth: *mut SonantSynth) {
+    if !synth.is_null() {
+        drop(Box::from_raw(synth));
+    }
+}
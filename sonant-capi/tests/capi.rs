@@ -0,0 +1,81 @@
+//! Tests for the C ABI: the generated header, and a C program that renders a song.
+
+#![cfg(unix)]
+
+use sonant::{Song, Synth};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FRAMES: usize = 44100;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Build the library, and return the directory that contains it.
+fn build_library() -> PathBuf {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir())
+        .args(["build", "--lib"])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to build the library");
+
+    target_dir.join("debug")
+}
+
+#[test]
+fn capi_header_is_up_to_date() {
+    let config = cbindgen::Config::from_root_or_default(manifest_dir());
+    let bindings = cbindgen::generate_with_config(manifest_dir(), config).unwrap();
+    let mut header = Vec::new();
+    bindings.write(&mut header);
+
+    let expected = std::fs::read(manifest_dir().join("include/sonant.h")).unwrap();
+    assert!(
+        header == expected,
+        "include/sonant.h is out of date; regenerate it with cbindgen"
+    );
+}
+
+#[test]
+fn capi_render_matches_rust() {
+    let lib_dir = build_library();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi/render");
+
+    let status = Command::new("cc")
+        .arg(manifest_dir().join("tests/capi/render.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lsonant_capi", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to compile the C program");
+
+    let song_path = manifest_dir().join("../examples/poseidon.snt");
+    let output = Command::new(&program)
+        .arg(&song_path)
+        .arg(FRAMES.to_string())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "the C program failed");
+
+    let samples: Vec<_> = output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+        .collect();
+
+    let song = Song::from_slice(&std::fs::read(song_path).unwrap()).unwrap();
+    let mut expected = vec![0.0; FRAMES * 2];
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    assert_eq!(synth.render(&mut expected), FRAMES);
+    assert_eq!(samples, expected);
+}
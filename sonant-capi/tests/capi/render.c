@@ -0,0 +1,45 @@
+/* Render the first frames of a song with the C API, and write them to stdout as raw floats.
+ *
+ * Usage: render <file.snt> <frames>
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "sonant.h"
+
+#define SONG_LENGTH 3333
+
+int main(int argc, char **argv) {
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s <file.snt> <frames>\n", argv[0]);
+        return 1;
+    }
+
+    FILE *file = fopen(argv[1], "rb");
+    if (!file) {
+        perror("fopen");
+        return 1;
+    }
+    uint8_t data[SONG_LENGTH + 1];
+    size_t len = fread(data, 1, sizeof(data), file);
+    fclose(file);
+
+    SonantSong *song = sonant_song_from_bytes(data, len);
+    if (!song) {
+        fprintf(stderr, "invalid song\n");
+        return 1;
+    }
+    SonantSynth *synth = sonant_synth_new(song, 0, 1, 44100.0f);
+    sonant_song_free(song);
+
+    size_t frames = strtoul(argv[2], NULL, 10);
+    float *buffer = malloc(frames * 2 * sizeof(float));
+    size_t rendered = sonant_synth_render(synth, buffer, frames * 2);
+    fwrite(buffer, sizeof(float), rendered * 2, stdout);
+
+    free(buffer);
+    sonant_synth_free(synth);
+
+    return rendered == frames ? 0 : 1;
+}
@@ -0,0 +1,217 @@
+use sonant::{Error, InstrumentVoice, Param, Song, SynthState};
+
+/// The note value of MIDI note 0. MIDI note 69 (A4) plays note `144`.
+const MIDI_OFFSET: u8 = 75;
+
+/// Length of a `.snt` file.
+const SONG_LENGTH: usize = 3333;
+
+/// Number of instruments in a song.
+const NUM_INSTRUMENTS: usize = 8;
+
+/// Version of the format written by [`Instrument::save_state`].
+const STATE_VERSION: u8 = 1;
+
+/// Length of the state: the version, the instrument index, the parameters, and the song.
+const STATE_LENGTH: usize = 2 + Param::ALL.len() * 4 + SONG_LENGTH;
+
+/// Row length of the default song in samples, for 120 BPM.
+const DEFAULT_ROW_LENGTH: u32 = 5513;
+
+/// Seed for the noise oscillator.
+const SEED: (u64, u64) = (0, 1);
+
+/// One instrument of a song, played with MIDI notes. This is everything the plugin does, without
+/// the CLAP ABI.
+///
+/// ```
+/// use sonant::Param;
+/// use sonant_clap::Instrument;
+///
+/// let data = std::fs::read("../examples/poseidon.snt")?;
+/// let mut instrument = Instrument::new(&data, 1)?;
+/// instrument.set_sample_rate(48000.0);
+///
+/// // Play A4 with a slow attack
+/// instrument.set_param(Param::EnvAttack, 20000.0);
+/// instrument.note_on(69, 1.0);
+///
+/// let mut left = [0.0; 256];
+/// let mut right = [0.0; 256];
+/// instrument.render(&mut left, &mut right);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Instrument {
+    data: Vec<u8>, // The `.snt` file, without parameter changes
+    song: Song,
+    index: usize,
+    sample_rate: f32,
+    voice: InstrumentVoice<Song>,
+    silence: SynthState, // The state of the voice before any notes, for `reset`
+}
+
+impl Default for Instrument {
+    /// A plain two-oscillator instrument in an otherwise empty song.
+    fn default() -> Self {
+        let mut data = vec![0; SONG_LENGTH];
+        data[..4].copy_from_slice(&DEFAULT_ROW_LENGTH.to_le_bytes());
+        let mut instrument = Self::new(&data, 0).expect("the default song is valid");
+
+        for (param, value) in [
+            (Param::Osc0Octave, 8.0),
+            (Param::Osc0Volume, 192.0),
+            (Param::Osc0Waveform, 2.0),
+            (Param::Osc1Octave, 8.0),
+            (Param::Osc1Detune, 10.0),
+            (Param::Osc1Volume, 128.0),
+            (Param::Osc1Waveform, 2.0),
+            (Param::EnvAttack, 100.0),
+            (Param::EnvSustain, 10000.0),
+            (Param::EnvRelease, 20000.0),
+            (Param::EnvMaster, 128.0),
+            (Param::FxFilter, 2.0),
+            (Param::FxFreq, 4000.0),
+            (Param::FxResonance, 192.0),
+        ] {
+            instrument.set_param(param, value);
+        }
+
+        instrument
+    }
+}
+
+impl Instrument {
+    /// Load instrument `index` (`0..8`) of the `.snt` file in `data`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the song cannot be parsed, or `index` is not a valid instrument
+    /// index.
+    pub fn new(data: &[u8], index: usize) -> Result<Self, Error> {
+        if index >= NUM_INSTRUMENTS {
+            return Err(Error::InvalidInstruments);
+        }
+        let song = Song::from_slice(data)?;
+        let sample_rate = 44100.0;
+        let voice = InstrumentVoice::new(song.clone(), index, SEED, sample_rate);
+        let silence = voice.state();
+
+        Ok(Self {
+            data: data.to_vec(),
+            song,
+            index,
+            sample_rate,
+            voice,
+            silence,
+        })
+    }
+
+    /// Restore an instrument saved with [`Instrument::save_state`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the state cannot be parsed.
+    pub fn from_state(state: &[u8]) -> Result<Self, Error> {
+        if state.len() != STATE_LENGTH || state[0] != STATE_VERSION {
+            return Err(Error::InvalidState);
+        }
+
+        let (params, data) = state[2..].split_at(Param::ALL.len() * 4);
+        let mut instrument = Self::new(data, usize::from(state[1]))?;
+        for (&param, value) in Param::ALL.iter().zip(params.chunks_exact(4)) {
+            let value = f32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+            instrument.set_param(param, value);
+        }
+
+        Ok(instrument)
+    }
+
+    /// Save the instrument, including the parameters and the song it was loaded from.
+    #[must_use]
+    pub fn save_state(&self) -> Vec<u8> {
+        let params = Param::ALL.map(|param| self.param(param));
+
+        Self::encode_state(&self.data, self.index, &params)
+    }
+
+    /// Write the state of instrument `index` of the `.snt` file in `data`, with the values of
+    /// [`Param::ALL`] in `params`.
+    pub(crate) fn encode_state(data: &[u8], index: usize, params: &[f32]) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_LENGTH);
+        state.push(STATE_VERSION);
+        state.push(index as u8);
+        for value in params {
+            state.extend_from_slice(&value.to_le_bytes());
+        }
+        state.extend_from_slice(data);
+
+        state
+    }
+
+    /// The `.snt` file the instrument was loaded from, without parameter changes.
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The index of the instrument in its song.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The output sample rate, in Hz.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Set the output sample rate in Hz. This stops all notes.
+    ///
+    /// This allocates, so it should not be called on the audio thread.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.voice = InstrumentVoice::new(self.song.clone(), self.index, SEED, sample_rate);
+        self.silence = self.voice.state();
+    }
+
+    /// Stop all notes and their delayed echoes. This does not allocate, so it can be called on
+    /// the audio thread.
+    pub fn reset(&mut self) {
+        // The state was taken from the same voice, so it is always valid
+        let _ = self.voice.restore(&self.silence);
+    }
+
+    /// Start playing MIDI note `key`. `velocity` is in the range `0.0..=1.0`.
+    ///
+    /// Notes play for the length of the envelope, so there is no way to stop them early.
+    pub fn note_on(&mut self, key: u8, velocity: f32) {
+        self.voice.note_on(MIDI_OFFSET + key.min(127), velocity);
+    }
+
+    /// Check if any notes are playing, or have echoes waiting to be played.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.voice.is_playing()
+    }
+
+    /// Get the value of an instrument parameter. See [`Song::param`].
+    #[must_use]
+    pub fn param(&self, param: Param) -> f32 {
+        self.song.param(self.index, param)
+    }
+
+    /// Set an instrument parameter, including for notes that are already playing. See
+    /// [`Song::set_param`].
+    pub fn set_param(&mut self, param: Param, value: f32) {
+        self.song.set_param(self.index, param, value);
+        self.voice.set_param(param, value);
+    }
+
+    /// Render the next frames into the `left` and `right` channel buffers.
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        for (left, right) in left.iter_mut().zip(right) {
+            [*left, *right] = self.voice.next().unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! A [CLAP](https://cleveraudio.org/) instrument plugin that plays one instrument of a Sonant song.
+//!
+//! Every oscillator, envelope, effects, and LFO setting of the instrument is an automatable plugin
+//! parameter, in the units of the `.snt` file (see [`sonant::Param`]). Notes are played from CLAP
+//! or MIDI note-on events with the same voice code as [`sonant::Synth`]. Sonant notes always play
+//! for the length of their envelope, so note-off events are ignored.
+//!
+//! The plugin starts with a simple two-oscillator instrument. Load an instrument from a song with
+//! the preset-load extension, using the path to the `.snt` file as the location and the
+//! instrument index (`0..8`) as the load key. The song and all parameter values are saved in the
+//! plugin state.
+//!
+//! Build the plugin with `cargo build --release -p sonant-clap`, and copy the library to your
+//! CLAP directory as `sonant.clap`.
+
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_sign_loss)]
+#![deny(unsafe_code)]
+
+mod instrument;
+mod plugin;
+
+pub use instrument::Instrument;
+pub use plugin::clap_entry;
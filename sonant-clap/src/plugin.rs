@@ -0,0 +1,850 @@
+// The plugin is only reachable through raw pointers handed over the CLAP ABI. Pointers from the
+// host are checked for null, and are otherwise trusted.
+#![allow(unsafe_code)]
+
+use crate::instrument::Instrument;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_midi, clap_event_note, clap_event_param_value, clap_input_events,
+    clap_output_events, CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_NOTE_ON,
+    CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS,
+    CLAP_PORT_STEREO,
+};
+use clap_sys::ext::note_ports::{
+    clap_note_port_info, clap_plugin_note_ports, CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP,
+    CLAP_NOTE_DIALECT_MIDI,
+};
+use clap_sys::ext::params::{
+    clap_host_params, clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS,
+    CLAP_PARAM_IS_AUTOMATABLE, CLAP_PARAM_IS_ENUM, CLAP_PARAM_IS_STEPPED, CLAP_PARAM_RESCAN_VALUES,
+};
+use clap_sys::ext::preset_load::{
+    clap_host_preset_load, clap_plugin_preset_load, CLAP_EXT_PRESET_LOAD,
+};
+use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::factory::preset_discovery::{
+    clap_preset_discovery_location_kind, CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+};
+use clap_sys::host::clap_host;
+use clap_sys::id::{clap_id, CLAP_INVALID_ID};
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::plugin_features::{
+    CLAP_PLUGIN_FEATURE_INSTRUMENT, CLAP_PLUGIN_FEATURE_STEREO, CLAP_PLUGIN_FEATURE_SYNTHESIZER,
+};
+use clap_sys::process::{
+    clap_process, clap_process_status, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR,
+    CLAP_PROCESS_SLEEP,
+};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::CLAP_VERSION;
+use sonant::Param;
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt::Write as _;
+use std::ptr::{addr_of, null, null_mut};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+/// The list of plugin features, which is an array of pointers in a `static`.
+struct Features([*const c_char; 4]);
+
+// SAFETY: The pointers are to string literals
+unsafe impl Sync for Features {}
+
+static FEATURES: Features = Features([
+    CLAP_PLUGIN_FEATURE_INSTRUMENT.as_ptr(),
+    CLAP_PLUGIN_FEATURE_SYNTHESIZER.as_ptr(),
+    CLAP_PLUGIN_FEATURE_STEREO.as_ptr(),
+    null(),
+]);
+
+static DESCRIPTOR: clap_plugin_descriptor = clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"com.github.parasyte.sonant".as_ptr(),
+    name: c"Sonant".as_ptr(),
+    vendor: c"Jay Oster".as_ptr(),
+    url: c"https://github.com/parasyte/sonant-rs".as_ptr(),
+    manual_url: c"".as_ptr(),
+    support_url: c"".as_ptr(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+    description: c"Plays one instrument of a Sonant song".as_ptr(),
+    features: FEATURES.0.as_ptr(),
+};
+
+/// The entry point of the plugin library, which is looked up by CLAP hosts.
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: Some(entry_init),
+    deinit: Some(entry_deinit),
+    get_factory: Some(entry_get_factory),
+};
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: Some(factory_get_plugin_count),
+    get_plugin_descriptor: Some(factory_get_plugin_descriptor),
+    create_plugin: Some(factory_create_plugin),
+};
+
+static PARAMS: clap_plugin_params = clap_plugin_params {
+    count: Some(params_count),
+    get_info: Some(params_get_info),
+    get_value: Some(params_get_value),
+    value_to_text: Some(params_value_to_text),
+    text_to_value: Some(params_text_to_value),
+    flush: Some(params_flush),
+};
+
+static STATE: clap_plugin_state = clap_plugin_state {
+    save: Some(state_save),
+    load: Some(state_load),
+};
+
+static NOTE_PORTS: clap_plugin_note_ports = clap_plugin_note_ports {
+    count: Some(note_ports_count),
+    get: Some(note_ports_get),
+};
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: Some(audio_ports_count),
+    get: Some(audio_ports_get),
+};
+
+static PRESET_LOAD: clap_plugin_preset_load = clap_plugin_preset_load {
+    from_location: Some(preset_load_from_location),
+};
+
+/// Names of the values of waveform parameters.
+const WAVEFORMS: &[&str] = &["Sine", "Square", "Saw", "Triangle"];
+
+/// Names of the values of the filter parameter.
+const FILTERS: &[&str] = &["None", "High-pass", "Low-pass", "Band-pass", "Notch"];
+
+/// Names of the values of parameters that can only be on or off.
+const SWITCHES: &[&str] = &["Off", "On"];
+
+/// A plugin instance. The `clap_plugin` handed to the host points back to the instance with its
+/// `plugin_data`.
+///
+/// The instrument is only locked by the audio thread, and by the main thread while the plugin is
+/// inactive or to swap in a new instrument. Everything else the main thread needs is kept outside
+/// of that lock.
+struct Plugin {
+    clap: clap_plugin,
+    host: *const clap_host,
+    defaults: Vec<f32>,
+    params: ParamValues,
+    source: Mutex<Source>,
+    instrument: Mutex<Instrument>,
+}
+
+/// The song the instrument was loaded from, and the sample rate it plays at. Only the main thread
+/// uses it, to save the state and prepare new instruments.
+struct Source {
+    data: Vec<u8>,
+    index: usize,
+    sample_rate: f32,
+}
+
+impl Source {
+    fn new(instrument: &Instrument) -> Self {
+        Self {
+            data: instrument.data().to_vec(),
+            index: instrument.index(),
+            sample_rate: instrument.sample_rate(),
+        }
+    }
+}
+
+/// The current value of each parameter, indexed by parameter ID, which either thread can read
+/// and write without locking.
+///
+/// Changes that don't come from the audio thread are queued, and the audio thread applies them to
+/// the instrument at the start of the next block.
+struct ParamValues {
+    values: Vec<AtomicU32>, // `f32` bits
+    queued: Vec<AtomicBool>,
+}
+
+impl ParamValues {
+    fn new(instrument: &Instrument) -> Self {
+        Self {
+            values: Param::ALL
+                .iter()
+                .map(|&param| AtomicU32::new(instrument.param(param).to_bits()))
+                .collect(),
+            queued: Param::ALL.iter().map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+
+    fn get(&self, id: clap_id) -> f32 {
+        f32::from_bits(self.values[id as usize].load(Ordering::Relaxed))
+    }
+
+    /// Record a change that was already made to the instrument.
+    fn set(&self, id: clap_id, value: f32) {
+        self.values[id as usize].store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Queue a change for the audio thread.
+    fn queue(&self, id: clap_id, value: f32) {
+        self.set(id, value);
+        self.queued[id as usize].store(true, Ordering::Release);
+    }
+
+    /// Apply the queued changes to the instrument.
+    fn apply(&self, instrument: &mut Instrument) {
+        for (id, &param) in Param::ALL.iter().enumerate() {
+            if self.queued[id].swap(false, Ordering::Acquire) {
+                instrument.set_param(param, self.get(id as clap_id));
+            }
+        }
+    }
+
+    /// Take the values of a new instrument, dropping queued changes.
+    fn replace(&self, instrument: &Instrument) {
+        for (id, &param) in Param::ALL.iter().enumerate() {
+            self.queued[id].store(false, Ordering::Relaxed);
+            self.set(id as clap_id, instrument.param(param));
+        }
+    }
+}
+
+impl Plugin {
+    /// Get the instance of a `clap_plugin` created by the factory.
+    unsafe fn from_raw<'a>(plugin: *const clap_plugin) -> &'a Self {
+        &*(*plugin).plugin_data.cast::<Self>()
+    }
+
+    fn instrument(&self) -> MutexGuard<'_, Instrument> {
+        self.instrument
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn source(&self) -> MutexGuard<'_, Source> {
+        self.source.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Swap in an instrument that was prepared with the current sample rate. Notes that are
+    /// playing stop.
+    fn replace_instrument(&self, loaded: Instrument) {
+        let mut source = self.source();
+        *source = Source::new(&loaded);
+
+        let mut instrument = self.instrument();
+        self.params.replace(&loaded);
+        let old = std::mem::replace(&mut *instrument, loaded);
+        drop(instrument);
+
+        // The old instrument is freed after unlocking, so the audio thread is not held up
+        drop(old);
+    }
+
+    /// Lock the instrument without waiting, for the audio thread. Returns `None` while the main
+    /// thread holds the lock.
+    fn try_instrument(&self) -> Option<MutexGuard<'_, Instrument>> {
+        match self.instrument.try_lock() {
+            Ok(instrument) => Some(instrument),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Get a host extension, if the host supports it.
+    unsafe fn host_extension<T>(&self, id: &CStr) -> Option<&T> {
+        let get_extension = (*self.host).get_extension?;
+
+        get_extension(self.host, id.as_ptr()).cast::<T>().as_ref()
+    }
+}
+
+/// Get the parameter with the given ID. Parameter IDs are indices in [`Param::ALL`].
+fn param(id: clap_id) -> Option<Param> {
+    Param::ALL.get(id as usize).copied()
+}
+
+/// Names of the values of an enumerated parameter.
+fn value_names(param: Param) -> Option<&'static [&'static str]> {
+    match param {
+        Param::Osc0Waveform | Param::Osc1Waveform | Param::LfoWaveform => Some(WAVEFORMS),
+        Param::FxFilter => Some(FILTERS),
+        Param::Osc0Envelope | Param::Osc1Envelope | Param::LfoOsc0Freq | Param::LfoFxFreq => {
+            Some(SWITCHES)
+        }
+        _ => None,
+    }
+}
+
+/// Copy `text` into a C string buffer, truncating it to fit.
+fn write_c_str(buffer: &mut [c_char], text: &str) {
+    let len = text.len().min(buffer.len().saturating_sub(1));
+    for (dst, &src) in buffer.iter_mut().zip(&text.as_bytes()[..len]) {
+        *dst = c_char::from_ne_bytes([src]);
+    }
+    if let Some(end) = buffer.get_mut(len) {
+        *end = 0;
+    }
+}
+
+/// Call `f` with each event in the list.
+unsafe fn for_each_event(list: *const clap_input_events, mut f: impl FnMut(&clap_event_header)) {
+    let Some(events) = list.as_ref() else {
+        return;
+    };
+    let (Some(size), Some(get)) = (events.size, events.get) else {
+        return;
+    };
+
+    for index in 0..size(list) {
+        if let Some(header) = get(list, index).as_ref() {
+            f(header);
+        }
+    }
+}
+
+/// Get the parameter ID and value of a parameter change event.
+// Events are read as the type in their header, and hosts allocate them with the full alignment
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn param_change(header: &clap_event_header) -> Option<(clap_id, Param, f32)> {
+    if header.space_id != CLAP_CORE_EVENT_SPACE_ID || header.type_ != CLAP_EVENT_PARAM_VALUE {
+        return None;
+    }
+
+    let header: *const clap_event_header = header;
+    let event = &*header.cast::<clap_event_param_value>();
+    let param = param(event.param_id)?;
+
+    Some((event.param_id, param, event.value as f32))
+}
+
+/// Play a note or change a parameter.
+// Events are read as the type in their header, and hosts allocate them with the full alignment
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn handle_event(
+    instrument: &mut Instrument,
+    params: &ParamValues,
+    header: &clap_event_header,
+) {
+    if header.space_id != CLAP_CORE_EVENT_SPACE_ID {
+        return;
+    }
+    if let Some((id, param, value)) = param_change(header) {
+        instrument.set_param(param, value);
+        params.set(id, value);
+        return;
+    }
+
+    let header: *const clap_event_header = header;
+    match (*header).type_ {
+        CLAP_EVENT_NOTE_ON => {
+            let event = &*header.cast::<clap_event_note>();
+            if let Ok(key) = u8::try_from(event.key) {
+                instrument.note_on(key, event.velocity as f32);
+            }
+        }
+        CLAP_EVENT_MIDI => {
+            let [status, key, velocity] = (*header.cast::<clap_event_midi>()).data;
+            if status & 0xf0 == 0x90 && velocity > 0 {
+                instrument.note_on(key, f32::from(velocity) / 127.0);
+            }
+        }
+        _ => (),
+    }
+}
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if !factory_id.is_null() && CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+        addr_of!(FACTORY).cast()
+    } else {
+        null()
+    }
+}
+
+unsafe extern "C" fn factory_get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_get_plugin_descriptor(
+    _factory: *const clap_plugin_factory,
+    index: u32,
+) -> *const clap_plugin_descriptor {
+    if index == 0 {
+        &raw const DESCRIPTOR
+    } else {
+        null()
+    }
+}
+
+unsafe extern "C" fn factory_create_plugin(
+    _factory: *const clap_plugin_factory,
+    host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    if host.is_null()
+        || plugin_id.is_null()
+        || CStr::from_ptr(plugin_id) != CStr::from_ptr(DESCRIPTOR.id)
+    {
+        return null();
+    }
+
+    let instrument = Instrument::default();
+    let plugin = Box::into_raw(Box::new(Plugin {
+        clap: clap_plugin {
+            desc: &raw const DESCRIPTOR,
+            plugin_data: null_mut(),
+            init: Some(plugin_init),
+            destroy: Some(plugin_destroy),
+            activate: Some(plugin_activate),
+            deactivate: Some(plugin_deactivate),
+            start_processing: Some(plugin_start_processing),
+            stop_processing: Some(plugin_stop_processing),
+            reset: Some(plugin_reset),
+            process: Some(plugin_process),
+            get_extension: Some(plugin_get_extension),
+            on_main_thread: Some(plugin_on_main_thread),
+        },
+        host,
+        defaults: Param::ALL.iter().map(|&p| instrument.param(p)).collect(),
+        params: ParamValues::new(&instrument),
+        source: Mutex::new(Source::new(&instrument)),
+        instrument: Mutex::new(instrument),
+    }));
+    (*plugin).clap.plugin_data = plugin.cast();
+
+    &raw const (*plugin).clap
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(Box::from_raw((*plugin).plugin_data.cast::<Plugin>()));
+}
+
+unsafe extern "C" fn plugin_activate(
+    plugin: *const clap_plugin,
+    sample_rate: f64,
+    _min_frames_count: u32,
+    _max_frames_count: u32,
+) -> bool {
+    // The host doesn't process while the plugin is inactive, so the audio thread is not held up
+    let plugin = Plugin::from_raw(plugin);
+    plugin.source().sample_rate = sample_rate as f32;
+    plugin.instrument().set_sample_rate(sample_rate as f32);
+
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_reset(plugin: *const clap_plugin) {
+    if let Some(mut instrument) = Plugin::from_raw(plugin).try_instrument() {
+        instrument.reset();
+    }
+}
+
+unsafe extern "C" fn plugin_process(
+    plugin: *const clap_plugin,
+    process: *const clap_process,
+) -> clap_process_status {
+    let plugin = Plugin::from_raw(plugin);
+    let process = &*process;
+
+    let Some(output) = process.audio_outputs.as_ref() else {
+        return CLAP_PROCESS_ERROR;
+    };
+    if process.audio_outputs_count == 0 || output.channel_count < 2 || output.data32.is_null() {
+        return CLAP_PROCESS_ERROR;
+    }
+    let frames = process.frames_count as usize;
+    let left = std::slice::from_raw_parts_mut(*output.data32, frames);
+    let right = std::slice::from_raw_parts_mut(*output.data32.add(1), frames);
+
+    // The main thread is swapping in a new instrument, which stops all notes. Rather than waiting
+    // for it, the block is silent, notes in it are dropped, and parameter changes are queued for
+    // the next block.
+    let Some(mut instrument) = plugin.try_instrument() else {
+        left.fill(0.0);
+        right.fill(0.0);
+        for_each_event(process.in_events, |header| {
+            if let Some((id, _, value)) = param_change(header) {
+                plugin.params.queue(id, value);
+            }
+        });
+        return CLAP_PROCESS_CONTINUE;
+    };
+    plugin.params.apply(&mut instrument);
+
+    // Render up to each event, so notes and parameter changes are sample accurate
+    let mut start = 0;
+    for_each_event(process.in_events, |header| {
+        let time = (header.time as usize).clamp(start, frames);
+        instrument.render(&mut left[start..time], &mut right[start..time]);
+        start = time;
+        handle_event(&mut instrument, &plugin.params, header);
+    });
+    instrument.render(&mut left[start..], &mut right[start..]);
+
+    if instrument.is_playing() {
+        CLAP_PROCESS_CONTINUE
+    } else {
+        CLAP_PROCESS_SLEEP
+    }
+}
+
+unsafe extern "C" fn plugin_get_extension(
+    _plugin: *const clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    if id.is_null() {
+        return null();
+    }
+
+    let id = CStr::from_ptr(id);
+    if id == CLAP_EXT_PARAMS {
+        addr_of!(PARAMS).cast()
+    } else if id == CLAP_EXT_STATE {
+        addr_of!(STATE).cast()
+    } else if id == CLAP_EXT_NOTE_PORTS {
+        addr_of!(NOTE_PORTS).cast()
+    } else if id == CLAP_EXT_AUDIO_PORTS {
+        addr_of!(AUDIO_PORTS).cast()
+    } else if id == CLAP_EXT_PRESET_LOAD {
+        addr_of!(PRESET_LOAD).cast()
+    } else {
+        null()
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn params_count(_plugin: *const clap_plugin) -> u32 {
+    Param::ALL.len() as u32
+}
+
+unsafe extern "C" fn params_get_info(
+    plugin: *const clap_plugin,
+    param_index: u32,
+    param_info: *mut clap_param_info,
+) -> bool {
+    let plugin = Plugin::from_raw(plugin);
+    let (Some(param), Some(info)) = (param(param_index), param_info.as_mut()) else {
+        return false;
+    };
+
+    let mut flags = CLAP_PARAM_IS_AUTOMATABLE;
+    if param.is_stepped() {
+        flags |= CLAP_PARAM_IS_STEPPED;
+    }
+    if value_names(param).is_some() {
+        flags |= CLAP_PARAM_IS_ENUM;
+    }
+
+    let range = param.range();
+    info.id = param_index;
+    info.flags = flags;
+    info.cookie = null_mut();
+    write_c_str(
+        &mut info.name,
+        &format!("{} {}", param.group(), param.name()),
+    );
+    write_c_str(&mut info.module, param.group());
+    info.min_value = f64::from(*range.start());
+    info.max_value = f64::from(*range.end());
+    info.default_value = f64::from(plugin.defaults[param_index as usize]);
+
+    true
+}
+
+unsafe extern "C" fn params_get_value(
+    plugin: *const clap_plugin,
+    param_id: clap_id,
+    out_value: *mut f64,
+) -> bool {
+    let plugin = Plugin::from_raw(plugin);
+    let (Some(_), Some(out_value)) = (param(param_id), out_value.as_mut()) else {
+        return false;
+    };
+    *out_value = f64::from(plugin.params.get(param_id));
+
+    true
+}
+
+unsafe extern "C" fn params_value_to_text(
+    _plugin: *const clap_plugin,
+    param_id: clap_id,
+    value: f64,
+    out_buffer: *mut c_char,
+    out_buffer_capacity: u32,
+) -> bool {
+    let Some(param) = param(param_id) else {
+        return false;
+    };
+    if out_buffer.is_null() {
+        return false;
+    }
+
+    let mut text = String::new();
+    match value_names(param) {
+        Some(names) => {
+            let index = (value.round().max(0.0) as usize).min(names.len() - 1);
+            text.push_str(names[index]);
+        }
+        None if param == Param::FxFreq => write!(text, "{value:.0} Hz").unwrap(),
+        None => write!(text, "{}", value.round()).unwrap(),
+    }
+
+    let buffer = std::slice::from_raw_parts_mut(out_buffer, out_buffer_capacity as usize);
+    write_c_str(buffer, &text);
+
+    true
+}
+
+unsafe extern "C" fn params_text_to_value(
+    _plugin: *const clap_plugin,
+    param_id: clap_id,
+    param_value_text: *const c_char,
+    out_value: *mut f64,
+) -> bool {
+    let Some(param) = param(param_id) else {
+        return false;
+    };
+    if param_value_text.is_null() || out_value.is_null() {
+        return false;
+    }
+    let Ok(text) = CStr::from_ptr(param_value_text).to_str() else {
+        return false;
+    };
+    let text = text.trim();
+
+    let index = value_names(param).and_then(|names| {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+    });
+    let value = match index {
+        Some(index) => index as f64,
+        None => match text.trim_end_matches("Hz").trim_end().parse() {
+            Ok(value) => value,
+            Err(_) => return false,
+        },
+    };
+    *out_value = value;
+
+    true
+}
+
+unsafe extern "C" fn params_flush(
+    plugin: *const clap_plugin,
+    in_: *const clap_input_events,
+    _out: *const clap_output_events,
+) {
+    // This is called on the audio thread while the plugin is active, so the changes are queued
+    // instead of locking the instrument
+    let plugin = Plugin::from_raw(plugin);
+    for_each_event(in_, |header| {
+        if let Some((id, _, value)) = param_change(header) {
+            plugin.params.queue(id, value);
+        }
+    });
+}
+
+unsafe extern "C" fn state_save(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool {
+    let plugin = Plugin::from_raw(plugin);
+    let Some(write) = stream.as_ref().and_then(|stream| stream.write) else {
+        return false;
+    };
+
+    let params: Vec<f32> = (0..Param::ALL.len() as clap_id)
+        .map(|id| plugin.params.get(id))
+        .collect();
+    let source = plugin.source();
+    let state = Instrument::encode_state(&source.data, source.index, &params);
+    drop(source);
+    let mut buffer = state.as_slice();
+    while !buffer.is_empty() {
+        let written = write(stream, buffer.as_ptr().cast(), buffer.len() as u64);
+        if written <= 0 {
+            return false;
+        }
+        buffer = &buffer[written as usize..];
+    }
+
+    true
+}
+
+unsafe extern "C" fn state_load(plugin: *const clap_plugin, stream: *const clap_istream) -> bool {
+    let plugin = Plugin::from_raw(plugin);
+    let Some(read) = stream.as_ref().and_then(|stream| stream.read) else {
+        return false;
+    };
+
+    let mut state = Vec::new();
+    let mut buffer = [0_u8; 4096];
+    loop {
+        let len = read(stream, buffer.as_mut_ptr().cast(), buffer.len() as u64);
+        match len {
+            0 => break,
+            1.. => state.extend_from_slice(&buffer[..len as usize]),
+            _ => return false,
+        }
+    }
+
+    let Ok(mut loaded) = Instrument::from_state(&state) else {
+        return false;
+    };
+
+    // Prepare the instrument before taking the lock, so the audio thread is not held up
+    loaded.set_sample_rate(plugin.source().sample_rate);
+    plugin.replace_instrument(loaded);
+
+    true
+}
+
+unsafe extern "C" fn note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+    u32::from(is_input)
+}
+
+unsafe extern "C" fn note_ports_get(
+    _plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_note_port_info,
+) -> bool {
+    let Some(info) = info.as_mut() else {
+        return false;
+    };
+    if index != 0 || !is_input {
+        return false;
+    }
+
+    info.id = 0;
+    info.supported_dialects = CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI;
+    info.preferred_dialect = CLAP_NOTE_DIALECT_CLAP;
+    write_c_str(&mut info.name, "Notes");
+
+    true
+}
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+    u32::from(!is_input)
+}
+
+unsafe extern "C" fn audio_ports_get(
+    _plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_audio_port_info,
+) -> bool {
+    let Some(info) = info.as_mut() else {
+        return false;
+    };
+    if index != 0 || is_input {
+        return false;
+    }
+
+    info.id = 0;
+    write_c_str(&mut info.name, "Output");
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = CLAP_PORT_STEREO.as_ptr();
+    info.in_place_pair = CLAP_INVALID_ID;
+
+    true
+}
+
+unsafe extern "C" fn preset_load_from_location(
+    plugin: *const clap_plugin,
+    location_kind: clap_preset_discovery_location_kind,
+    location: *const c_char,
+    load_key: *const c_char,
+) -> bool {
+    let plugin = Plugin::from_raw(plugin);
+    let preset_load = plugin.host_extension::<clap_host_preset_load>(CLAP_EXT_PRESET_LOAD);
+
+    match load_preset(plugin, location_kind, location, load_key) {
+        Ok(()) => {
+            if let Some(params) = plugin.host_extension::<clap_host_params>(CLAP_EXT_PARAMS) {
+                if let Some(rescan) = params.rescan {
+                    rescan(plugin.host, CLAP_PARAM_RESCAN_VALUES);
+                }
+            }
+            if let Some(loaded) = preset_load.and_then(|preset_load| preset_load.loaded) {
+                loaded(plugin.host, location_kind, location, load_key);
+            }
+
+            true
+        }
+        Err((os_error, msg)) => {
+            if let Some(on_error) = preset_load.and_then(|preset_load| preset_load.on_error) {
+                let msg = msg.replace('\0', " ") + "\0";
+                on_error(
+                    plugin.host,
+                    location_kind,
+                    location,
+                    load_key,
+                    os_error,
+                    msg.as_ptr().cast(),
+                );
+            }
+
+            false
+        }
+    }
+}
+
+/// Load an instrument from the `.snt` file at `location`. The instrument index is in `load_key`,
+/// or the first instrument is loaded when there is no key.
+///
+/// Errors are returned as an OS error code and a message for the host.
+unsafe fn load_preset(
+    plugin: &Plugin,
+    location_kind: clap_preset_discovery_location_kind,
+    location: *const c_char,
+    load_key: *const c_char,
+) -> Result<(), (i32, String)> {
+    if location_kind != CLAP_PRESET_DISCOVERY_LOCATION_FILE || location.is_null() {
+        return Err((0, "Presets can only be loaded from files".to_string()));
+    }
+
+    let path = CStr::from_ptr(location)
+        .to_str()
+        .map_err(|_| (0, "Invalid path".to_string()))?;
+    let index = if load_key.is_null() {
+        0
+    } else {
+        CStr::from_ptr(load_key)
+            .to_str()
+            .ok()
+            .and_then(|key| key.parse().ok())
+            .ok_or_else(|| (0, "Invalid instrument index".to_string()))?
+    };
+
+    let data =
+        std::fs::read(path).map_err(|err| (err.raw_os_error().unwrap_or(0), err.to_string()))?;
+    let mut loaded = Instrument::new(&data, index).map_err(|err| (0, err.to_string()))?;
+
+    // Prepare the instrument before taking the lock, so the audio thread is not held up
+    loaded.set_sample_rate(plugin.source().sample_rate);
+    plugin.replace_instrument(loaded);
+
+    Ok(())
+}
@@ -0,0 +1,565 @@
+//! Tests for the plugin, through the CLAP ABI with a minimal host.
+
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::events::{
+    clap_event_header, clap_event_midi, clap_event_note, clap_event_param_value, clap_input_events,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_NOTE_ON, CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::params::{
+    clap_host_params, clap_param_info, clap_param_rescan_flags, clap_plugin_params,
+    CLAP_EXT_PARAMS, CLAP_PARAM_IS_ENUM, CLAP_PARAM_IS_STEPPED,
+};
+use clap_sys::ext::preset_load::{clap_plugin_preset_load, CLAP_EXT_PRESET_LOAD};
+use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::factory::preset_discovery::CLAP_PRESET_DISCOVERY_LOCATION_FILE;
+use clap_sys::host::clap_host;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{
+    clap_process, clap_process_status, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_SLEEP,
+};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::CLAP_VERSION;
+use sonant::{InstrumentVoice, Param, Song};
+use sonant_clap::clap_entry;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::mem::size_of;
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const POSEIDON: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples/poseidon.snt");
+const PLUGIN_ID: &[u8] = b"com.github.parasyte.sonant\0";
+
+/// A host that counts parameter rescans.
+struct Host {
+    clap: clap_host,
+    rescans: AtomicU32,
+}
+
+static HOST_PARAMS: clap_host_params = clap_host_params {
+    rescan: Some(host_params_rescan),
+    clear: None,
+    request_flush: None,
+};
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    id: *const c_char,
+) -> *const c_void {
+    if CStr::from_ptr(id) == CLAP_EXT_PARAMS {
+        (&HOST_PARAMS as *const clap_host_params).cast()
+    } else {
+        null()
+    }
+}
+
+unsafe extern "C" fn host_params_rescan(host: *const clap_host, _flags: clap_param_rescan_flags) {
+    let host = &*(*host).host_data.cast::<Host>();
+    host.rescans.fetch_add(1, Ordering::Relaxed);
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+impl Host {
+    fn new() -> Box<Self> {
+        let mut host = Box::new(Self {
+            clap: clap_host {
+                clap_version: CLAP_VERSION,
+                host_data: null_mut(),
+                name: c"test".as_ptr(),
+                vendor: c"".as_ptr(),
+                url: c"".as_ptr(),
+                version: c"0".as_ptr(),
+                get_extension: Some(host_get_extension),
+                request_restart: Some(host_request),
+                request_process: Some(host_request),
+                request_callback: Some(host_request),
+            },
+            rescans: AtomicU32::new(0),
+        });
+        host.clap.host_data = (&mut *host as *mut Self).cast();
+
+        host
+    }
+}
+
+/// An input event for the plugin.
+enum Event {
+    Note(clap_event_note),
+    Midi(clap_event_midi),
+    Param(clap_event_param_value),
+}
+
+impl Event {
+    fn header(type_: u16, size: usize, time: u32) -> clap_event_header {
+        clap_event_header {
+            size: size as u32,
+            time,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_,
+            flags: 0,
+        }
+    }
+
+    fn note_on(time: u32, key: i16, velocity: f64) -> Self {
+        Self::Note(clap_event_note {
+            header: Self::header(CLAP_EVENT_NOTE_ON, size_of::<clap_event_note>(), time),
+            note_id: -1,
+            port_index: 0,
+            channel: 0,
+            key,
+            velocity,
+        })
+    }
+
+    fn midi(time: u32, data: [u8; 3]) -> Self {
+        Self::Midi(clap_event_midi {
+            header: Self::header(CLAP_EVENT_MIDI, size_of::<clap_event_midi>(), time),
+            port_index: 0,
+            data,
+        })
+    }
+
+    fn param(time: u32, param: Param, value: f64) -> Self {
+        let param_id = Param::ALL.iter().position(|&p| p == param).unwrap() as u32;
+        Self::Param(clap_event_param_value {
+            header: Self::header(
+                CLAP_EVENT_PARAM_VALUE,
+                size_of::<clap_event_param_value>(),
+                time,
+            ),
+            param_id,
+            cookie: null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value,
+        })
+    }
+
+    fn as_header(&self) -> *const clap_event_header {
+        match self {
+            Self::Note(event) => &event.header,
+            Self::Midi(event) => &event.header,
+            Self::Param(event) => &event.header,
+        }
+    }
+}
+
+unsafe extern "C" fn events_size(list: *const clap_input_events) -> u32 {
+    let events = &*(*list).ctx.cast::<Vec<Event>>();
+    events.len() as u32
+}
+
+unsafe extern "C" fn events_get(
+    list: *const clap_input_events,
+    index: u32,
+) -> *const clap_event_header {
+    let events = &*(*list).ctx.cast::<Vec<Event>>();
+    events[index as usize].as_header()
+}
+
+fn input_events(events: &mut Vec<Event>) -> clap_input_events {
+    clap_input_events {
+        ctx: (events as *mut Vec<Event>).cast(),
+        size: Some(events_size),
+        get: Some(events_get),
+    }
+}
+
+unsafe extern "C" fn ostream_write(
+    stream: *const clap_ostream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    // Write at most 100 bytes at a time, to test partial writes
+    let size = size.min(100) as usize;
+    let data = &mut *(*stream).ctx.cast::<Vec<u8>>();
+    data.extend_from_slice(std::slice::from_raw_parts(buffer.cast(), size));
+
+    size as i64
+}
+
+unsafe extern "C" fn istream_read(
+    stream: *const clap_istream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let data = &mut *(*stream).ctx.cast::<&[u8]>();
+    let size = (size as usize).min(data.len());
+    std::ptr::copy_nonoverlapping(data.as_ptr(), buffer.cast(), size);
+    *data = &data[size..];
+
+    size as i64
+}
+
+/// A plugin instance created through the entry point.
+struct Plugin {
+    plugin: *const clap_plugin,
+    host: Box<Host>,
+}
+
+impl Plugin {
+    fn new() -> Self {
+        let host = Host::new();
+        let plugin = unsafe {
+            let factory = factory();
+            let create_plugin = factory.create_plugin.unwrap();
+            create_plugin(factory, &host.clap, PLUGIN_ID.as_ptr().cast())
+        };
+        assert!(!plugin.is_null());
+
+        unsafe {
+            assert!((*plugin).init.unwrap()(plugin));
+            assert!((*plugin).activate.unwrap()(plugin, 44100.0, 1, 4096));
+            assert!((*plugin).start_processing.unwrap()(plugin));
+        }
+
+        Self { plugin, host }
+    }
+
+    fn extension<T>(&self, id: &CStr) -> &T {
+        unsafe {
+            let get_extension = (*self.plugin).get_extension.unwrap();
+            &*get_extension(self.plugin, id.as_ptr()).cast::<T>()
+        }
+    }
+
+    /// Load an instrument from a song file with the preset-load extension.
+    fn load_preset(&self, path: &str, instrument: &str) -> bool {
+        let preset_load = self.extension::<clap_plugin_preset_load>(CLAP_EXT_PRESET_LOAD);
+        let path = CString::new(path).unwrap();
+        let key = CString::new(instrument).unwrap();
+
+        unsafe {
+            preset_load.from_location.unwrap()(
+                self.plugin,
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                path.as_ptr(),
+                key.as_ptr(),
+            )
+        }
+    }
+
+    /// Process one block of `frames` frames, returning the status and the stereo output.
+    fn process(
+        &self,
+        frames: usize,
+        mut events: Vec<Event>,
+    ) -> (clap_process_status, Vec<[f32; 2]>) {
+        let mut left = vec![f32::NAN; frames];
+        let mut right = vec![f32::NAN; frames];
+        let mut channels = [left.as_mut_ptr(), right.as_mut_ptr()];
+        let mut output = clap_audio_buffer {
+            data32: channels.as_mut_ptr(),
+            data64: null_mut(),
+            channel_count: 2,
+            latency: 0,
+            constant_mask: 0,
+        };
+        let in_events = input_events(&mut events);
+        let process = clap_process {
+            steady_time: -1,
+            frames_count: frames as u32,
+            transport: null(),
+            audio_inputs: null(),
+            audio_outputs: &mut output,
+            audio_inputs_count: 0,
+            audio_outputs_count: 1,
+            in_events: &in_events,
+            out_events: null(),
+        };
+
+        let status = unsafe { (*self.plugin).process.unwrap()(self.plugin, &process) };
+        let frames = left.into_iter().zip(right).map(|(l, r)| [l, r]).collect();
+
+        (status, frames)
+    }
+
+    fn reset(&self) {
+        unsafe { (*self.plugin).reset.unwrap()(self.plugin) };
+    }
+
+    fn params(&self) -> &clap_plugin_params {
+        self.extension(CLAP_EXT_PARAMS)
+    }
+
+    fn param(&self, param: Param) -> f64 {
+        let id = Param::ALL.iter().position(|&p| p == param).unwrap() as u32;
+        let mut value = f64::NAN;
+        unsafe {
+            assert!(self.params().get_value.unwrap()(
+                self.plugin,
+                id,
+                &mut value
+            ))
+        };
+
+        value
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let state = self.extension::<clap_plugin_state>(CLAP_EXT_STATE);
+        let mut data = Vec::new();
+        let stream = clap_ostream {
+            ctx: (&mut data as *mut Vec<u8>).cast(),
+            write: Some(ostream_write),
+        };
+        unsafe { assert!(state.save.unwrap()(self.plugin, &stream)) };
+
+        data
+    }
+
+    fn load_state(&self, mut data: &[u8]) -> bool {
+        let state = self.extension::<clap_plugin_state>(CLAP_EXT_STATE);
+        let stream = clap_istream {
+            ctx: (&mut data as *mut &[u8]).cast(),
+            read: Some(istream_read),
+        };
+
+        unsafe { state.load.unwrap()(self.plugin, &stream) }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe {
+            (*self.plugin).stop_processing.unwrap()(self.plugin);
+            (*self.plugin).deactivate.unwrap()(self.plugin);
+            (*self.plugin).destroy.unwrap()(self.plugin);
+        }
+    }
+}
+
+unsafe fn factory() -> &'static clap_plugin_factory {
+    assert!(clap_entry.init.unwrap()(c"".as_ptr()));
+    let factory = clap_entry.get_factory.unwrap()(CLAP_PLUGIN_FACTORY_ID.as_ptr());
+
+    &*factory.cast::<clap_plugin_factory>()
+}
+
+/// Render a note on instrument 1 of Poseidon with the voice code directly, after `start` frames
+/// of silence. The LFO and panning continue during the silence.
+fn poseidon_note(start: usize, pitch: u8, frames: usize) -> Vec<[f32; 2]> {
+    let song = Song::from_slice(&std::fs::read(POSEIDON).unwrap()).unwrap();
+    let mut voice = InstrumentVoice::new(song, 1, (0, 1), 44100.0);
+    voice.by_ref().take(start).for_each(drop);
+    voice.note_on(pitch, 1.0);
+
+    voice.take(frames).collect()
+}
+
+#[test]
+fn plugin_factory() {
+    unsafe {
+        let factory = factory();
+        assert_eq!(factory.get_plugin_count.unwrap()(factory), 1);
+        assert!(factory.get_plugin_descriptor.unwrap()(factory, 1).is_null());
+
+        let descriptor = &*factory.get_plugin_descriptor.unwrap()(factory, 0);
+        assert_eq!(CStr::from_ptr(descriptor.id).to_bytes_with_nul(), PLUGIN_ID);
+        assert_eq!(CStr::from_ptr(descriptor.name).to_str(), Ok("Sonant"));
+        assert_eq!(
+            CStr::from_ptr(*descriptor.features).to_str(),
+            Ok("instrument")
+        );
+
+        let host = Host::new();
+        let other = c"com.example.other".as_ptr();
+        assert!(factory.create_plugin.unwrap()(factory, &host.clap, other).is_null());
+        assert!(clap_entry.get_factory.unwrap()(c"clap.other".as_ptr()).is_null());
+    }
+}
+
+#[test]
+fn plugin_note_on_matches_voice() {
+    let plugin = Plugin::new();
+    assert!(plugin.load_preset(POSEIDON, "1"));
+    assert_eq!(plugin.host.rescans.load(Ordering::Relaxed), 1);
+
+    let (_, output) = plugin.process(4096, vec![Event::note_on(0, 69, 1.0)]);
+    assert_eq!(output, poseidon_note(0, 144, 4096));
+}
+
+#[test]
+fn plugin_events_are_sample_accurate() {
+    let plugin = Plugin::new();
+    assert!(plugin.load_preset(POSEIDON, "1"));
+
+    let (_, output) = plugin.process(1024, vec![Event::note_on(100, 69, 1.0)]);
+    assert!(output[..100].iter().all(|&frame| frame == [0.0; 2]));
+    assert_eq!(output[100..], poseidon_note(100, 144, 924));
+}
+
+#[test]
+fn plugin_reset_stops_notes() {
+    let plugin = Plugin::new();
+    assert!(plugin.load_preset(POSEIDON, "1"));
+
+    let (status, _) = plugin.process(1024, vec![Event::note_on(0, 69, 1.0)]);
+    assert_eq!(status, CLAP_PROCESS_CONTINUE);
+    plugin.reset();
+    let (status, output) = plugin.process(256, Vec::new());
+    assert_eq!(status, CLAP_PROCESS_SLEEP);
+    assert!(output.iter().all(|&frame| frame == [0.0; 2]));
+
+    // The voice starts over, including the LFO and the noise generator
+    plugin.reset();
+    let (_, output) = plugin.process(4096, vec![Event::note_on(0, 69, 1.0)]);
+    assert_eq!(output, poseidon_note(0, 144, 4096));
+}
+
+#[test]
+fn plugin_midi_notes() {
+    let plugin = Plugin::new();
+    assert!(plugin.load_preset(POSEIDON, "1"));
+
+    // A note-on with zero velocity is a note-off, and notes can't be stopped
+    let (status, output) = plugin.process(256, vec![Event::midi(0, [0x90, 69, 0])]);
+    assert_eq!(status, CLAP_PROCESS_SLEEP);
+    assert!(output.iter().all(|&frame| frame == [0.0; 2]));
+
+    let (_, output) = plugin.process(4096, vec![Event::midi(0, [0x91, 57, 127])]);
+    assert_eq!(output, poseidon_note(256, 132, 4096));
+}
+
+#[test]
+fn plugin_param_automation() {
+    let plugin = Plugin::new();
+
+    let events = vec![
+        Event::param(0, Param::Osc0Volume, 0.0),
+        Event::param(0, Param::Osc1Volume, 0.0),
+        Event::note_on(0, 60, 1.0),
+    ];
+    let (_, output) = plugin.process(1024, events);
+    assert_eq!(plugin.param(Param::Osc0Volume), 0.0);
+    assert!(output.iter().all(|&frame| frame == [0.0; 2]));
+
+    // Parameters change for notes that are already playing
+    let (_, output) = plugin.process(1024, vec![Event::param(512, Param::Osc0Volume, 255.0)]);
+    assert!(output[..512].iter().all(|&frame| frame == [0.0; 2]));
+    assert!(output[512..].iter().any(|&frame| frame != [0.0; 2]));
+
+    // Parameters can also change without processing
+    let mut events = vec![Event::param(0, Param::FxFreq, 1234.5)];
+    let in_events = input_events(&mut events);
+    unsafe { plugin.params().flush.unwrap()(plugin.plugin, &in_events, null()) };
+    assert_eq!(plugin.param(Param::FxFreq), 1234.5);
+    assert_ne!(plugin.save_state(), Plugin::new().save_state());
+}
+
+#[test]
+fn plugin_flushed_params_apply_to_the_next_block() {
+    let flushed = Plugin::new();
+    let mut events = vec![Event::param(0, Param::FxFreq, 300.0)];
+    let in_events = input_events(&mut events);
+    unsafe { flushed.params().flush.unwrap()(flushed.plugin, &in_events, null()) };
+
+    let processed = Plugin::new();
+    let note = || vec![Event::note_on(0, 60, 1.0)];
+    let (_, expected) = processed.process(
+        4096,
+        vec![
+            Event::param(0, Param::FxFreq, 300.0),
+            Event::note_on(0, 60, 1.0),
+        ],
+    );
+    assert_ne!(expected, Plugin::new().process(4096, note()).1);
+    assert_eq!(flushed.process(4096, note()).1, expected);
+}
+
+#[test]
+fn plugin_param_info() {
+    let plugin = Plugin::new();
+    let params = plugin.params();
+    assert_eq!(unsafe { params.count.unwrap()(plugin.plugin) }, 29);
+
+    let info = |id| unsafe {
+        let mut info: clap_param_info = std::mem::zeroed();
+        assert!(params.get_info.unwrap()(plugin.plugin, id, &mut info));
+        let name = CStr::from_ptr(info.name.as_ptr())
+            .to_str()
+            .unwrap()
+            .to_string();
+        let module = CStr::from_ptr(info.module.as_ptr())
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        (info, name, module)
+    };
+
+    let (waveform, name, module) = info(5);
+    assert_eq!(name, "Oscillator 0 Waveform");
+    assert_eq!(module, "Oscillator 0");
+    assert_eq!(waveform.flags & CLAP_PARAM_IS_ENUM, CLAP_PARAM_IS_ENUM);
+    assert_eq!((waveform.min_value, waveform.max_value), (0.0, 3.0));
+
+    let (freq, name, _) = info(18);
+    assert_eq!(name, "Effects Frequency");
+    assert_eq!(freq.flags & CLAP_PARAM_IS_STEPPED, 0);
+    assert_eq!(freq.default_value, plugin.param(Param::FxFreq));
+
+    let text = |id, value| unsafe {
+        let mut buffer = [0; 64];
+        let value_to_text = params.value_to_text.unwrap();
+        assert!(value_to_text(
+            plugin.plugin,
+            id,
+            value,
+            buffer.as_mut_ptr(),
+            64
+        ));
+        CStr::from_ptr(buffer.as_ptr())
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(text(5, 2.0), "Saw");
+    assert_eq!(text(17, 4.0), "Notch");
+    assert_eq!(text(18, 1234.4), "1234 Hz");
+    assert_eq!(text(13, 100.0), "100");
+
+    let value = |id, text: &str| unsafe {
+        let text = CString::new(text).unwrap();
+        let mut value = f64::NAN;
+        let text_to_value = params.text_to_value.unwrap();
+        text_to_value(plugin.plugin, id, text.as_ptr(), &mut value).then_some(value)
+    };
+    assert_eq!(value(5, "triangle"), Some(3.0));
+    assert_eq!(value(18, "880 Hz"), Some(880.0));
+    assert_eq!(value(18, "loud"), None);
+    assert_eq!(value(29, "1"), None);
+}
+
+#[test]
+fn plugin_state_round_trip() {
+    let plugin = Plugin::new();
+    assert!(plugin.load_preset(POSEIDON, "3"));
+    plugin.process(1, vec![Event::param(0, Param::FxResonance, 12.0)]);
+    let state = plugin.save_state();
+
+    let restored = Plugin::new();
+    assert!(!restored.load_state(&state[1..]));
+    assert!(restored.load_state(&state));
+    for param in Param::ALL {
+        assert_eq!(restored.param(param), plugin.param(param), "{param:?}");
+    }
+    assert_eq!(restored.param(Param::FxResonance), 12.0);
+    // Catch up with the block that changed the parameter
+    restored.process(1, vec![]);
+
+    let note = || vec![Event::note_on(0, 72, 0.5)];
+    assert_eq!(restored.process(4096, note()), plugin.process(4096, note()));
+}
+
+#[test]
+fn plugin_preset_load_errors() {
+    let plugin = Plugin::new();
+    assert!(!plugin.load_preset("missing.snt", "0"));
+    assert!(!plugin.load_preset(POSEIDON, "8"));
+    assert!(!plugin.load_preset(POSEIDON, "first"));
+    assert_eq!(plugin.host.rescans.load(Ordering::Relaxed), 0);
+}
@@ -1,8 +1,13 @@
 pub(crate) const NUM_CHANNELS: usize = 2;
+/// The maximum number of channels supported by [`Layout::Multichannel`](crate::Layout).
+pub const MAX_CHANNELS: usize = 8;
 pub(crate) const NUM_INSTRUMENTS: usize = 8;
 pub(crate) const NUM_PATTERNS: usize = 10;
 
-pub(crate) const MAX_OVERLAPPING_NOTES: usize = 8;
+/// The maximum number of notes (voices) that can play at once on each instrument track.
+pub const MAX_OVERLAPPING_NOTES: usize = 8;
+pub(crate) const MAX_TRIGGERED_NOTES: usize = 16;
+pub(crate) const MAX_JUMPS: usize = 16;
 
 pub(crate) const HEADER_LENGTH: usize = 4;
 pub(crate) const INSTRUMENT_LENGTH: usize = 0x1a0;
@@ -0,0 +1,76 @@
+use core::time::Duration;
+
+/// Playback position of a [`Synth`](crate::Synth).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Position {
+    /// Index into the pattern sequence.
+    pub sequence: usize,
+
+    /// Row (quarter note) within the current pattern.
+    pub row: usize,
+
+    /// Samples elapsed since the start of the current row.
+    pub row_sample: u32,
+
+    /// Samples elapsed since the start of the song.
+    pub sample: u32,
+
+    /// Time elapsed since the start of the song.
+    pub elapsed: Duration,
+}
+
+/// Playback events, for synchronizing visuals with the music.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A pattern starts at the given sequence index.
+    Pattern {
+        /// Index into the pattern sequence.
+        sequence: usize,
+    },
+
+    /// A row starts.
+    Row {
+        /// Index into the pattern sequence.
+        sequence: usize,
+
+        /// Row within the pattern.
+        row: usize,
+    },
+
+    /// The last pattern in the sequence has ended. Notes that are still playing (and their
+    /// delayed echoes) continue until they are silent.
+    End,
+
+    /// A note starts.
+    Note {
+        /// Instrument track index.
+        track: usize,
+
+        /// Note pitch, on the same scale as the patterns.
+        pitch: u8,
+
+        /// `true` when the note is a delayed echo of an earlier note.
+        echo: bool,
+    },
+}
+
+/// Receives [`Event`]s from a [`Synth`](crate::Synth).
+///
+/// Events are sent during the call that generates the sample where the event occurs, before the
+/// sample is generated. `position` is the position of that sample.
+///
+/// This is implemented for closures, so the easiest way to receive events is with a closure like
+/// `|position, event| { ... }`.
+pub trait EventSink {
+    /// Receive an event.
+    fn event(&mut self, position: &Position, event: Event);
+}
+
+impl<F> EventSink for F
+where
+    F: FnMut(&Position, Event),
+{
+    fn event(&mut self, position: &Position, event: Event) {
+        self(position, event);
+    }
+}
@@ -0,0 +1,22 @@
+//! Render songs to audio files.
+//!
+//! This module is only available with the `std` feature.
+
+#[cfg(feature = "flac")]
+pub mod flac;
+#[cfg(feature = "flac")]
+mod md5;
+pub mod wav;
+
+use crate::sample::{self, Sample};
+use randomize::PCG32;
+
+/// Create the random number generator for dither, if it is `enabled`.
+fn dither(enabled: bool, seed: (u64, u64)) -> Option<PCG32> {
+    enabled.then(|| sample::dither_generator(seed))
+}
+
+/// Convert a `sample` to the integer format `T`, optionally with dither.
+fn quantize<T: Sample>(sample: f32, dither: Option<&mut PCG32>) -> T {
+    T::from_f32(sample, dither.map_or(0.0, sample::tpdf))
+}
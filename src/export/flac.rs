@@ -0,0 +1,875 @@
+//! Write songs to FLAC files.
+//!
+//! This module is only available with the `flac` feature. The encoder is written in pure Rust.
+//! It uses fixed and linear predictive (LPC) coding, with the best stereo decorrelation for each
+//! block, and it streams blocks to the writer as they are encoded.
+//!
+//! ```no_run
+//! use sonant::export::flac::{self, Format, Options};
+//! use sonant::{Song, Synth};
+//! use std::fs::File;
+//!
+//! let song = Song::from_slice(include_bytes!("../../examples/poseidon.snt"))?;
+//! let synth = Synth::new(&song, (0, 1), 48000.0);
+//! let options = Options {
+//!     format: Format::Int24,
+//!     ..Options::default()
+//! };
+//! flac::write(synth, File::create("poseidon.flac")?, &options)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::md5::Md5;
+use super::{dither, quantize};
+use crate::consts::NUM_CHANNELS;
+use crate::sample::I24;
+use crate::song::Song;
+use crate::synth::Synth;
+use arrayvec::ArrayVec;
+use core::borrow::Borrow;
+use randomize::PCG32;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+/// Sample format of a FLAC file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// 16-bit samples.
+    #[default]
+    Int16,
+
+    /// 24-bit samples.
+    Int24,
+}
+
+impl Format {
+    fn bits(self) -> u32 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+        }
+    }
+
+    /// Sample size code in the frame header.
+    fn code(self) -> u64 {
+        match self {
+            Self::Int16 => 0b100,
+            Self::Int24 => 0b110,
+        }
+    }
+}
+
+/// Options for [`write()`] and [`Encoder`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// Sample format. The default is [`Format::Int16`].
+    pub format: Format,
+
+    /// Add triangular (TPDF) dither when quantizing. The default is `false`.
+    pub dither: bool,
+
+    /// Seed for the dither. The default is `(0, 1)`.
+    pub seed: (u64, u64),
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            dither: false,
+            seed: (0, 1),
+        }
+    }
+}
+
+/// Number of samples per channel in each block, except the last one.
+const BLOCK_SIZE: usize = 4096;
+
+/// Block size code in the frame header for [`BLOCK_SIZE`].
+const BLOCK_SIZE_CODE: u64 = 0b1100;
+
+/// Block size code for an explicit 16-bit block size at the end of the frame header.
+const BLOCK_SIZE_CODE_16_BIT: u64 = 0b0111;
+
+/// Channel assignments in the frame header.
+const INDEPENDENT: u64 = 0b0001;
+const LEFT_SIDE: u64 = 0b1000;
+const SIDE_RIGHT: u64 = 0b1001;
+const MID_SIDE: u64 = 0b1010;
+
+/// Highest order of the linear predictor.
+const MAX_LPC_ORDER: usize = 12;
+
+/// Precision of the quantized LPC coefficients, in bits.
+const LPC_PRECISION: u32 = 15;
+
+/// Highest partition order of the residual.
+const MAX_PARTITION_ORDER: u32 = 8;
+
+/// Coefficients of the fixed predictors, for orders 0 to 4.
+const FIXED_COEFFICIENTS: [&[i32]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+/// Length of the metadata block header and the STREAMINFO block, in bytes.
+const STREAMINFO_LENGTH: usize = 4 + 34;
+
+/// Encodes stereo frames to a FLAC stream.
+///
+/// Samples are buffered until a block is complete, and each block is written as soon as it is
+/// encoded. The stream header is written first, and patched with the length of the stream and
+/// its MD5 signature by [`Encoder::finish`].
+///
+/// ```
+/// use sonant::export::flac::{Encoder, Options};
+/// use sonant::{Song, Synth};
+/// use std::io::Cursor;
+///
+/// let song = Song::from_slice(include_bytes!("../../examples/poseidon.snt"))?;
+/// let synth = Synth::new(&song, (0, 1), 44100.0);
+///
+/// // Encode the first second of the song
+/// let mut encoder = Encoder::new(Cursor::new(Vec::new()), 44100, &Options::default())?;
+/// let frames = synth.take(44100).collect::<Vec<_>>();
+/// encoder.write(&frames)?;
+/// let file = encoder.finish()?.into_inner();
+/// assert_eq!(&file[..4], b"fLaC");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Encoder<W: Write + Seek> {
+    writer: BufWriter<W>,
+    start: u64, // Stream position of the header
+    sample_rate: u32,
+    format: Format,
+    dither: Option<PCG32>,
+
+    // Samples of the current block
+    channels: [Vec<i32>; NUM_CHANNELS],
+
+    // Stream statistics
+    md5: Md5,
+    frames: u64,
+    frame_number: u32,
+    min_frame_size: u32,
+    max_frame_size: u32,
+}
+
+impl<W: Write + Seek> Encoder<W> {
+    /// Create an encoder, and write the stream header to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Errors from `writer` are returned. An error is also returned when the sample rate is not
+    /// supported by FLAC (above 655,350 Hz or zero).
+    pub fn new(writer: W, sample_rate: u32, options: &Options) -> io::Result<Self> {
+        if sample_rate == 0 || sample_rate > 655_350 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported sample rate",
+            ));
+        }
+
+        let mut writer = BufWriter::new(writer);
+        let start = writer.stream_position()?;
+
+        let mut encoder = Self {
+            writer,
+            start,
+            sample_rate,
+            format: options.format,
+            dither: dither(options.dither, options.seed),
+            channels: [
+                Vec::with_capacity(BLOCK_SIZE),
+                Vec::with_capacity(BLOCK_SIZE),
+            ],
+            md5: Md5::new(),
+            frames: 0,
+            frame_number: 0,
+            min_frame_size: 0,
+            max_frame_size: 0,
+        };
+        encoder.writer.write_all(b"fLaC")?;
+        encoder.write_stream_info([0; 16])?;
+
+        Ok(encoder)
+    }
+
+    /// Encode interleaved stereo `frames`, like the output of [`Synth`].
+    ///
+    /// # Errors
+    ///
+    /// Errors from the writer are returned.
+    pub fn write(&mut self, frames: &[[f32; NUM_CHANNELS]]) -> io::Result<()> {
+        let bytes = self.format.bits() as usize / 8;
+
+        for frame in frames {
+            for (channel, &sample) in self.channels.iter_mut().zip(frame) {
+                let dither = self.dither.as_mut();
+                let sample = match self.format {
+                    Format::Int16 => i32::from(quantize::<i16>(sample, dither)),
+                    Format::Int24 => quantize::<I24>(sample, dither).0,
+                };
+                self.md5.update(&sample.to_le_bytes()[..bytes]);
+                channel.push(sample);
+            }
+
+            if self.channels[0].len() == BLOCK_SIZE {
+                self.write_frame()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encode the last block, and patch the stream header. Returns the writer, which is left at
+    /// the end of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the writer are returned.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.channels[0].is_empty() {
+            self.write_frame()?;
+        }
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.start + 4))?;
+        let md5 = core::mem::replace(&mut self.md5, Md5::new()).finish();
+        self.write_stream_info(md5)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        self.writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+
+    /// Write the STREAMINFO metadata block.
+    fn write_stream_info(&mut self, md5: [u8; 16]) -> io::Result<()> {
+        let mut bits = BitWriter::with_capacity(STREAMINFO_LENGTH);
+
+        // Metadata block header: last block, type STREAMINFO
+        bits.write(1, 1);
+        bits.write(0, 7);
+        bits.write(STREAMINFO_LENGTH as u64 - 4, 24);
+
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(self.min_frame_size.into(), 24);
+        bits.write(self.max_frame_size.into(), 24);
+        bits.write(self.sample_rate.into(), 20);
+        bits.write(NUM_CHANNELS as u64 - 1, 3);
+        bits.write(u64::from(self.format.bits()) - 1, 5);
+        bits.write(self.frames >> 32, 4);
+        bits.write(self.frames, 32);
+
+        let mut bytes = bits.finish();
+        bytes.extend_from_slice(&md5);
+        self.writer.write_all(&bytes)
+    }
+
+    /// Encode the current block as a frame.
+    fn write_frame(&mut self) -> io::Result<()> {
+        let [left, right] = &self.channels;
+        let block_size = left.len();
+        let bits = self.format.bits();
+
+        // Try every stereo decorrelation, and keep the smallest
+        let mid: Vec<_> = left.iter().zip(right).map(|(l, r)| (l + r) >> 1).collect();
+        let side: Vec<_> = left.iter().zip(right).map(|(l, r)| l - r).collect();
+        let left = Subframe::new(left, bits);
+        let right = Subframe::new(right, bits);
+        let mid = Subframe::new(&mid, bits);
+        let side = Subframe::new(&side, bits + 1);
+
+        let (assignment, a, b) = [
+            (INDEPENDENT, &left, &right),
+            (LEFT_SIDE, &left, &side),
+            (SIDE_RIGHT, &side, &right),
+            (MID_SIDE, &mid, &side),
+        ]
+        .into_iter()
+        .min_by_key(|(_, a, b)| a.bits + b.bits)
+        .unwrap();
+
+        // Frame header
+        let mut frame = BitWriter::with_capacity(((a.bits + b.bits) / 8) as usize + 32);
+        frame.write(0b11_1111_1111_1110, 14);
+        frame.write(0, 1); // Reserved
+        frame.write(0, 1); // Fixed block size
+        if block_size == BLOCK_SIZE {
+            frame.write(BLOCK_SIZE_CODE, 4);
+        } else {
+            frame.write(BLOCK_SIZE_CODE_16_BIT, 4);
+        }
+        frame.write(0, 4); // Sample rate from STREAMINFO
+        frame.write(assignment, 4);
+        frame.write(self.format.code(), 3);
+        frame.write(0, 1); // Reserved
+        frame.write_utf8(self.frame_number);
+        if block_size != BLOCK_SIZE {
+            frame.write(block_size as u64 - 1, 16);
+        }
+        frame.write(crc8(&frame.bytes).into(), 8);
+
+        a.write(&mut frame);
+        b.write(&mut frame);
+
+        // Frame footer
+        let mut bytes = frame.finish();
+        bytes.extend_from_slice(&crc16(&bytes).to_be_bytes());
+        self.writer.write_all(&bytes)?;
+
+        let frame_size = bytes.len() as u32;
+        self.min_frame_size = if self.frame_number == 0 {
+            frame_size
+        } else {
+            self.min_frame_size.min(frame_size)
+        };
+        self.max_frame_size = self.max_frame_size.max(frame_size);
+        self.frame_number += 1;
+        self.frames += block_size as u64;
+        for channel in &mut self.channels {
+            channel.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// Render the song played by `synth`, and write it to `writer` as a FLAC file. Returns the number
+/// of frames written.
+///
+/// The sample rate of the file is the sample rate of the synth.
+///
+/// # Errors
+///
+/// Errors from `writer` are returned. An error is also returned when the sample rate is not
+/// supported by FLAC.
+pub fn write<S, W>(synth: Synth<S>, writer: W, options: &Options) -> io::Result<u64>
+where
+    S: Borrow<Song>,
+    W: Write + Seek,
+{
+    let mut encoder = Encoder::new(writer, synth.sample_rate() as u32, options)?;
+    for frame in synth {
+        encoder.write(&[frame])?;
+    }
+
+    let frames = encoder.frames + encoder.channels[0].len() as u64;
+    encoder.finish()?;
+
+    Ok(frames)
+}
+
+/// How a subframe predicts its samples.
+enum Prediction {
+    /// All samples are the same.
+    Constant,
+
+    /// Samples are stored without prediction.
+    Verbatim,
+
+    /// One of the fixed polynomial predictors.
+    Fixed,
+
+    /// Linear predictor with quantized coefficients.
+    Lpc {
+        coefficients: ArrayVec<i32, MAX_LPC_ORDER>,
+        shift: u32,
+    },
+}
+
+/// An encoded channel of a frame.
+struct Subframe<'a> {
+    samples: &'a [i32],
+    sample_bits: u32,
+    prediction: Prediction,
+    order: usize,
+    residual: Option<Residual>,
+    bits: u64, // Estimated size of the subframe
+}
+
+impl<'a> Subframe<'a> {
+    /// Find the smallest encoding of `samples`, which have `sample_bits` bits each.
+    fn new(samples: &'a [i32], sample_bits: u32) -> Self {
+        let header_bits = 8;
+
+        if samples.iter().all(|&sample| sample == samples[0]) {
+            return Self {
+                samples,
+                sample_bits,
+                prediction: Prediction::Constant,
+                order: 0,
+                residual: None,
+                bits: header_bits + u64::from(sample_bits),
+            };
+        }
+
+        let mut best = Self {
+            samples,
+            sample_bits,
+            prediction: Prediction::Verbatim,
+            order: 0,
+            residual: None,
+            bits: header_bits + u64::from(sample_bits) * samples.len() as u64,
+        };
+
+        for (order, coefficients) in FIXED_COEFFICIENTS.iter().enumerate() {
+            if order >= samples.len() {
+                break;
+            }
+
+            if let Some(residual) = Residual::new(samples, coefficients, 0) {
+                let bits = header_bits + u64::from(sample_bits) * order as u64 + residual.bits;
+                if bits < best.bits {
+                    best = Self {
+                        prediction: Prediction::Fixed,
+                        order,
+                        residual: Some(residual),
+                        bits,
+                        ..best
+                    };
+                }
+            }
+        }
+
+        if let Some((coefficients, shift)) = lpc_coefficients(samples, sample_bits) {
+            if let Some(residual) = Residual::new(samples, &coefficients, shift) {
+                let order = coefficients.len();
+                let bits = header_bits
+                    + u64::from(sample_bits + LPC_PRECISION) * order as u64
+                    + 4
+                    + 5
+                    + residual.bits;
+                if bits < best.bits {
+                    best = Self {
+                        prediction: Prediction::Lpc {
+                            coefficients,
+                            shift,
+                        },
+                        order,
+                        residual: Some(residual),
+                        bits,
+                        ..best
+                    };
+                }
+            }
+        }
+
+        best
+    }
+
+    fn write(&self, bits: &mut BitWriter) {
+        let sample_bits = self.sample_bits;
+        let warm_up = &self.samples[..self.order];
+
+        // Subframe header, without wasted bits
+        bits.write(0, 1);
+        match &self.prediction {
+            Prediction::Constant => {
+                bits.write(0b00_0000, 6);
+                bits.write(0, 1);
+                bits.write_signed(self.samples[0], sample_bits);
+            }
+            Prediction::Verbatim => {
+                bits.write(0b00_0001, 6);
+                bits.write(0, 1);
+                for &sample in self.samples {
+                    bits.write_signed(sample, sample_bits);
+                }
+            }
+            Prediction::Fixed => {
+                bits.write(0b00_1000 | self.order as u64, 6);
+                bits.write(0, 1);
+                for &sample in warm_up {
+                    bits.write_signed(sample, sample_bits);
+                }
+            }
+            Prediction::Lpc {
+                coefficients,
+                shift,
+            } => {
+                bits.write(0b10_0000 | (self.order as u64 - 1), 6);
+                bits.write(0, 1);
+                for &sample in warm_up {
+                    bits.write_signed(sample, sample_bits);
+                }
+                bits.write(u64::from(LPC_PRECISION) - 1, 4);
+                bits.write((*shift).into(), 5);
+                for &coefficient in coefficients {
+                    bits.write_signed(coefficient, LPC_PRECISION);
+                }
+            }
+        }
+
+        if let Some(residual) = &self.residual {
+            residual.write(bits, self.samples.len());
+        }
+    }
+}
+
+/// Prediction residual, coded with Rice codes in partitions.
+struct Residual {
+    values: Vec<u32>, // Zigzag encoded
+    partition_order: u32,
+    parameters: ArrayVec<u32, { 1 << MAX_PARTITION_ORDER }>,
+    extended: bool, // Use 5-bit Rice parameters
+    bits: u64,      // Estimated size
+}
+
+impl Residual {
+    /// Compute the residual of a linear predictor with integer `coefficients`, scaled down by
+    /// `shift` bits. Returns `None` if the residual does not fit in 32 bits.
+    fn new(samples: &[i32], coefficients: &[i32], shift: u32) -> Option<Self> {
+        let order = coefficients.len();
+        let mut values = Vec::with_capacity(samples.len() - order);
+
+        for (i, &sample) in samples.iter().enumerate().skip(order) {
+            let prediction: i64 = coefficients
+                .iter()
+                .zip(samples[..i].iter().rev())
+                .map(|(&c, &s)| i64::from(c) * i64::from(s))
+                .sum();
+            let residual = i32::try_from(i64::from(sample) - (prediction >> shift)).ok()?;
+            values.push(((residual << 1) ^ (residual >> 31)) as u32);
+        }
+
+        Some(Self::partition(values, samples.len(), order))
+    }
+
+    /// Find the partition order and Rice parameters with the smallest size.
+    fn partition(values: Vec<u32>, block_size: usize, order: usize) -> Self {
+        // The highest partition order that divides the block evenly, and leaves room for the
+        // warm-up samples in the first partition
+        let mut max_order = 0;
+        while max_order < MAX_PARTITION_ORDER
+            && block_size.is_multiple_of(2 << max_order)
+            && block_size >> (max_order + 1) > order
+        {
+            max_order += 1;
+        }
+
+        // Sum the values in each partition of the highest order
+        let partition_size = block_size >> max_order;
+        let mut sums = ArrayVec::<u64, { 1 << MAX_PARTITION_ORDER }>::new();
+        let mut start = 0;
+        for partition in 0..1 << max_order {
+            let end = (partition + 1) * partition_size - order;
+            sums.push(
+                values[start..end]
+                    .iter()
+                    .map(|&value| u64::from(value))
+                    .sum(),
+            );
+            start = end;
+        }
+
+        let mut best = Self {
+            values: Vec::new(),
+            partition_order: 0,
+            parameters: ArrayVec::new(),
+            extended: false,
+            bits: u64::MAX,
+        };
+
+        // Lower orders merge the partition sums pairwise
+        for partition_order in (0..=max_order).rev() {
+            let partition_size = block_size >> partition_order;
+            let mut parameters = ArrayVec::new();
+            let mut bits = 2 + 4;
+            for (partition, &sum) in sums.iter().enumerate() {
+                let len = (partition_size - if partition == 0 { order } else { 0 }) as u64;
+                let (parameter, size) = rice_parameter(sum, len);
+                parameters.push(parameter);
+                bits += size;
+            }
+
+            let extended = parameters.iter().any(|&parameter| parameter > 14);
+            bits += parameters.len() as u64 * if extended { 5 } else { 4 };
+            if bits < best.bits {
+                best = Self {
+                    values: Vec::new(),
+                    partition_order,
+                    parameters,
+                    extended,
+                    bits,
+                };
+            }
+
+            for i in 0..sums.len() / 2 {
+                sums[i] = sums[2 * i] + sums[2 * i + 1];
+            }
+            sums.truncate(sums.len() / 2);
+        }
+
+        best.values = values;
+        best
+    }
+
+    fn write(&self, bits: &mut BitWriter, block_size: usize) {
+        let (method, parameter_bits) = if self.extended { (1, 5) } else { (0, 4) };
+        bits.write(method, 2);
+        bits.write(self.partition_order.into(), 4);
+
+        let order = block_size - self.values.len();
+        let partition_size = block_size >> self.partition_order;
+        let mut start = 0;
+        for (partition, &parameter) in self.parameters.iter().enumerate() {
+            let end = (partition + 1) * partition_size - order;
+            bits.write(parameter.into(), parameter_bits);
+            for &value in &self.values[start..end] {
+                bits.write_unary(value >> parameter);
+                bits.write(u64::from(value) & ((1 << parameter) - 1), parameter);
+            }
+            start = end;
+        }
+    }
+}
+
+/// Choose the Rice parameter for a partition of `len` values that add up to `sum`. Returns the
+/// parameter and the estimated size of the partition, in bits.
+fn rice_parameter(sum: u64, len: u64) -> (u32, u64) {
+    let size = |parameter: u32| len * (u64::from(parameter) + 1) + (sum >> parameter);
+
+    // The best parameter is close to the base 2 logarithm of the mean
+    let mean = sum / len.max(1);
+    let estimate = (u64::BITS - mean.leading_zeros()).min(30);
+
+    (estimate.saturating_sub(1)..=estimate.min(29) + 1)
+        .map(|parameter| (parameter, size(parameter)))
+        .min_by_key(|&(_, size)| size)
+        .unwrap()
+}
+
+/// Compute quantized linear predictor coefficients for `samples`, with the order estimated to give
+/// the smallest subframe. Returns the coefficients and their shift, or `None` if no useful
+/// predictor was found.
+fn lpc_coefficients(
+    samples: &[i32],
+    sample_bits: u32,
+) -> Option<(ArrayVec<i32, MAX_LPC_ORDER>, u32)> {
+    let len = samples.len();
+    let max_order = MAX_LPC_ORDER.min(len.saturating_sub(1));
+    if max_order == 0 {
+        return None;
+    }
+
+    // Autocorrelation of the samples, with a Tukey window (half cosine tapered)
+    let taper = len / 4;
+    let windowed: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &sample)| {
+            let edge = i.min(len - 1 - i);
+            let window = if edge < taper {
+                0.5 - 0.5 * libm::cos(core::f64::consts::PI * edge as f64 / taper as f64)
+            } else {
+                1.0
+            };
+            f64::from(sample) * window
+        })
+        .collect();
+    let mut autocorrelation = [0.0; MAX_LPC_ORDER + 1];
+    for (lag, value) in autocorrelation.iter_mut().enumerate().take(max_order + 1) {
+        *value = windowed
+            .iter()
+            .zip(&windowed[lag..])
+            .map(|(a, b)| a * b)
+            .sum();
+    }
+    if autocorrelation[0] <= 0.0 {
+        return None;
+    }
+
+    // Levinson-Durbin recursion, keeping the order with the smallest estimated size
+    let mut lpc = [0.0; MAX_LPC_ORDER];
+    let mut error = autocorrelation[0];
+    let mut best: Option<(f64, ArrayVec<f64, MAX_LPC_ORDER>)> = None;
+    for order in 1..=max_order {
+        let mut reflection = autocorrelation[order];
+        for j in 1..order {
+            reflection -= lpc[j - 1] * autocorrelation[order - j];
+        }
+        reflection /= error;
+
+        let previous = lpc;
+        lpc[order - 1] = reflection;
+        for j in 1..order {
+            lpc[j - 1] = previous[j - 1] - reflection * previous[order - j - 1];
+        }
+        error *= 1.0 - reflection * reflection;
+        if error <= 0.0 {
+            break;
+        }
+
+        let residual_bits = (0.5 * libm::log2(error / len as f64)).max(0.0);
+        let size = residual_bits * (len - order) as f64
+            + f64::from(sample_bits + LPC_PRECISION) * order as f64;
+        if best.as_ref().is_none_or(|(best_size, _)| size < *best_size) {
+            best = Some((size, lpc[..order].iter().copied().collect()));
+        }
+    }
+
+    quantize_coefficients(&best?.1)
+}
+
+/// Quantize LPC coefficients to [`LPC_PRECISION`] bits.
+fn quantize_coefficients(lpc: &[f64]) -> Option<(ArrayVec<i32, MAX_LPC_ORDER>, u32)> {
+    let max = lpc.iter().fold(0.0, |max: f64, c| max.max(c.abs()));
+    if max <= 0.0 || !max.is_finite() {
+        return None;
+    }
+
+    let q_max = (1 << (LPC_PRECISION - 1)) - 1;
+    let q_min = -(1 << (LPC_PRECISION - 1));
+    let (_, exponent) = libm::frexp(max);
+    let shift = u32::try_from(i64::from(LPC_PRECISION) - 1 - i64::from(exponent))
+        .ok()?
+        .min(15);
+
+    // Carry the rounding error over to the next coefficient
+    let mut coefficients = ArrayVec::new();
+    let mut error = 0.0;
+    for &c in lpc {
+        error += c * f64::from(1 << shift);
+        let q = libm::round(error).clamp(f64::from(q_min), f64::from(q_max));
+        error -= q;
+        coefficients.push(q as i32);
+    }
+
+    coefficients
+        .iter()
+        .any(|&c| c != 0)
+        .then_some((coefficients, shift))
+}
+
+/// Writes bits, most significant first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    count: u32, // Bits in the accumulator
+}
+
+impl BitWriter {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            accumulator: 0,
+            count: 0,
+        }
+    }
+
+    /// Write the lowest `bits` bits of `value`, up to 32 bits.
+    fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= 32);
+
+        self.accumulator = (self.accumulator << bits) | (value & ((1 << bits) - 1));
+        self.count += bits;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.bytes.push((self.accumulator >> self.count) as u8);
+        }
+        self.accumulator &= (1 << self.count) - 1;
+    }
+
+    /// Write a signed `value` in two's complement.
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(i64::from(value) as u64, bits);
+    }
+
+    /// Write `value` zeros, followed by a one.
+    fn write_unary(&mut self, mut value: u32) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+        self.write(1, value + 1);
+    }
+
+    /// Write a frame number with the UTF-8 style encoding of FLAC.
+    fn write_utf8(&mut self, value: u32) {
+        if value < 0x80 {
+            self.write(value.into(), 8);
+            return;
+        }
+
+        // Each continuation byte holds 6 bits, and the first byte holds what is left
+        let continuation = match value {
+            0..=0x7ff => 1,
+            0x800..=0xffff => 2,
+            0x1_0000..=0x1f_ffff => 3,
+            0x20_0000..=0x3ff_ffff => 4,
+            _ => 5,
+        };
+        let prefix = (0xff_u64 << (7 - continuation)) & 0xff;
+        self.write(prefix | u64::from(value >> (6 * continuation)), 8);
+        for byte in (0..continuation).rev() {
+            self.write(0x80 | u64::from((value >> (6 * byte)) & 0x3f), 8);
+        }
+    }
+
+    /// Pad with zeros to a byte boundary, and return the bytes.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+
+        self.bytes
+    }
+}
+
+const fn crc8_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x07
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x8005
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+const CRC8_TABLE: [u8; 256] = crc8_table();
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+/// CRC-8 of the frame header (polynomial `0x07`).
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0, |crc, &byte| CRC8_TABLE[usize::from(crc ^ byte)])
+}
+
+/// CRC-16 of the frame (polynomial `0x8005`).
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (crc << 8) ^ CRC16_TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+    })
+}
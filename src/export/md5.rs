@@ -0,0 +1,106 @@
+//! MD5 digest, for the audio signature of FLAC files.
+
+/// Per-round shift amounts.
+const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// Per-step constants, the integer part of `abs(sin(i + 1)) * 2^32`.
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+/// Incremental MD5 digest.
+#[derive(Clone, Debug)]
+pub(super) struct Md5 {
+    state: [u32; 4],
+    buffer: [u8; 64],
+    length: u64, // In bytes
+}
+
+impl Md5 {
+    pub(super) fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            buffer: [0; 64],
+            length: 0,
+        }
+    }
+
+    pub(super) fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let offset = (self.length % 64) as usize;
+            let count = data.len().min(64 - offset);
+            self.buffer[offset..offset + count].copy_from_slice(&data[..count]);
+            self.length += count as u64;
+            data = &data[count..];
+
+            if offset + count == 64 {
+                self.process();
+            }
+        }
+    }
+
+    pub(super) fn finish(mut self) -> [u8; 16] {
+        let bits = self.length.wrapping_mul(8);
+
+        // Pad to 56 bytes (mod 64), then append the length in bits
+        self.update(&[0x80]);
+        while self.length % 64 != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_le_bytes());
+
+        let mut digest = [0; 16];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+
+        digest
+    }
+
+    /// Process the full buffer.
+    fn process(&mut self) {
+        let mut words = [0_u32; 16];
+        for (word, bytes) in words.iter_mut().zip(self.buffer.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for (i, constant) in K.iter().enumerate() {
+            let (mix, index) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let mix = mix
+                .wrapping_add(a)
+                .wrapping_add(*constant)
+                .wrapping_add(words[index]);
+
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(mix.rotate_left(SHIFTS[i / 16 * 4 + i % 4]));
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Write songs to WAV files.
+//!
+//! ```no_run
+//! use sonant::export::wav::{self, Format, Options};
+//! use sonant::Song;
+//! use std::fs::File;
+//!
+//! let song = Song::from_slice(include_bytes!("../../examples/poseidon.snt"))?;
+//! let options = Options {
+//!     sample_rate: 48000,
+//!     format: Format::Int24,
+//!     dither: true,
+//!     ..Options::default()
+//! };
+//! wav::write(&song, File::create("poseidon.wav")?, &options)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::{dither, quantize};
+use crate::consts::NUM_CHANNELS;
+use crate::sample::I24;
+use crate::song::Song;
+use crate::synth::Synth;
+use randomize::PCG32;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+/// Sample format of a WAV file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// 16-bit integer PCM.
+    #[default]
+    Int16,
+
+    /// 24-bit integer PCM.
+    Int24,
+
+    /// 32-bit floating point. Samples are written without quantization, so dither is never
+    /// applied.
+    Float32,
+}
+
+impl Format {
+    fn bits(self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+}
+
+/// Options for [`write()`] and [`Encoder`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// Sample rate in Hz. The default is 44100.
+    pub sample_rate: u32,
+
+    /// Sample format. The default is [`Format::Int16`].
+    pub format: Format,
+
+    /// Add triangular (TPDF) dither when quantizing to integer samples. The default is `false`.
+    pub dither: bool,
+
+    /// Seed for the noise generator of the synth and the dither. The same seed always produces
+    /// the same file. The default is `(0, 1)`.
+    pub seed: (u64, u64),
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            format: Format::default(),
+            dither: false,
+            seed: (0, 1),
+        }
+    }
+}
+
+/// WAV format tag for integer PCM.
+const WAVE_FORMAT_PCM: u16 = 1;
+
+/// WAV format tag for floating point samples.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Encodes stereo frames to a WAV file.
+///
+/// The header is written first, and patched with the final lengths by [`Encoder::finish`]. The
+/// sample rate is taken from the options.
+///
+/// ```
+/// use sonant::export::wav::{Encoder, Options};
+/// use sonant::{Song, Synth};
+/// use std::io::Cursor;
+///
+/// let song = Song::from_slice(include_bytes!("../../examples/poseidon.snt"))?;
+/// let synth = Synth::new(&song, (0, 1), 44100.0);
+///
+/// // Encode the first second of the song
+/// let mut encoder = Encoder::new(Cursor::new(Vec::new()), &Options::default())?;
+/// let frames = synth.take(44100).collect::<Vec<_>>();
+/// encoder.write(&frames)?;
+/// let file = encoder.finish()?.into_inner();
+/// assert_eq!(file.len(), 44 + 44100 * 4);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Encoder<W: Write + Seek> {
+    writer: BufWriter<W>,
+    start: u64, // Stream position of the header
+    options: Options,
+    dither: Option<PCG32>,
+    frames: u64,
+}
+
+impl<W: Write + Seek> Encoder<W> {
+    /// Create an encoder, and write the header to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Errors from `writer` are returned.
+    pub fn new(writer: W, options: &Options) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        let start = writer.stream_position()?;
+        write_header(&mut writer, options, 0)?;
+
+        Ok(Self {
+            writer,
+            start,
+            options: *options,
+            dither: dither(options.dither, options.seed),
+            frames: 0,
+        })
+    }
+
+    /// Encode interleaved stereo `frames`, like the output of [`Synth`].
+    ///
+    /// # Errors
+    ///
+    /// Errors from the writer are returned.
+    pub fn write(&mut self, frames: &[[f32; NUM_CHANNELS]]) -> io::Result<()> {
+        for &sample in frames.iter().flatten() {
+            match self.options.format {
+                Format::Int16 => {
+                    let sample: i16 = quantize(sample, self.dither.as_mut());
+                    self.writer.write_all(&sample.to_le_bytes())?;
+                }
+                Format::Int24 => {
+                    let I24(sample) = quantize(sample, self.dither.as_mut());
+                    self.writer.write_all(&sample.to_le_bytes()[..3])?;
+                }
+                Format::Float32 => self.writer.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.frames += frames.len() as u64;
+
+        Ok(())
+    }
+
+    /// Patch the header with the final lengths. Returns the writer, which is left at the end of
+    /// the file.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the writer are returned. An error is also returned when the file is too long
+    /// for the 4 GB limit of WAV files.
+    pub fn finish(mut self) -> io::Result<W> {
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        write_header(&mut self.writer, &self.options, self.frames)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        self.writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+}
+
+/// Render `song` and write it to `writer` as a stereo WAV file. Returns the number of frames
+/// written.
+///
+/// The header is written before the samples, and patched with the final lengths when the song
+/// has ended. The writer is left at the end of the file.
+///
+/// ```
+/// use sonant::export::wav::{self, Options};
+/// use sonant::Song;
+/// use std::io::Cursor;
+///
+/// // A short, silent song
+/// let mut data = [0; 3333];
+/// data[0] = 16;
+/// let song = Song::from_slice(&data)?;
+///
+/// let mut file = Cursor::new(Vec::new());
+/// let frames = wav::write(&song, &mut file, &Options::default())?;
+///
+/// let file = file.into_inner();
+/// assert_eq!(&file[..4], b"RIFF");
+/// assert_eq!(file.len() as u64, 44 + frames * 4);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// Errors from `writer` are returned. An error is also returned when the song is too long for
+/// the 4 GB limit of WAV files.
+pub fn write<W: Write + Seek>(song: &Song, writer: W, options: &Options) -> io::Result<u64> {
+    let synth = Synth::new(song, options.seed, options.sample_rate as f32);
+
+    let mut encoder = Encoder::new(writer, options)?;
+    for frame in synth {
+        encoder.write(&[frame])?;
+    }
+
+    let frames = encoder.frames;
+    encoder.finish()?;
+
+    Ok(frames)
+}
+
+/// Write the WAV header for a file with the given number of `frames`.
+fn write_header(writer: &mut impl Write, options: &Options, frames: u64) -> io::Result<()> {
+    let bits = options.format.bits();
+    let block_align = NUM_CHANNELS as u16 * bits / 8;
+    let data_length = u32::try_from(frames * u64::from(block_align))
+        .ok()
+        .filter(|&length| length <= u32::MAX - 58)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "song is too long"))?;
+
+    // Floating point files need an extended format chunk, and a fact chunk
+    let float = options.format == Format::Float32;
+    let (format_tag, fmt_length, riff_length) = if float {
+        (WAVE_FORMAT_IEEE_FLOAT, 18, 50 + data_length)
+    } else {
+        (WAVE_FORMAT_PCM, 16, 36 + data_length)
+    };
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_length.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&u32::to_le_bytes(fmt_length))?;
+    writer.write_all(&format_tag.to_le_bytes())?;
+    writer.write_all(&(NUM_CHANNELS as u16).to_le_bytes())?;
+    writer.write_all(&options.sample_rate.to_le_bytes())?;
+    writer.write_all(&(options.sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits.to_le_bytes())?;
+
+    if float {
+        writer.write_all(&0_u16.to_le_bytes())?;
+        writer.write_all(b"fact")?;
+        writer.write_all(&4_u32.to_le_bytes())?;
+        writer.write_all(&(data_length / u32::from(block_align)).to_le_bytes())?;
+    }
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_length.to_le_bytes())
+}
@@ -0,0 +1,86 @@
+use crate::consts::{MAX_CHANNELS, NUM_CHANNELS, NUM_INSTRUMENTS};
+use crate::song::Error;
+
+/// Channel layout of the rendered output.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Layout {
+    /// A single channel. Both sides of the stereo image are summed, which cancels out panning.
+    Mono,
+
+    /// Two channels (left and right). This is the native layout of the synthesizer.
+    #[default]
+    Stereo,
+
+    /// Up to [`MAX_CHANNELS`](crate::MAX_CHANNELS) channels, with each instrument track routed
+    /// individually.
+    Multichannel {
+        /// Number of output channels.
+        channels: usize,
+
+        /// Routing for each instrument track.
+        tracks: [Route; NUM_INSTRUMENTS],
+    },
+}
+
+/// Where an instrument track is sent in a [`Layout::Multichannel`] output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Route {
+    /// Send the track to a single channel. Panning is ignored.
+    Channel(usize),
+
+    /// Send the track to a pair of channels, which take the place of left and right. Panning is
+    /// applied between the two channels.
+    Pair(usize, usize),
+}
+
+impl Layout {
+    /// The number of interleaved channels in each frame.
+    #[must_use]
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => NUM_CHANNELS,
+            Self::Multichannel { channels, .. } => *channels,
+        }
+    }
+
+    /// Ensure every channel referenced by the layout exists.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if let Self::Multichannel { channels, tracks } = self {
+            let in_range = |channel: &usize| channel < channels;
+            let routes_ok = tracks.iter().all(|route| match route {
+                Route::Channel(channel) => in_range(channel),
+                Route::Pair(left, right) => in_range(left) && in_range(right),
+            });
+
+            if *channels == 0 || *channels > MAX_CHANNELS || !routes_ok {
+                return Err(Error::InvalidLayout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mix the stereo output of each track into `frame`, which must have exactly
+    /// [`Layout::channels`] elements.
+    pub(crate) fn mix(&self, tracks: &[[f32; NUM_CHANNELS]; NUM_INSTRUMENTS], frame: &mut [f32]) {
+        frame.fill(0.0);
+
+        for (i, [left, right]) in tracks.iter().enumerate() {
+            match self {
+                Self::Mono => frame[0] += left + right,
+                Self::Stereo => {
+                    frame[0] += left;
+                    frame[1] += right;
+                }
+                Self::Multichannel { tracks, .. } => match tracks[i] {
+                    Route::Channel(channel) => frame[channel] += left + right,
+                    Route::Pair(l, r) => {
+                        frame[l] += left;
+                        frame[r] += right;
+                    }
+                },
+            }
+        }
+    }
+}
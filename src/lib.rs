@@ -6,8 +6,19 @@
 //!
 //! # Crate features
 //!
-//! - `std` (default) - Allow `std::error::Error`. Disable default features to use `sonant` in a
+//! - `std` (default) - Allow `std::error::Error`, and enable APIs that allocate or do I/O, like
+//!   `render_note` and the [`export`] module. Disable default features to use `sonant` in a
 //!   `no_std` environment.
+//! - `flac` - Enable the `export::flac` module for writing FLAC files. Implies `std`.
+//! - `player` - Enable `Player`, for playing songs on the default audio output device with
+//!   `cpal`. Implies `std`.
+//! - `rodio` - Enable `SynthSource`, for playing songs with `rodio`. Implies `std`.
+//! - `bevy` - Enable `SonantPlugin`, for loading `.snt` files as [`Song`] assets and playing
+//!   them with Bevy. Implies `rodio`.
+//! - `wasm` - Export JavaScript bindings with `wasm-bindgen`, for rendering songs in an
+//!   `AudioWorklet`. Implies `std`. This feature uses `unsafe` code generated by `wasm-bindgen`.
+//! - `cli` - Build the `sonant` command-line tool, for playing, rendering, and inspecting `.snt`
+//!   files. Implies `flac` and `player`.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![deny(clippy::all)]
@@ -15,11 +26,58 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_precision_loss)]
 #![allow(clippy::cast_sign_loss)]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "wasm"), forbid(unsafe_code))]
+#![cfg_attr(feature = "wasm", deny(unsafe_code))]
 
 mod consts;
+mod events;
+#[cfg(feature = "std")]
+pub mod export;
+mod layout;
+mod master;
+mod meter;
+mod music;
+mod oscillator;
+mod oversampling;
+mod param;
+#[cfg(feature = "player")]
+mod player;
+#[cfg(feature = "bevy")]
+mod plugin;
+mod sample;
 mod song;
+#[cfg(feature = "rodio")]
+mod source;
+mod state;
 mod synth;
+mod timeline;
+mod tuning;
+mod voice;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+pub use consts::{MAX_CHANNELS, MAX_OVERLAPPING_NOTES};
+pub use events::{Event, EventSink, Position};
+pub use layout::{Layout, Route};
+pub use master::{ClipReport, Clipping};
+pub use meter::TrackLevel;
+pub use music::{MusicPlayer, Transition};
+pub use oscillator::OscillatorMode;
+pub use oversampling::Oversampling;
+pub use param::Param;
+#[cfg(feature = "player")]
+pub use player::{Player, PlayerError};
+#[cfg(feature = "bevy")]
+pub use plugin::{SonantPlugin, SongLoader, SongLoaderError};
+pub use sample::{Sample, I24};
 pub use song::{Error, Song};
+#[cfg(feature = "rodio")]
+pub use source::SynthSource;
+pub use state::SynthState;
 pub use synth::Synth;
+pub use tuning::Tuning;
+#[cfg(feature = "std")]
+pub use voice::render_note;
+pub use voice::{render_note_into, InstrumentVoice};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmRenderer, WasmSong};
@@ -0,0 +1,484 @@
+//! The `sonant` command-line tool, for playing, rendering, and inspecting `.snt` files.
+//!
+//! ```bash
+//! cargo install sonant --features cli
+//! sonant render poseidon.snt poseidon.flac --bits 24
+//! ```
+
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_sign_loss)]
+#![forbid(unsafe_code)]
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use colored::Colorize;
+use error_iter::ErrorIter as _;
+use sonant::export::{flac, wav};
+use sonant::{Player, Song, Synth, MAX_OVERLAPPING_NOTES};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Number of frames rendered at a time.
+const BLOCK_SIZE: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unable to read {}", .0.display())]
+    Read(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to write {}", .0.display())]
+    Write(PathBuf, #[source] std::io::Error),
+
+    #[error("Invalid song {}", .0.display())]
+    Song(PathBuf, #[source] sonant::Error),
+
+    #[error("Unable to play the song")]
+    Player(#[source] sonant::PlayerError),
+
+    #[error("Unknown format for {}; expected a {} file", .0.display(), .1)]
+    Extension(PathBuf, &'static str),
+
+    #[error("FLAC files do not support 32-bit samples")]
+    FlacBits,
+
+    #[error("{0} of {1} files are not valid songs")]
+    Invalid(usize, usize),
+}
+
+/// Play, render, and inspect Sonant songs.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Play a song on the default audio output device.
+    Play(PlayArgs),
+
+    /// Render a song to a WAV or FLAC file.
+    Render(RenderArgs),
+
+    /// Show the tempo, duration, and instruments of songs.
+    Info(FilesArgs),
+
+    /// Check that files are valid songs.
+    Validate(FilesArgs),
+
+    /// Convert a song to a C header or Rust source file, for embedding it in a program.
+    Convert(ConvertArgs),
+}
+
+#[derive(Debug, Args)]
+struct PlayArgs {
+    /// The `.snt` file to play.
+    file: PathBuf,
+
+    /// Seed for the noise generator.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Output volume, where 1.0 is full scale.
+    #[arg(long, default_value_t = 1.0)]
+    volume: f32,
+}
+
+#[derive(Debug, Args)]
+struct RenderArgs {
+    /// The `.snt` file to render.
+    input: PathBuf,
+
+    /// The output file. The format is chosen by the extension: `.wav` or `.flac`.
+    output: PathBuf,
+
+    /// Sample rate in Hz.
+    #[arg(short, long, default_value_t = 44100)]
+    rate: u32,
+
+    /// Bit depth. 32-bit samples are floating point, and only supported by WAV files.
+    #[arg(short, long, value_enum, default_value = "16")]
+    bits: Bits,
+
+    /// Add triangular (TPDF) dither to integer samples.
+    #[arg(long)]
+    dither: bool,
+
+    /// Seed for the noise generator and the dither.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Render each instrument track that plays notes to its own file, named like
+    /// `<output>-<track>.wav` with tracks numbered from 1.
+    #[arg(long)]
+    stems: bool,
+
+    /// Number of times to play the sequence. Later passes overlap the fading echoes of the
+    /// previous one, like a looping song in the tracker.
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    loops: u32,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Bits {
+    #[value(name = "16")]
+    Int16,
+
+    #[value(name = "24")]
+    Int24,
+
+    #[value(name = "32")]
+    Float32,
+}
+
+#[derive(Debug, Args)]
+struct FilesArgs {
+    /// The `.snt` files.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    /// The `.snt` file to convert.
+    input: PathBuf,
+
+    /// The output file. The language is chosen by the extension: `.h` or `.rs`.
+    output: PathBuf,
+}
+
+/// An encoder for the output format chosen by [`RenderArgs`].
+enum Encoder {
+    Wav(wav::Encoder<File>),
+    Flac(flac::Encoder<File>),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Play(args) => play(&args),
+        Command::Render(args) => render(&args),
+        Command::Info(args) => info(&args),
+        Command::Validate(args) => validate(&args),
+        Command::Convert(args) => convert(&args),
+    };
+
+    match result {
+        Err(e) => {
+            report(&e);
+
+            ExitCode::FAILURE
+        }
+        Ok(()) => ExitCode::SUCCESS,
+    }
+}
+
+/// Print an error and its causes to stderr.
+fn report(error: &Error) {
+    eprintln!("{} {}", "error:".red(), error);
+
+    for cause in error.sources().skip(1) {
+        eprintln!("{} {}", "caused by:".bright_red(), cause);
+    }
+}
+
+/// Print a warning to stderr.
+fn warn(message: &str) {
+    eprintln!("{} {}", "warning:".yellow(), message);
+}
+
+/// Read and parse a song.
+fn load(path: &Path) -> Result<Song, Error> {
+    let data = std::fs::read(path).map_err(|e| Error::Read(path.into(), e))?;
+
+    Song::from_slice(&data).map_err(|e| Error::Song(path.into(), e))
+}
+
+/// Get the lowercase extension of `path`.
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Format a duration like `2:42.9`, or describe a song that never ends.
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => {
+            let secs = duration.as_secs_f64();
+            format!("{}:{:04.1}", (secs / 60.0) as u64, secs % 60.0)
+        }
+        None => "never ends (echoes repeat forever)".to_string(),
+    }
+}
+
+fn play(args: &PlayArgs) -> Result<(), Error> {
+    let song = load(&args.file)?;
+    let duration = Synth::new(&song, (args.seed, 1), 44100.0).duration();
+    println!(
+        "Playing {} ({})",
+        args.file.display(),
+        format_duration(duration)
+    );
+
+    // Play the song, waking up this thread when it ends
+    let thread = std::thread::current();
+    let player = Player::with_end_callback(song, (args.seed, 1), move || thread.unpark())
+        .map_err(Error::Player)?;
+    player.set_volume(args.volume);
+
+    while player.is_playing() {
+        std::thread::park();
+    }
+
+    Ok(())
+}
+
+fn render(args: &RenderArgs) -> Result<(), Error> {
+    let song = load(&args.input)?;
+
+    // Check the output format before rendering anything
+    let ext = extension(&args.output);
+    match (ext.as_str(), args.bits) {
+        ("flac", Bits::Float32) => return Err(Error::FlacBits),
+        ("wav" | "flac", _) => (),
+        _ => return Err(Error::Extension(args.output.clone(), ".wav or .flac")),
+    }
+
+    if !args.stems {
+        return render_file(&song, None, &args.output, args);
+    }
+
+    let synth = Synth::new(&song, (args.seed, 1), args.rate as f32);
+    let polyphony = synth.max_polyphony();
+    let stem = args
+        .output
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    for track in (0..polyphony.len()).filter(|&track| polyphony[track] > 0) {
+        let path = args
+            .output
+            .with_file_name(format!("{stem}-{}.{ext}", track + 1));
+        render_file(&song, Some(track), &path, args)?;
+    }
+
+    Ok(())
+}
+
+/// Render `song` to the file at `path`. When `solo` is a track index, the other tracks are muted.
+fn render_file(
+    song: &Song,
+    solo: Option<usize>,
+    path: &Path,
+    args: &RenderArgs,
+) -> Result<(), Error> {
+    let mut synth = Synth::new(song, (args.seed, 1), args.rate as f32);
+    if let Some(solo) = solo {
+        let tracks = synth.max_polyphony().len();
+        for track in (0..tracks).filter(|&track| track != solo) {
+            for sequence in 0..song.sequence_length() {
+                synth.set_sequence_entry(track, sequence, 0);
+            }
+        }
+    }
+
+    let to_error = |e| Error::Write(path.into(), e);
+    let file = File::create(path).map_err(to_error)?;
+    let mut encoder = if extension(path) == "flac" {
+        let format = match args.bits {
+            Bits::Int16 => flac::Format::Int16,
+            Bits::Int24 => flac::Format::Int24,
+            Bits::Float32 => unreachable!("`render` rejects 32-bit FLAC before rendering"),
+        };
+        let options = flac::Options {
+            format,
+            dither: args.dither,
+            seed: (args.seed, 1),
+        };
+        Encoder::Flac(flac::Encoder::new(file, args.rate, &options).map_err(to_error)?)
+    } else {
+        let format = match args.bits {
+            Bits::Int16 => wav::Format::Int16,
+            Bits::Int24 => wav::Format::Int24,
+            Bits::Float32 => wav::Format::Float32,
+        };
+        let options = wav::Options {
+            sample_rate: args.rate,
+            format,
+            dither: args.dither,
+            seed: (args.seed, 1),
+        };
+        Encoder::Wav(wav::Encoder::new(file, &options).map_err(to_error)?)
+    };
+
+    // Jump back to the start at the first sample of the last pattern, once for each extra loop
+    let last = song.sequence_length() - 1;
+    let mut loops = args.loops - 1;
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    loop {
+        block.clear();
+        while block.len() < BLOCK_SIZE {
+            let position = synth.position();
+            if loops > 0
+                && position.sequence == last
+                && position.row == 0
+                && position.row_sample == 0
+            {
+                synth.jump_to(0);
+                loops -= 1;
+            }
+
+            match synth.next() {
+                Some(frame) => block.push(frame),
+                None => break,
+            }
+        }
+        if block.is_empty() {
+            break;
+        }
+
+        match &mut encoder {
+            Encoder::Wav(encoder) => encoder.write(&block),
+            Encoder::Flac(encoder) => encoder.write(&block),
+        }
+        .map_err(to_error)?;
+    }
+
+    match encoder {
+        Encoder::Wav(encoder) => encoder.finish().map(drop),
+        Encoder::Flac(encoder) => encoder.finish().map(drop),
+    }
+    .map_err(to_error)?;
+    println!("Wrote {}", path.display());
+
+    Ok(())
+}
+
+fn info(args: &FilesArgs) -> Result<(), Error> {
+    for (i, path) in args.files.iter().enumerate() {
+        let song = load(path)?;
+        let synth = Synth::new(&song, (0, 1), 44100.0);
+        let polyphony = synth.max_polyphony();
+        let tracks = (0..polyphony.len()).filter(|&track| polyphony[track] > 0);
+
+        let mut instruments = String::new();
+        let mut voices = String::new();
+        for track in tracks {
+            let _ = write!(instruments, " {}", track + 1);
+            let _ = write!(voices, " {}:{}", track + 1, polyphony[track]);
+        }
+        if instruments.is_empty() {
+            instruments.push_str(" none");
+        }
+
+        if i > 0 {
+            println!();
+        }
+        println!("{}", path.display().to_string().bold());
+        println!("  Tempo:       {:.1} BPM", song.tempo());
+        println!("  Patterns:    {}", song.sequence_length());
+        println!("  Duration:    {}", format_duration(synth.duration()));
+        println!("  Instruments:{instruments}");
+        println!("  Polyphony:  {voices}");
+        warn_polyphony(path, &polyphony);
+    }
+
+    Ok(())
+}
+
+fn validate(args: &FilesArgs) -> Result<(), Error> {
+    let mut invalid = 0;
+    for path in &args.files {
+        match load(path) {
+            Ok(song) => {
+                println!("{} {}", "ok:".green(), path.display());
+                let synth = Synth::new(&song, (0, 1), 44100.0);
+                warn_polyphony(path, &synth.max_polyphony());
+            }
+            Err(e) => {
+                report(&e);
+                invalid += 1;
+            }
+        }
+    }
+
+    if invalid > 0 {
+        return Err(Error::Invalid(invalid, args.files.len()));
+    }
+
+    Ok(())
+}
+
+/// Warn about tracks that play more notes at once than the synth has voices for.
+fn warn_polyphony(path: &Path, polyphony: &[usize]) {
+    for (track, &voices) in polyphony.iter().enumerate() {
+        if voices > MAX_OVERLAPPING_NOTES {
+            warn(&format!(
+                "{}: track {} plays up to {voices} notes at once; only the newest \
+                 {MAX_OVERLAPPING_NOTES} are heard",
+                path.display(),
+                track + 1,
+            ));
+        }
+    }
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), Error> {
+    let data = std::fs::read(&args.input).map_err(|e| Error::Read(args.input.clone(), e))?;
+
+    // Parse the song, so only valid songs are embedded
+    Song::from_slice(&data).map_err(|e| Error::Song(args.input.clone(), e))?;
+
+    // Name the array after the input file
+    let stem = args.input.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, '_');
+    }
+
+    let mut bytes = String::new();
+    for chunk in data.chunks(16) {
+        bytes.push_str("   ");
+        for byte in chunk {
+            let _ = write!(bytes, " 0x{byte:02x},");
+        }
+        bytes.push('\n');
+    }
+
+    let source = args.input.file_name().unwrap_or_default().to_string_lossy();
+    let len = data.len();
+    let output = match extension(&args.output).as_str() {
+        "h" => {
+            let name = name.to_lowercase();
+            format!(
+                "/* Generated by `sonant convert` from {source} */\n\n\
+                 static const unsigned char {name}[{len}] = {{\n{bytes}}};\n"
+            )
+        }
+        "rs" => {
+            let name = name.to_uppercase();
+            format!(
+                "// Generated by `sonant convert` from {source}\n\n\
+                 pub const {name}: [u8; {len}] = [\n{bytes}];\n"
+            )
+        }
+        _ => return Err(Error::Extension(args.output.clone(), ".h or .rs")),
+    };
+
+    std::fs::write(&args.output, output).map_err(|e| Error::Write(args.output.clone(), e))?;
+    println!("Wrote {}", args.output.display());
+
+    Ok(())
+}
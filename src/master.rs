@@ -0,0 +1,69 @@
+/// Clipping behavior of the master output stage.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Clipping {
+    /// Hard-clamp samples to `[-1.0, 1.0]`. This is how the original player behaves.
+    #[default]
+    Hard,
+
+    /// Pass samples through unchanged below -6 dBFS, and smoothly saturate louder samples so they
+    /// approach (but never exceed) full scale.
+    Soft,
+
+    /// Do not clip at all. Samples may exceed `[-1.0, 1.0]`, which is useful when the output is
+    /// mixed or normalized further down the line.
+    Off,
+}
+
+/// Clipping statistics collected by the master output stage.
+///
+/// Both values are measured after the master gain is applied, but before clipping.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClipReport {
+    /// Number of samples (counting each channel individually) that exceeded full scale.
+    pub clipped: u64,
+
+    /// Largest absolute sample value seen.
+    pub peak: f32,
+}
+
+/// The master output stage applies gain and clipping to the mixed samples.
+#[derive(Clone, Debug)]
+pub(crate) struct Master {
+    pub(crate) gain: f32,
+    pub(crate) clipping: Clipping,
+    pub(crate) report: ClipReport,
+}
+
+/// Knee of the soft clipping curve (-6 dBFS).
+const SOFT_KNEE: f32 = 0.5;
+
+impl Master {
+    pub(crate) fn new() -> Self {
+        Self {
+            gain: 1.0,
+            clipping: Clipping::default(),
+            report: ClipReport::default(),
+        }
+    }
+
+    /// Process a single sample.
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        let sample = sample * self.gain;
+        let magnitude = libm::fabsf(sample);
+
+        self.report.peak = self.report.peak.max(magnitude);
+        if magnitude > 1.0 {
+            self.report.clipped += 1;
+        }
+
+        match self.clipping {
+            Clipping::Hard => sample.clamp(-1.0, 1.0),
+            Clipping::Soft if magnitude > SOFT_KNEE => {
+                let range = 1.0 - SOFT_KNEE;
+                let knee = SOFT_KNEE + range * libm::tanhf((magnitude - SOFT_KNEE) / range);
+                libm::copysignf(knee, sample)
+            }
+            Clipping::Soft | Clipping::Off => sample,
+        }
+    }
+}
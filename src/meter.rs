@@ -0,0 +1,85 @@
+use crate::consts::{MAX_OVERLAPPING_NOTES, NUM_CHANNELS, NUM_INSTRUMENTS};
+
+/// Output level of a single instrument track, measured over one meter block.
+///
+/// Levels are measured before the master output stage, on the same scale as the output samples
+/// (full scale is `1.0`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrackLevel {
+    /// Root mean square of both channels.
+    pub rms: f32,
+
+    /// Largest absolute sample value on either channel.
+    pub peak: f32,
+}
+
+/// Default meter block length, in samples.
+const DEFAULT_BLOCK_LENGTH: u32 = 1024;
+
+/// Per-track level meters. Levels and envelopes are published at the end of each block.
+#[derive(Clone, Debug)]
+pub(crate) struct Meter {
+    block_length: u32,
+    pub(crate) levels: [TrackLevel; NUM_INSTRUMENTS],
+    pub(crate) envelopes: [[f32; MAX_OVERLAPPING_NOTES]; NUM_INSTRUMENTS],
+
+    // Accumulators for the current block
+    count: u32,
+    sum_sq: [f32; NUM_INSTRUMENTS],
+    peak: [f32; NUM_INSTRUMENTS],
+}
+
+impl Meter {
+    pub(crate) fn new() -> Self {
+        Self {
+            block_length: DEFAULT_BLOCK_LENGTH,
+            levels: [TrackLevel::default(); NUM_INSTRUMENTS],
+            envelopes: [[0.0; MAX_OVERLAPPING_NOTES]; NUM_INSTRUMENTS],
+            count: 0,
+            sum_sq: [0.0; NUM_INSTRUMENTS],
+            peak: [0.0; NUM_INSTRUMENTS],
+        }
+    }
+
+    /// Change the block length, and restart the current block.
+    pub(crate) fn set_block_length(&mut self, block_length: u32) {
+        self.block_length = block_length;
+        self.reset_block();
+    }
+
+    fn reset_block(&mut self) {
+        self.count = 0;
+        self.sum_sq = [0.0; NUM_INSTRUMENTS];
+        self.peak = [0.0; NUM_INSTRUMENTS];
+    }
+
+    /// Measure the stereo output of each track for a single sample.
+    ///
+    /// Returns `true` when the block is complete and the levels have been published. The caller
+    /// is responsible for publishing the envelopes at the same time.
+    pub(crate) fn process(&mut self, tracks: &[[f32; NUM_CHANNELS]; NUM_INSTRUMENTS]) -> bool {
+        for (i, [left, right]) in tracks.iter().enumerate() {
+            self.sum_sq[i] += left * left + right * right;
+            self.peak[i] = self.peak[i]
+                .max(libm::fabsf(*left))
+                .max(libm::fabsf(*right));
+        }
+
+        self.count += 1;
+        if self.count < self.block_length {
+            return false;
+        }
+
+        let samples = (self.count as usize * NUM_CHANNELS) as f32;
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            *level = TrackLevel {
+                rms: libm::sqrtf(self.sum_sq[i] / samples),
+                peak: self.peak[i],
+            };
+        }
+
+        self.reset_block();
+
+        true
+    }
+}
@@ -0,0 +1,239 @@
+use crate::consts::NUM_CHANNELS;
+use crate::events::{Event, Position};
+use crate::sample::Sample;
+use crate::song::Song;
+use crate::synth::Synth;
+use core::borrow::Borrow;
+use core::f32::consts::FRAC_PI_2;
+
+/// How a [`MusicPlayer`] switches to a new song.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Transition {
+    /// Switch immediately.
+    #[default]
+    Cut,
+
+    /// Crossfade from the current song to the new song, starting immediately.
+    Crossfade {
+        /// Length of the crossfade.
+        samples: u32,
+    },
+
+    /// Keep playing the current song until the next pattern starts (or the song ends), then
+    /// crossfade to the new song. Use zero `samples` to switch without a crossfade.
+    NextPattern {
+        /// Length of the crossfade.
+        samples: u32,
+    },
+}
+
+impl Transition {
+    fn samples(self) -> u32 {
+        match self {
+            Self::Cut => 0,
+            Self::Crossfade { samples } | Self::NextPattern { samples } => samples,
+        }
+    }
+}
+
+/// Plays music made of several songs, switching between them with [`Transition`]s.
+///
+/// `MusicPlayer` implements `Iterator` just like [`Synth`], but the iterator never ends. It
+/// produces silence when no song is playing. Switching songs does not allocate, so the player can
+/// run in an audio callback.
+///
+/// ```
+/// use sonant::{MusicPlayer, Song, Synth, Transition};
+///
+/// let forest = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let cave = Song::from_slice(include_bytes!("../examples/microscope.snt"))?;
+///
+/// let mut player = MusicPlayer::new();
+/// player.play(Synth::new(&forest, (0, 1), 44100.0), Transition::Cut);
+/// player.by_ref().take(44100).for_each(drop);
+///
+/// // The player walks into a cave
+/// let transition = Transition::NextPattern { samples: 22050 };
+/// player.play(Synth::new(&cave, (0, 1), 44100.0), transition);
+/// for [sample_l, sample_r] in player.take(44100) {
+///     // Do something with the samples
+/// }
+/// # Ok::<(), sonant::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct MusicPlayer<S> {
+    current: Option<Synth<S>>,
+    incoming: Option<Synth<S>>,
+    resume: Option<Synth<S>>, // Interrupted by a stinger
+
+    // Transition state
+    waiting: bool, // The incoming song is waiting for a pattern boundary
+    stinger: bool, // The incoming song is a stinger
+    fade: u32,     // Samples elapsed in the crossfade
+    fade_length: u32,
+}
+
+impl<S: Borrow<Song>> Default for MusicPlayer<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Borrow<Song>> MusicPlayer<S> {
+    /// Create a `MusicPlayer` that is not playing anything yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            incoming: None,
+            resume: None,
+            waiting: false,
+            stinger: false,
+            fade: 0,
+            fade_length: 0,
+        }
+    }
+
+    /// Switch to the song played by `synth`, using the given `transition`.
+    ///
+    /// A transition that is already crossfading is finished immediately, and a song that is still
+    /// waiting for a pattern boundary is replaced. A song interrupted by a stinger will not resume.
+    pub fn play(&mut self, synth: Synth<S>, transition: Transition) {
+        self.resume = None;
+        self.start(synth, transition, false);
+    }
+
+    /// Play a one-shot stinger, like a jingle for a level-up. The current song is paused when the
+    /// `transition` to the stinger is done, and it resumes when the stinger ends, fading in over
+    /// the same number of samples as the transition.
+    ///
+    /// ```
+    /// use sonant::{MusicPlayer, Song, Synth, Transition};
+    ///
+    /// let music = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let jingle = Song::from_slice(include_bytes!("../examples/microscope.snt"))?;
+    ///
+    /// let mut player = MusicPlayer::new();
+    /// player.play(Synth::new(&music, (0, 1), 44100.0), Transition::Cut);
+    /// player.play_stinger(
+    ///     Synth::new(&jingle, (0, 1), 44100.0),
+    ///     Transition::Crossfade { samples: 441 },
+    /// );
+    /// # player.by_ref().take(1000).for_each(drop);
+    /// # assert!(player.is_playing());
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn play_stinger(&mut self, synth: Synth<S>, transition: Transition) {
+        self.start(synth, transition, true);
+    }
+
+    /// Check if any song is playing, or waiting to be played.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() || self.incoming.is_some() || self.resume.is_some()
+    }
+
+    /// Render interleaved stereo frames into `buffer`, in any [`Sample`] format. Returns the
+    /// number of frames written.
+    ///
+    /// Silence is rendered when no song is playing, so every complete frame in the buffer is
+    /// written.
+    pub fn render<T: Sample>(&mut self, buffer: &mut [T]) -> usize {
+        let mut frames = 0;
+
+        for (frame, samples) in buffer.chunks_exact_mut(NUM_CHANNELS).zip(self.by_ref()) {
+            for (output, sample) in frame.iter_mut().zip(samples) {
+                *output = T::from_f32(sample, 0.0);
+            }
+            frames += 1;
+        }
+
+        frames
+    }
+
+    fn start(&mut self, synth: Synth<S>, transition: Transition, stinger: bool) {
+        // Finish a crossfade that is already in progress
+        if self.incoming.is_some() && !self.waiting {
+            self.finish_transition();
+        }
+
+        self.incoming = Some(synth);
+        self.waiting = matches!(transition, Transition::NextPattern { .. });
+        self.stinger = stinger;
+        self.fade = 0;
+        self.fade_length = transition.samples();
+    }
+
+    fn finish_transition(&mut self) {
+        let previous = core::mem::replace(&mut self.current, self.incoming.take());
+
+        // Keep the song that the stinger interrupts, unless it is another stinger
+        if self.stinger && self.resume.is_none() {
+            self.resume = previous;
+        }
+        self.stinger = false;
+    }
+}
+
+impl<S: Borrow<Song>> Iterator for MusicPlayer<S> {
+    type Item = [f32; NUM_CHANNELS];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut boundary = false;
+        let mut samples = [0.0; NUM_CHANNELS];
+
+        if let Some(current) = &mut self.current {
+            let mut on_event = |_: &Position, event| {
+                if let Event::Pattern { .. } | Event::End = event {
+                    boundary = true;
+                }
+            };
+
+            if let Some(current_samples) = current.next_with_events(&mut on_event) {
+                samples = current_samples;
+            } else {
+                // The song (or stinger) has ended
+                self.current = None;
+                boundary = true;
+            }
+        }
+
+        // Resume the song that was interrupted by a stinger
+        if self.current.is_none() && self.incoming.is_none() {
+            if let Some(resume) = self.resume.take() {
+                let samples = self.fade_length;
+                self.start(resume, Transition::Crossfade { samples }, false);
+            }
+        }
+
+        // Nothing to wait for when no song is playing
+        if self.waiting && (boundary || self.current.is_none()) {
+            self.waiting = false;
+        }
+
+        if !self.waiting {
+            if let Some(incoming) = &mut self.incoming {
+                let incoming_samples = incoming.next().unwrap_or_default();
+
+                // Equal power crossfade
+                let t = if self.fade_length == 0 {
+                    1.0
+                } else {
+                    (self.fade + 1) as f32 / self.fade_length as f32
+                };
+                let gain_out = libm::cosf(t * FRAC_PI_2);
+                let gain_in = libm::sinf(t * FRAC_PI_2);
+                for (sample, incoming_sample) in samples.iter_mut().zip(incoming_samples) {
+                    *sample = *sample * gain_out + incoming_sample * gain_in;
+                }
+
+                self.fade += 1;
+                if self.fade >= self.fade_length {
+                    self.finish_transition();
+                }
+            }
+        }
+
+        Some(samples)
+    }
+}
@@ -0,0 +1,78 @@
+/// How the instrument oscillators generate their waveforms.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OscillatorMode {
+    /// Generate the waveforms directly, like the original player. Square, saw, and triangle
+    /// waves alias audibly at high pitches.
+    #[default]
+    Naive,
+
+    /// Smooth the discontinuities of square, saw, and triangle waves with polynomial band-limited
+    /// steps and ramps (`PolyBLEP` and `PolyBLAMP`). This removes most of the aliasing, at a small
+    /// cost in performance. Sine waves are unaffected.
+    BandLimited,
+}
+
+/// Residual of a band-limited unit step at phase `0`, for an oscillator at phase `t` advancing
+/// by `dt` per sample.
+fn blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt - 1.0;
+        -0.5 * x * x
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        0.5 * x * x
+    } else {
+        0.0
+    }
+}
+
+/// Residual of a band-limited unit ramp (a change in slope of `1` per sample) at phase `0`, for
+/// an oscillator at phase `t` advancing by `dt` per sample.
+fn blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = 1.0 - t / dt;
+        x * x * x / 6.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 6.0
+    } else {
+        0.0
+    }
+}
+
+/// Get the phase of an oscillator at time `t`, and the phase increment for the band-limiting
+/// corrections. Corrections are disabled when the oscillator is not advancing.
+fn phase(t: f32, dt: f32) -> (f32, f32) {
+    let dt = if dt > 0.0 { dt.min(0.5) } else { 0.0 };
+
+    (t - libm::floorf(t), dt)
+}
+
+/// Band-limited square wave generator
+pub(crate) fn square(t: f32, dt: f32) -> f32 {
+    let (t, dt) = phase(t, dt);
+    let half = libm::fmodf(t + 0.5, 1.0);
+    let value = if t < 0.5 { -1.0 } else { 1.0 };
+
+    value - 2.0 * blep(t, dt) + 2.0 * blep(half, dt)
+}
+
+/// Band-limited saw wave generator
+pub(crate) fn saw(t: f32, dt: f32) -> f32 {
+    let (t, dt) = phase(t, dt);
+
+    0.5 - t + blep(t, dt)
+}
+
+/// Band-limited triangle wave generator
+pub(crate) fn triangle(t: f32, dt: f32) -> f32 {
+    let (t, dt) = phase(t, dt);
+    let half = libm::fmodf(t + 0.5, 1.0);
+    let value = if t < 0.5 {
+        4.0 * t - 1.0
+    } else {
+        3.0 - 4.0 * t
+    };
+
+    value + 8.0 * dt * (blamp(t, dt) - blamp(half, dt))
+}
@@ -0,0 +1,161 @@
+use crate::consts::NUM_CHANNELS;
+use crate::song::Error;
+use crate::state::{StateReader, StateWriter};
+
+/// Oversampling of the instrument oscillators and filters.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Oversampling {
+    /// Run at the output sample rate, like the original player.
+    #[default]
+    Off,
+
+    /// Run at twice the output sample rate.
+    X2,
+
+    /// Run at four times the output sample rate.
+    X4,
+}
+
+impl Oversampling {
+    /// The number of samples generated for each output sample.
+    #[must_use]
+    pub fn factor(&self) -> usize {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+        }
+    }
+
+    pub(crate) fn from_factor(factor: usize) -> Option<Self> {
+        match factor {
+            1 => Some(Self::Off),
+            2 => Some(Self::X2),
+            4 => Some(Self::X4),
+            _ => None,
+        }
+    }
+}
+
+/// The largest oversampling factor.
+pub(crate) const MAX_FACTOR: usize = 4;
+
+/// Length of the halfband filter for decimating from 4x to 2x.
+const SHORT_LENGTH: usize = 15;
+
+/// Length of the halfband filter for decimating from 2x to the output sample rate.
+const LONG_LENGTH: usize = 31;
+
+// Halfband filter coefficients (Kaiser windowed sinc). Only the center tap and the odd taps on one
+// side of it are listed; the even taps are zero, and the filter is symmetric.
+const SHORT_CENTER: f32 = 0.499_758_87;
+const SHORT_TAPS: [f32; 4] = [3.007_941e-1, -6.270_991e-2, 1.271_238e-2, -6.760_067e-4];
+const LONG_CENTER: f32 = 0.499_971_5;
+const LONG_TAPS: [f32; 8] = [
+    3.137_375e-1,
+    -9.309_054e-2,
+    4.398_898e-2,
+    -2.159_19e-2,
+    9.804_082e-3,
+    -3.772_472e-3,
+    1.064_504e-3,
+    -1.258_613e-4,
+];
+
+/// Stereo halfband filter that halves the sample rate.
+#[derive(Clone, Debug)]
+struct HalfBand<const N: usize> {
+    history: [[f32; NUM_CHANNELS]; N],
+}
+
+impl<const N: usize> HalfBand<N> {
+    fn new() -> Self {
+        Self {
+            history: [[0.0; NUM_CHANNELS]; N],
+        }
+    }
+
+    /// Filter two input frames, and return a single output frame.
+    fn process(
+        &mut self,
+        center: f32,
+        taps: &[f32],
+        input: [[f32; NUM_CHANNELS]; 2],
+    ) -> [f32; NUM_CHANNELS] {
+        self.history.copy_within(2.., 0);
+        self.history[N - 2..].copy_from_slice(&input);
+
+        let c = N / 2;
+        let mut output = [0.0; NUM_CHANNELS];
+        for (ch, sample) in output.iter_mut().enumerate() {
+            *sample = center * self.history[c][ch];
+            for (k, tap) in taps.iter().enumerate() {
+                let offset = 2 * k + 1;
+                *sample += tap * (self.history[c - offset][ch] + self.history[c + offset][ch]);
+            }
+        }
+
+        output
+    }
+
+    fn write(&self, writer: &mut StateWriter) {
+        for frame in &self.history {
+            for &sample in frame {
+                writer.f32(sample);
+            }
+        }
+    }
+
+    fn read(&mut self, reader: &mut StateReader<'_>) -> Result<(), Error> {
+        for frame in &mut self.history {
+            for sample in frame {
+                *sample = reader.f32()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Serialized size of a [`Decimator`], in bytes.
+pub(crate) const DECIMATOR_STATE_LENGTH: usize = (SHORT_LENGTH + LONG_LENGTH) * NUM_CHANNELS * 4;
+
+/// Converts oversampled stereo frames back to the output sample rate.
+#[derive(Clone, Debug)]
+pub(crate) struct Decimator {
+    short: HalfBand<SHORT_LENGTH>,
+    long: HalfBand<LONG_LENGTH>,
+}
+
+impl Decimator {
+    pub(crate) fn new() -> Self {
+        Self {
+            short: HalfBand::new(),
+            long: HalfBand::new(),
+        }
+    }
+
+    /// Decimate the oversampled `input` frames (2 or 4 of them) to a single output frame.
+    pub(crate) fn process(&mut self, input: &[[f32; NUM_CHANNELS]]) -> [f32; NUM_CHANNELS] {
+        let input = match *input {
+            [a, b] => [a, b],
+            [a, b, c, d] => [
+                self.short.process(SHORT_CENTER, &SHORT_TAPS, [a, b]),
+                self.short.process(SHORT_CENTER, &SHORT_TAPS, [c, d]),
+            ],
+            _ => unreachable!("unsupported oversampling factor"),
+        };
+
+        self.long.process(LONG_CENTER, &LONG_TAPS, input)
+    }
+
+    pub(crate) fn write(&self, writer: &mut StateWriter) {
+        self.short.write(writer);
+        self.long.write(writer);
+    }
+
+    pub(crate) fn read(&mut self, reader: &mut StateReader<'_>) -> Result<(), Error> {
+        self.short.read(reader)?;
+        self.long.read(reader)
+    }
+}
@@ -0,0 +1,354 @@
+use crate::song::{parse_filter, parse_waveform, Filter, Instrument, Waveform};
+use core::num::Wrapping as w;
+use core::ops::RangeInclusive;
+
+/// An instrument parameter: one of the knobs and switches of an instrument in the tracker.
+///
+/// Values are in the units of the `.snt` file. Most knobs go from `0` to `255`, switches are `0`
+/// or `1`, the envelope is timed in 44.1 kHz samples, and the filter frequency is in Hz. See
+/// [`Param::range`].
+///
+/// ```
+/// use sonant::{Param, Song};
+///
+/// let mut song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// assert_eq!(song.param(0, Param::FxResonance), 240.0);
+///
+/// // Open up the filter of the kick drum
+/// song.set_param(0, Param::FxResonance, 255.0);
+/// # Ok::<(), sonant::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Param {
+    /// Octave of oscillator 0, where `8` is the pitch of the note.
+    Osc0Octave,
+    /// Semitones added to the pitch of oscillator 0.
+    Osc0Semitone,
+    /// Fine detune of oscillator 0, up to 20% above the pitch.
+    Osc0Detune,
+    /// Oscillator 0 frequency follows the envelope.
+    Osc0Envelope,
+    /// Volume of oscillator 0.
+    Osc0Volume,
+    /// Waveform of oscillator 0: sine, square, saw, or triangle.
+    Osc0Waveform,
+
+    /// Octave of oscillator 1, where `8` is the pitch of the note.
+    Osc1Octave,
+    /// Semitones added to the pitch of oscillator 1.
+    Osc1Semitone,
+    /// Fine detune of oscillator 1, up to 20% above the pitch.
+    Osc1Detune,
+    /// Oscillator 1 frequency follows the envelope.
+    Osc1Envelope,
+    /// Volume of oscillator 1.
+    Osc1Volume,
+    /// Waveform of oscillator 1: sine, square, saw, or triangle.
+    Osc1Waveform,
+
+    /// Volume of the noise oscillator.
+    NoiseVolume,
+
+    /// Attack time of the envelope, in 44.1 kHz samples.
+    EnvAttack,
+    /// Sustain time of the envelope, in 44.1 kHz samples.
+    EnvSustain,
+    /// Release time of the envelope, in 44.1 kHz samples.
+    EnvRelease,
+    /// Master volume of the instrument.
+    EnvMaster,
+
+    /// Filter type: none, high-pass, low-pass, band-pass, or notch.
+    FxFilter,
+    /// Filter frequency in Hz.
+    FxFreq,
+    /// Filter resonance.
+    FxResonance,
+    /// Time between delayed echoes, in eighth notes.
+    FxDelayTime,
+    /// Volume of each delayed echo, relative to the previous one.
+    FxDelayAmount,
+    /// Panning frequency, where `8` is one cycle per quarter note and each step doubles it.
+    FxPanFreq,
+    /// Panning amount.
+    FxPanAmount,
+
+    /// The LFO modulates the frequency of oscillator 0.
+    LfoOsc0Freq,
+    /// The LFO modulates the filter frequency.
+    LfoFxFreq,
+    /// LFO frequency, where `8` is one cycle per quarter note and each step doubles it.
+    LfoFreq,
+    /// LFO amount.
+    LfoAmount,
+    /// Waveform of the LFO: sine, square, saw, or triangle.
+    LfoWaveform,
+}
+
+impl Param {
+    /// Every parameter, in the order of the tracker.
+    pub const ALL: [Param; 29] = [
+        Self::Osc0Octave,
+        Self::Osc0Semitone,
+        Self::Osc0Detune,
+        Self::Osc0Envelope,
+        Self::Osc0Volume,
+        Self::Osc0Waveform,
+        Self::Osc1Octave,
+        Self::Osc1Semitone,
+        Self::Osc1Detune,
+        Self::Osc1Envelope,
+        Self::Osc1Volume,
+        Self::Osc1Waveform,
+        Self::NoiseVolume,
+        Self::EnvAttack,
+        Self::EnvSustain,
+        Self::EnvRelease,
+        Self::EnvMaster,
+        Self::FxFilter,
+        Self::FxFreq,
+        Self::FxResonance,
+        Self::FxDelayTime,
+        Self::FxDelayAmount,
+        Self::FxPanFreq,
+        Self::FxPanAmount,
+        Self::LfoOsc0Freq,
+        Self::LfoFxFreq,
+        Self::LfoFreq,
+        Self::LfoAmount,
+        Self::LfoWaveform,
+    ];
+
+    /// The section of the instrument that the parameter belongs to, like `"Oscillator 0"`.
+    #[must_use]
+    pub fn group(self) -> &'static str {
+        match self {
+            Self::Osc0Octave
+            | Self::Osc0Semitone
+            | Self::Osc0Detune
+            | Self::Osc0Envelope
+            | Self::Osc0Volume
+            | Self::Osc0Waveform => "Oscillator 0",
+            Self::Osc1Octave
+            | Self::Osc1Semitone
+            | Self::Osc1Detune
+            | Self::Osc1Envelope
+            | Self::Osc1Volume
+            | Self::Osc1Waveform => "Oscillator 1",
+            Self::NoiseVolume => "Noise",
+            Self::EnvAttack | Self::EnvSustain | Self::EnvRelease | Self::EnvMaster => "Envelope",
+            Self::FxFilter
+            | Self::FxFreq
+            | Self::FxResonance
+            | Self::FxDelayTime
+            | Self::FxDelayAmount
+            | Self::FxPanFreq
+            | Self::FxPanAmount => "Effects",
+            Self::LfoOsc0Freq
+            | Self::LfoFxFreq
+            | Self::LfoFreq
+            | Self::LfoAmount
+            | Self::LfoWaveform => "LFO",
+        }
+    }
+
+    /// The name of the parameter within its [group](Param::group), like `"Octave"`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Osc0Octave | Self::Osc1Octave => "Octave",
+            Self::Osc0Semitone | Self::Osc1Semitone => "Semitone",
+            Self::Osc0Detune | Self::Osc1Detune => "Detune",
+            Self::Osc0Envelope | Self::Osc1Envelope => "Envelope",
+            Self::Osc0Volume | Self::Osc1Volume | Self::NoiseVolume => "Volume",
+            Self::Osc0Waveform | Self::Osc1Waveform | Self::LfoWaveform => "Waveform",
+            Self::EnvAttack => "Attack",
+            Self::EnvSustain => "Sustain",
+            Self::EnvRelease => "Release",
+            Self::EnvMaster => "Master",
+            Self::FxFilter => "Filter",
+            Self::FxFreq | Self::LfoFreq => "Frequency",
+            Self::FxResonance => "Resonance",
+            Self::FxDelayTime => "Delay time",
+            Self::FxDelayAmount => "Delay amount",
+            Self::FxPanFreq => "Pan frequency",
+            Self::FxPanAmount => "Pan amount",
+            Self::LfoOsc0Freq => "Oscillator 0 frequency",
+            Self::LfoFxFreq => "Filter frequency",
+            Self::LfoAmount => "Amount",
+        }
+    }
+
+    /// The range of values that [`Song::set_param`](crate::Song::set_param) accepts.
+    #[must_use]
+    pub fn range(self) -> RangeInclusive<f32> {
+        match self {
+            Self::Osc0Envelope | Self::Osc1Envelope | Self::LfoOsc0Freq | Self::LfoFxFreq => {
+                0.0..=1.0
+            }
+            Self::Osc0Waveform | Self::Osc1Waveform | Self::LfoWaveform => 0.0..=3.0,
+            Self::FxFilter => 0.0..=4.0,
+            Self::Osc0Octave
+            | Self::Osc1Octave
+            | Self::FxDelayTime
+            | Self::FxPanFreq
+            | Self::LfoFreq => 0.0..=16.0,
+            Self::EnvAttack | Self::EnvSustain | Self::EnvRelease => 0.0..=100_000.0,
+            Self::FxFreq => 0.0..=11025.0,
+            Self::Osc0Semitone
+            | Self::Osc0Detune
+            | Self::Osc0Volume
+            | Self::Osc1Semitone
+            | Self::Osc1Detune
+            | Self::Osc1Volume
+            | Self::NoiseVolume
+            | Self::EnvMaster
+            | Self::FxResonance
+            | Self::FxDelayAmount
+            | Self::FxPanAmount
+            | Self::LfoAmount => 0.0..=255.0,
+        }
+    }
+
+    /// Check if the parameter only takes whole numbers. Only the filter frequency is continuous.
+    #[must_use]
+    pub fn is_stepped(self) -> bool {
+        self != Self::FxFreq
+    }
+}
+
+impl Instrument {
+    /// Get the value of `param`, in the units of the `.snt` file.
+    pub(crate) fn param(&self, param: Param) -> f32 {
+        let byte = |value: f32, scale: f32| libm::roundf(value * scale);
+        let switch = |value: bool| f32::from(u8::from(value));
+
+        match param {
+            Param::Osc0Octave | Param::Osc1Octave => {
+                let octave = self.osc[osc_index(param)].octave;
+                f32::from(i8::from_ne_bytes([octave]) / 12 + 8)
+            }
+            Param::Osc0Semitone | Param::Osc1Semitone => {
+                f32::from(self.osc[osc_index(param)].detune_freq)
+            }
+            Param::Osc0Detune | Param::Osc1Detune => {
+                byte(self.osc[osc_index(param)].detune - 1.0, 255.0 / 0.2)
+            }
+            Param::Osc0Envelope | Param::Osc1Envelope => {
+                switch(self.osc[osc_index(param)].envelope)
+            }
+            Param::Osc0Volume | Param::Osc1Volume => byte(self.osc[osc_index(param)].volume, 255.0),
+            Param::Osc0Waveform | Param::Osc1Waveform => {
+                f32::from(waveform_index(&self.osc[osc_index(param)].waveform))
+            }
+            Param::NoiseVolume => byte(self.noise_fader, 255.0),
+            Param::EnvAttack => self.env.attack as f32,
+            Param::EnvSustain => self.env.sustain as f32,
+            Param::EnvRelease => self.env.release as f32,
+            Param::EnvMaster => byte(self.env.master, 1.0 / 156.0),
+            Param::FxFilter => f32::from(filter_index(&self.fx.filter)),
+            Param::FxFreq => self.fx.freq,
+            Param::FxResonance => byte(self.fx.resonance, 255.0),
+            Param::FxDelayTime => f32::from(self.fx.delay_time),
+            Param::FxDelayAmount => byte(self.fx.delay_amount, 255.0),
+            Param::FxPanFreq => f32::from(self.fx.pan_freq),
+            Param::FxPanAmount => byte(self.fx.pan_amount, 512.0),
+            Param::LfoOsc0Freq => switch(self.lfo.osc0_freq),
+            Param::LfoFxFreq => switch(self.lfo.fx_freq),
+            Param::LfoFreq => f32::from(self.lfo.freq),
+            Param::LfoAmount => byte(self.lfo.amount, 512.0),
+            Param::LfoWaveform => f32::from(waveform_index(&self.lfo.waveform)),
+        }
+    }
+
+    /// Set the value of `param`, in the units of the `.snt` file. The value is clamped to the
+    /// range of the parameter, and rounded if the parameter is stepped.
+    pub(crate) fn set_param(&mut self, param: Param, value: f32) {
+        let range = param.range();
+        let mut value = value.clamp(*range.start(), *range.end());
+        if param.is_stepped() {
+            value = libm::roundf(value);
+        }
+        let byte = value as u8;
+
+        match param {
+            Param::Osc0Octave | Param::Osc1Octave => {
+                self.osc[osc_index(param)].octave = ((w(byte) - w(8)) * w(12)).0;
+            }
+            Param::Osc0Semitone | Param::Osc1Semitone => {
+                self.osc[osc_index(param)].detune_freq = byte;
+            }
+            Param::Osc0Detune | Param::Osc1Detune => {
+                self.osc[osc_index(param)].detune = libm::fmaf(value, 0.2 / 255.0, 1.0);
+            }
+            Param::Osc0Envelope | Param::Osc1Envelope => {
+                self.osc[osc_index(param)].envelope = byte != 0;
+            }
+            Param::Osc0Volume | Param::Osc1Volume => {
+                self.osc[osc_index(param)].volume = value / 255.0;
+            }
+            Param::Osc0Waveform | Param::Osc1Waveform => {
+                if let Ok(waveform) = parse_waveform(byte) {
+                    self.osc[osc_index(param)].waveform = waveform;
+                }
+            }
+            Param::NoiseVolume => self.noise_fader = value / 255.0,
+            Param::EnvAttack => self.env.attack = value as u32,
+            Param::EnvSustain => self.env.sustain = value as u32,
+            Param::EnvRelease => self.env.release = value as u32,
+            Param::EnvMaster => self.env.master = value * 156.0,
+            Param::FxFilter => {
+                if let Ok(filter) = parse_filter(byte) {
+                    self.fx.filter = filter;
+                }
+            }
+            Param::FxFreq => self.fx.freq = value,
+            Param::FxResonance => self.fx.resonance = value / 255.0,
+            Param::FxDelayTime => self.fx.delay_time = byte,
+            Param::FxDelayAmount => self.fx.delay_amount = value / 255.0,
+            Param::FxPanFreq => self.fx.pan_freq = byte,
+            Param::FxPanAmount => self.fx.pan_amount = value / 512.0,
+            Param::LfoOsc0Freq => self.lfo.osc0_freq = byte != 0,
+            Param::LfoFxFreq => self.lfo.fx_freq = byte != 0,
+            Param::LfoFreq => self.lfo.freq = byte,
+            Param::LfoAmount => self.lfo.amount = value / 512.0,
+            Param::LfoWaveform => {
+                if let Ok(waveform) = parse_waveform(byte) {
+                    self.lfo.waveform = waveform;
+                }
+            }
+        }
+    }
+}
+
+/// Get the oscillator that an oscillator parameter belongs to.
+fn osc_index(param: Param) -> usize {
+    match param {
+        Param::Osc1Octave
+        | Param::Osc1Semitone
+        | Param::Osc1Detune
+        | Param::Osc1Envelope
+        | Param::Osc1Volume
+        | Param::Osc1Waveform => 1,
+        _ => 0,
+    }
+}
+
+fn waveform_index(waveform: &Waveform) -> u8 {
+    match waveform {
+        Waveform::Sine => 0,
+        Waveform::Square => 1,
+        Waveform::Saw => 2,
+        Waveform::Triangle => 3,
+    }
+}
+
+fn filter_index(filter: &Filter) -> u8 {
+    match filter {
+        Filter::None => 0,
+        Filter::HighPass => 1,
+        Filter::LowPass => 2,
+        Filter::BandPass => 3,
+        Filter::Notch => 4,
+    }
+}
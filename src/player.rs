@@ -0,0 +1,296 @@
+use crate::song::Song;
+use crate::state::SynthState;
+use crate::synth::Synth;
+use core::borrow::Borrow;
+use cpal::traits::{DeviceTrait as _, HostTrait as _, StreamTrait as _};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+// Playback states
+const PLAYING: u8 = 0;
+const PAUSED: u8 = 1;
+const STOPPED: u8 = 2;
+
+/// Errors from [`Player`].
+#[derive(Debug, Error)]
+pub enum PlayerError {
+    /// No audio output device is available
+    #[error("No audio output device")]
+    NoOutputDevice,
+
+    /// The output device uses a sample format that is not supported
+    #[error("Unsupported audio sample format")]
+    UnsupportedSampleFormat,
+
+    /// The output device cannot be configured
+    #[error("Audio stream config error")]
+    AudioConfig(#[from] cpal::DefaultStreamConfigError),
+
+    /// The audio stream cannot be created
+    #[error("Audio stream builder error")]
+    AudioStream(#[from] cpal::BuildStreamError),
+
+    /// The audio stream cannot be started
+    #[error("Audio stream play error")]
+    AudioPlay(#[from] cpal::PlayStreamError),
+}
+
+/// Plays a song on the default audio output device.
+///
+/// The [`Synth`] runs directly in the audio callback, which does not allocate, lock, or wait on
+/// other threads. The controls are lock-free, so they can be used from any thread that has access
+/// to the player.
+///
+/// Stereo is mixed down for mono devices, and devices with more than two channels get the song in
+/// the first two channels. Playback starts as soon as the player is created.
+///
+/// ```no_run
+/// use sonant::{Player, Song};
+///
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+///
+/// // Wake up this thread when the song ends
+/// let thread = std::thread::current();
+/// let player = Player::with_end_callback(song, (0, 1), move || thread.unpark())?;
+/// player.set_volume(0.5);
+///
+/// while player.is_playing() {
+///     std::thread::park();
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Player {
+    // Playback ends when the stream is dropped
+    _stream: Stream,
+    controls: Arc<Controls>,
+    sample_rate: u32,
+}
+
+/// Controls shared with the audio callback.
+struct Controls {
+    state: AtomicU8,
+    rewind: AtomicBool,
+    volume: AtomicU32, // Bits of an f32
+}
+
+/// The state owned by the audio callback.
+struct Output<S, F> {
+    synth: Synth<S>,
+    start: SynthState,
+    controls: Arc<Controls>,
+    on_end: F,
+}
+
+impl Player {
+    /// Open the default output device and start playing `song` on it. The `seed` is used for the
+    /// noise generator of the [`Synth`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when there is no output device, or the audio stream cannot be
+    /// configured or started.
+    pub fn new<S>(song: S, seed: (u64, u64)) -> Result<Self, PlayerError>
+    where
+        S: Borrow<Song> + Send + 'static,
+    {
+        Self::with_end_callback(song, seed, || ())
+    }
+
+    /// Create a player like [`Player::new`], and call `on_end` each time the song ends.
+    ///
+    /// The callback runs on the audio thread, so it should return quickly without blocking.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when there is no output device, or the audio stream cannot be
+    /// configured or started.
+    pub fn with_end_callback<S, F>(
+        song: S,
+        seed: (u64, u64),
+        on_end: F,
+    ) -> Result<Self, PlayerError>
+    where
+        S: Borrow<Song> + Send + 'static,
+        F: FnMut() + Send + 'static,
+    {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or(PlayerError::NoOutputDevice)?;
+        let supported = device.default_output_config()?;
+        let format = supported.sample_format();
+        let config = StreamConfig::from(supported);
+        let sample_rate = config.sample_rate.0;
+
+        let controls = Arc::new(Controls {
+            state: AtomicU8::new(PLAYING),
+            rewind: AtomicBool::new(false),
+            volume: AtomicU32::new(1.0_f32.to_bits()),
+        });
+        let synth = Synth::new(song, seed, sample_rate as f32);
+        let output = Output {
+            start: synth.state(),
+            synth,
+            controls: Arc::clone(&controls),
+            on_end,
+        };
+
+        let stream = match format {
+            SampleFormat::I8 => output.build_stream::<i8>(&device, &config),
+            SampleFormat::I16 => output.build_stream::<i16>(&device, &config),
+            SampleFormat::I32 => output.build_stream::<i32>(&device, &config),
+            SampleFormat::I64 => output.build_stream::<i64>(&device, &config),
+            SampleFormat::U8 => output.build_stream::<u8>(&device, &config),
+            SampleFormat::U16 => output.build_stream::<u16>(&device, &config),
+            SampleFormat::U32 => output.build_stream::<u32>(&device, &config),
+            SampleFormat::U64 => output.build_stream::<u64>(&device, &config),
+            SampleFormat::F32 => output.build_stream::<f32>(&device, &config),
+            SampleFormat::F64 => output.build_stream::<f64>(&device, &config),
+            _ => return Err(PlayerError::UnsupportedSampleFormat),
+        }?;
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            controls,
+            sample_rate,
+        })
+    }
+
+    /// Start or resume playback.
+    pub fn play(&self) {
+        self.controls.state.store(PLAYING, Ordering::Release);
+    }
+
+    /// Pause playback. It resumes from the same position with [`Player::play`].
+    pub fn pause(&self) {
+        // A stopped player stays stopped
+        let _ = self.controls.state.compare_exchange(
+            PLAYING,
+            PAUSED,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+
+    /// Stop playback, and rewind to the start of the song.
+    pub fn stop(&self) {
+        self.controls.state.store(STOPPED, Ordering::Release);
+        self.controls.rewind.store(true, Ordering::Release);
+    }
+
+    /// Check if the song is playing. This is `false` while paused or stopped, and after the song
+    /// has ended.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.controls.state.load(Ordering::Acquire) == PLAYING
+    }
+
+    /// Check if playback is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.controls.state.load(Ordering::Acquire) == PAUSED
+    }
+
+    /// Set the volume as a linear gain. The default is `1.0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `volume` is negative or NaN.
+    pub fn set_volume(&self, volume: f32) {
+        assert!(volume >= 0.0, "volume must not be negative");
+        self.controls
+            .volume
+            .store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The volume set with [`Player::set_volume`].
+    #[must_use]
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.controls.volume.load(Ordering::Relaxed))
+    }
+
+    /// The sample rate of the output device.
+    #[must_use]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+impl core::fmt::Debug for Player {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Player")
+            .field("playing", &self.is_playing())
+            .field("paused", &self.is_paused())
+            .field("volume", &self.volume())
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, F> Output<S, F>
+where
+    S: Borrow<Song> + Send + 'static,
+    F: FnMut() + Send + 'static,
+{
+    fn build_stream<T>(
+        mut self,
+        device: &cpal::Device,
+        config: &StreamConfig,
+    ) -> Result<Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let channels = usize::from(config.channels);
+
+        device.build_output_stream(
+            config,
+            move |buffer: &mut [T], _: &cpal::OutputCallbackInfo| self.fill(buffer, channels),
+            // There is nothing to recover; the device is gone, or the stream will continue
+            |_| (),
+            None,
+        )
+    }
+
+    /// Fill the interleaved `buffer` with the next frames of the song, or silence.
+    fn fill<T: SizedSample + FromSample<f32>>(&mut self, buffer: &mut [T], channels: usize) {
+        if self.controls.rewind.swap(false, Ordering::AcqRel) {
+            self.rewind();
+        }
+
+        let volume = f32::from_bits(self.controls.volume.load(Ordering::Relaxed));
+        let mut playing = self.controls.state.load(Ordering::Acquire) == PLAYING;
+
+        for frame in buffer.chunks_mut(channels) {
+            let [left, right] = match playing.then(|| self.synth.next()) {
+                Some(Some(samples)) => samples.map(|sample| sample * volume),
+                Some(None) => {
+                    // The song has ended; the next play starts over
+                    playing = false;
+                    self.controls.state.store(STOPPED, Ordering::Release);
+                    self.rewind();
+                    (self.on_end)();
+
+                    [0.0; 2]
+                }
+                None => [0.0; 2],
+            };
+
+            match frame {
+                [mono] => *mono = T::from_sample((left + right) * 0.5),
+                [first, second, rest @ ..] => {
+                    *first = T::from_sample(left);
+                    *second = T::from_sample(right);
+                    rest.fill_with(|| T::from_sample(0.0));
+                }
+                [] => (),
+            }
+        }
+    }
+
+    fn rewind(&mut self) {
+        // The state was taken from the same synth, so it is always valid
+        let _ = self.synth.restore(&self.start);
+    }
+}
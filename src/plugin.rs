@@ -0,0 +1,95 @@
+use crate::song::Song;
+use crate::source::SynthSource;
+use crate::synth::Synth;
+use bevy::app::{App, Plugin};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetApp as _, AssetLoader, LoadContext};
+use bevy::audio::{AddAudioSource as _, Decodable};
+use bevy::reflect::TypePath;
+use thiserror::Error;
+
+/// The sample rate of songs played by Bevy. The audio output resamples it to the device rate.
+const SAMPLE_RATE: f32 = 44100.0;
+
+/// The seed for the noise generator of songs played by Bevy.
+const SEED: (u64, u64) = (0, 1);
+
+/// Adds support for playing `.snt` files with Bevy.
+///
+/// The plugin registers [`Song`] as an asset and an audio source, and adds a [`SongLoader`]. Add
+/// it after the asset and audio plugins:
+///
+/// ```no_run
+/// use bevy::audio::AudioPlugin;
+/// use bevy::prelude::*;
+/// use sonant::{SonantPlugin, Song};
+///
+/// fn play(mut commands: Commands, asset_server: Res<AssetServer>) {
+///     let song: Handle<Song> = asset_server.load("poseidon.snt");
+///     commands.spawn(AudioPlayer(song));
+/// }
+///
+/// App::new()
+///     .add_plugins((MinimalPlugins, AssetPlugin::default(), AudioPlugin::default()))
+///     .add_plugins(SonantPlugin)
+///     .add_systems(Startup, play)
+///     .run();
+/// ```
+#[derive(Debug, Default)]
+pub struct SonantPlugin;
+
+/// Loads `.snt` files as [`Song`] assets.
+#[derive(Debug, Default, TypePath)]
+pub struct SongLoader;
+
+/// Errors from [`SongLoader`].
+#[derive(Debug, Error)]
+pub enum SongLoaderError {
+    /// The song file cannot be read
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    /// The song file is invalid
+    #[error("Invalid song")]
+    Song(#[from] crate::song::Error),
+}
+
+impl Plugin for SonantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Song>()
+            .init_asset_loader::<SongLoader>();
+    }
+}
+
+impl AssetLoader for SongLoader {
+    type Asset = Song;
+    type Settings = ();
+    type Error = SongLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+
+        Ok(Song::from_slice(&data)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["snt"]
+    }
+}
+
+/// Songs are played from the start at 44.1 kHz, with the seed `(0, 1)` for the noise generator.
+/// Each playback gets its own copy of the song.
+impl Decodable for Song {
+    type DecoderItem = f32;
+    type Decoder = SynthSource<Song>;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthSource::new(Synth::new(self.clone(), SEED, SAMPLE_RATE))
+    }
+}
@@ -0,0 +1,97 @@
+use randomize::{Gen32 as _, PCG32};
+
+/// A sample format that [`Synth::render`](crate::Synth::render) can write.
+///
+/// Integer formats scale samples in the range `[-1.0, 1.0]` to their full range, round them, and
+/// clamp anything that is out of range. Unsigned formats are offset by half of their range, so
+/// silence is in the middle. Floating point formats are converted without scaling.
+///
+/// ```
+/// use sonant::{Sample, I24};
+///
+/// assert_eq!(i16::from_f32(1.0, 0.0), i16::MAX);
+/// assert_eq!(u8::from_f32(0.0, 0.0), 128);
+/// assert_eq!(I24::from_f32(-1.0, 0.0), I24(-0x7f_ffff));
+/// assert_eq!(f64::from_f32(0.5, 0.0), 0.5);
+/// ```
+pub trait Sample: Copy {
+    /// Convert a `sample`, adding `dither` before rounding. The dither is in units of the least
+    /// significant bit, and it is ignored by floating point formats.
+    fn from_f32(sample: f32, dither: f32) -> Self;
+}
+
+/// A 24-bit signed integer sample, stored in the lower bits of an `i32`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct I24(pub i32);
+
+/// Scale a `sample` to an integer in `-max - 1..=max`, with `dither`.
+fn quantize(sample: f32, max: i32, dither: f32) -> i32 {
+    let max = max as f32;
+
+    libm::roundf(sample * max + dither).clamp(-max - 1.0, max) as i32
+}
+
+impl Sample for i8 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        quantize(sample, Self::MAX.into(), dither) as Self
+    }
+}
+
+impl Sample for i16 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        quantize(sample, Self::MAX.into(), dither) as Self
+    }
+}
+
+impl Sample for I24 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        Self(quantize(sample, 0x7f_ffff, dither))
+    }
+}
+
+impl Sample for i32 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        // Single precision is not precise enough for 32 bits
+        let max = f64::from(Self::MAX);
+
+        libm::round(f64::from(sample) * max + f64::from(dither)).clamp(-max - 1.0, max) as Self
+    }
+}
+
+impl Sample for u8 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        (i8::from_f32(sample, dither) as Self) ^ 0x80
+    }
+}
+
+impl Sample for u16 {
+    fn from_f32(sample: f32, dither: f32) -> Self {
+        (i16::from_f32(sample, dither) as Self) ^ 0x8000
+    }
+}
+
+impl Sample for f32 {
+    fn from_f32(sample: f32, _dither: f32) -> Self {
+        sample
+    }
+}
+
+impl Sample for f64 {
+    fn from_f32(sample: f32, _dither: f32) -> Self {
+        sample.into()
+    }
+}
+
+/// Generate triangular (TPDF) dither, spanning +/- 1 LSB. This is the sum of two uniform
+/// distributions.
+pub(crate) fn tpdf(random: &mut PCG32) -> f32 {
+    random.next_f32_unit() - random.next_f32_unit()
+}
+
+/// Create the random number generator for dither. It uses a different stream than the noise
+/// generator of a synth with the same `seed`.
+pub(crate) fn dither_generator(seed: (u64, u64)) -> PCG32 {
+    let (state, inc) = seed;
+
+    PCG32::new(state, inc ^ 2)
+}
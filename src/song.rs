@@ -1,5 +1,6 @@
 use crate::consts::{HEADER_LENGTH, INSTRUMENT_LENGTH, NUM_INSTRUMENTS, NUM_PATTERNS};
 use crate::consts::{OSCILLATOR_LENGTH, PATTERN_LENGTH, SEQUENCE_LENGTH, SONG_LENGTH};
+use crate::param::Param;
 use arrayvec::ArrayVec;
 use byteorder::{ByteOrder as _, LittleEndian};
 use core::num::Wrapping as w;
@@ -25,11 +26,24 @@ pub enum Error {
     /// Invalid instruments
     #[cfg_attr(feature = "std", error("Invalid instruments"))]
     InvalidInstruments,
+
+    /// Invalid output layout
+    #[cfg_attr(feature = "std", error("Invalid output layout"))]
+    InvalidLayout,
+
+    /// Invalid synth state
+    #[cfg_attr(feature = "std", error("Invalid synth state"))]
+    InvalidState,
+
+    /// Invalid tuning
+    #[cfg_attr(feature = "std", error("Invalid tuning"))]
+    InvalidTuning,
 }
 
 /// A `Song` contains a list of up to 8 `Instruments` and defines the sample
 /// length for each row (in the tracker).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy::asset::Asset, bevy::reflect::TypePath))]
 pub struct Song {
     pub(crate) instruments: [Instrument; NUM_INSTRUMENTS],
     pub(crate) seq_length: usize, // Total number of patterns to play
@@ -39,7 +53,7 @@ pub struct Song {
 /// Contains two `Oscillator`s, a simple `Envelope`, `Effects` and `LFO`. The
 /// tracker `Sequence` (up to 48) is defined here, as well as the tracker
 /// `Patterns` (up to 10).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Instrument {
     pub(crate) osc: [Oscillator; 2],          // Oscillators 0 and 1
     pub(crate) noise_fader: f32,              // Noise Oscillator
@@ -51,7 +65,7 @@ pub(crate) struct Instrument {
 }
 
 /// The `Oscillator` defines the `Instrument` sound.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Oscillator {
     pub(crate) octave: u8,         // Octave knob
     pub(crate) detune_freq: u8,    // Detune frequency
@@ -63,7 +77,7 @@ pub(crate) struct Oscillator {
 
 /// `Envelope` is for compressing the sample amplitude over time.
 /// (E.g. raising and lowering volume.)
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Envelope {
     pub(crate) attack: u32,  // Attack
     pub(crate) sustain: u32, // Sustain
@@ -72,7 +86,7 @@ pub(crate) struct Envelope {
 }
 
 /// The `Effects` provide filtering, resonance, and panning.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Effects {
     pub(crate) filter: Filter,    // Hi, lo, bandpass, or notch toggle
     pub(crate) freq: f32,         // FX Frequency
@@ -85,7 +99,7 @@ pub(crate) struct Effects {
 
 /// `LFO` is a Low-Frequency Oscillator. It can be used to adjust the frequency
 /// of `Oscillator` 0 and `Effects` over time.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Lfo {
     pub(crate) osc0_freq: bool,    // Modify Oscillator 0 frequency (FM) toggle
     pub(crate) fx_freq: bool,      // Modify FX frequency toggle
@@ -95,13 +109,13 @@ pub(crate) struct Lfo {
 }
 
 /// Contains the tracker notes (up to 32).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Pattern {
     pub(crate) notes: [u8; PATTERN_LENGTH],
 }
 
 /// Available filters.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum Filter {
     None,
     HighPass,
@@ -111,7 +125,7 @@ pub(crate) enum Filter {
 }
 
 /// Available wave forms.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum Waveform {
     Sine,
     Square,
@@ -137,10 +151,8 @@ impl Song {
             return Err(Error::FileLength);
         }
 
-        // Get quarter note length and eighth note length (in samples)
-        // This properly handles odd quarter note lengths
+        // Get quarter note length (in samples)
         let quarter_note_length = LittleEndian::read_u32(&slice[..HEADER_LENGTH]);
-        let quarter_note_length = quarter_note_length - (quarter_note_length % 2);
 
         let seq_length = slice[HEADER_LENGTH + INSTRUMENT_LENGTH * 8] as usize;
         let mut instruments = ArrayVec::new();
@@ -157,9 +169,57 @@ impl Song {
             quarter_note_length,
         })
     }
+
+    /// The tempo of the song in beats per minute, like the tracker shows it. Each beat is four
+    /// rows of a pattern.
+    ///
+    /// ```
+    /// use sonant::Song;
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// assert_eq!(song.tempo().round(), 144.0);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn tempo(&self) -> f32 {
+        60.0 * 44100.0 / 4.0 / self.quarter_note_length as f32
+    }
+
+    /// The number of patterns in the sequence.
+    #[must_use]
+    pub fn sequence_length(&self) -> usize {
+        self.seq_length + 1
+    }
+
+    /// Get the value of parameter `param` of `instrument`, in the units of the `.snt` file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instrument` is not a valid instrument index (`0..8`).
+    #[must_use]
+    pub fn param(&self, instrument: usize, param: Param) -> f32 {
+        assert!(instrument < NUM_INSTRUMENTS, "invalid instrument");
+
+        self.instruments[instrument].param(param)
+    }
+
+    /// Set parameter `param` of `instrument`. The value is clamped to [`Param::range`], and
+    /// rounded to a whole number if the parameter [is stepped](Param::is_stepped).
+    ///
+    /// To change an instrument while it is playing, use [`Synth::set_param`](crate::Synth::set_param) or
+    /// [`InstrumentVoice::set_param`](crate::InstrumentVoice::set_param) instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instrument` is not a valid instrument index (`0..8`).
+    pub fn set_param(&mut self, instrument: usize, param: Param, value: f32) {
+        assert!(instrument < NUM_INSTRUMENTS, "invalid instrument");
+
+        self.instruments[instrument].set_param(param, value);
+    }
 }
 
-fn parse_waveform(waveform: u8) -> Result<Waveform, Error> {
+pub(crate) fn parse_waveform(waveform: u8) -> Result<Waveform, Error> {
     Ok(match waveform {
         0 => Waveform::Sine,
         1 => Waveform::Square,
@@ -169,6 +229,17 @@ fn parse_waveform(waveform: u8) -> Result<Waveform, Error> {
     })
 }
 
+pub(crate) fn parse_filter(filter: u8) -> Result<Filter, Error> {
+    Ok(match filter {
+        0 => Filter::None,
+        1 => Filter::HighPass,
+        2 => Filter::LowPass,
+        3 => Filter::BandPass,
+        4 => Filter::Notch,
+        _ => return Err(Error::InvalidFilter),
+    })
+}
+
 fn load_oscillator(slice: &[u8], i: usize, o: usize) -> Result<Oscillator, Error> {
     let i = i + o * OSCILLATOR_LENGTH;
     let octave = ((w(slice[i]) - w(8)) * w(12)).0;
@@ -203,14 +274,7 @@ fn load_envelope(slice: &[u8], i: usize) -> Envelope {
 }
 
 fn load_effects(slice: &[u8], i: usize) -> Result<Effects, Error> {
-    let filter = match slice[i] {
-        0 => Filter::None,
-        1 => Filter::HighPass,
-        2 => Filter::LowPass,
-        3 => Filter::BandPass,
-        4 => Filter::Notch,
-        _ => return Err(Error::InvalidFilter),
-    };
+    let filter = parse_filter(slice[i])?;
     let i = i + 3;
     let freq = f32::from_bits(LittleEndian::read_u32(&slice[i..i + 4]));
     let resonance = f32::from(slice[i + 4]) / 255.0;
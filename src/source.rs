@@ -0,0 +1,115 @@
+use crate::consts::NUM_CHANNELS;
+use crate::song::Song;
+use crate::synth::Synth;
+use core::borrow::Borrow;
+use core::time::Duration;
+
+/// Adapts a [`Synth`] into a [`rodio::Source`].
+///
+/// The source produces interleaved stereo samples at the sample rate of the `Synth`. It can be
+/// appended to a `rodio::Sink`, and used with any of the `rodio::Source` combinators, like `mix`,
+/// `repeat_infinite`, and `speed`.
+///
+/// With the `bevy` feature, it also implements the `Source` trait of the `rodio` version used by
+/// Bevy, and it is the decoder of the [`Song`] audio source.
+///
+/// ```
+/// use rodio::Source as _;
+/// use sonant::{Song, Synth, SynthSource};
+///
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let source = SynthSource::new(Synth::new(song, (0, 1), 44100.0));
+/// assert_eq!(source.total_duration().unwrap().as_secs(), 162);
+///
+/// // Play it twice as fast, on repeat
+/// let source = source.speed(2.0).repeat_infinite();
+/// # Ok::<(), sonant::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SynthSource<S> {
+    synth: Synth<S>,
+    duration: Option<Duration>,
+    frame: [f32; NUM_CHANNELS],
+    channel: usize, // Next sample to take from the frame
+}
+
+impl<S: Borrow<Song>> SynthSource<S> {
+    /// Create a source that plays the `synth` from its current position.
+    ///
+    /// The total duration is computed with [`Synth::duration`] when the source is created.
+    #[must_use]
+    pub fn new(synth: Synth<S>) -> Self {
+        Self {
+            duration: synth.duration(),
+            synth,
+            frame: [0.0; NUM_CHANNELS],
+            channel: NUM_CHANNELS,
+        }
+    }
+
+    /// Get the `Synth` back. Samples of a partially consumed frame are dropped.
+    #[must_use]
+    pub fn into_inner(self) -> Synth<S> {
+        self.synth
+    }
+}
+
+impl<S: Borrow<Song>> From<Synth<S>> for SynthSource<S> {
+    fn from(synth: Synth<S>) -> Self {
+        Self::new(synth)
+    }
+}
+
+impl<S: Borrow<Song>> Iterator for SynthSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channel >= NUM_CHANNELS {
+            self.frame = self.synth.next()?;
+            self.channel = 0;
+        }
+
+        let sample = self.frame[self.channel];
+        self.channel += 1;
+
+        Some(sample)
+    }
+}
+
+impl<S: Borrow<Song>> rodio::Source for SynthSource<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        // The format never changes
+        None
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        NUM_CHANNELS as rodio::ChannelCount
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        libm::roundf(self.synth.sample_rate()) as rodio::SampleRate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
+
+#[cfg(feature = "bevy")]
+impl<S: Borrow<Song>> bevy::audio::Source for SynthSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        rodio::Source::channels(self)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        rodio::Source::sample_rate(self)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
@@ -0,0 +1,180 @@
+use crate::consts::{
+    MAX_JUMPS, MAX_OVERLAPPING_NOTES, MAX_TRIGGERED_NOTES, NUM_INSTRUMENTS, SEQUENCE_LENGTH,
+};
+use crate::oversampling::DECIMATOR_STATE_LENGTH;
+use crate::song::Error;
+use arrayvec::ArrayVec;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Version of the serialized state format.
+pub(crate) const STATE_VERSION: u8 = 1;
+
+// Serialized sizes of each part of the state, in bytes
+const HEADER_LENGTH: usize = 1 + 16; // Version, PRNG
+const TIMING_LENGTH: usize = 4 + 4 + 8 + 4 + 4;
+const COUNTERS_LENGTH: usize = 4 * 5 + 8 * 2 + 2;
+const SEQUENCES_LENGTH: usize = 2 + 1 + NUM_INSTRUMENTS * SEQUENCE_LENGTH + 1 + MAX_JUMPS * 5;
+const NOTE_LENGTH: usize = 1 + 8 + 4 + 1 + 4 + 8 + 8 + 4 + 4;
+const TRIGGERED_NOTE_LENGTH: usize = 1 + 1 + 4 + 8 + 4;
+
+/// The largest possible serialized state, in bytes.
+const MAX_STATE_LENGTH: usize = HEADER_LENGTH
+    + TIMING_LENGTH
+    + COUNTERS_LENGTH
+    + SEQUENCES_LENGTH
+    + 1
+    + NUM_INSTRUMENTS * DECIMATOR_STATE_LENGTH
+    + NUM_INSTRUMENTS * (4 + MAX_OVERLAPPING_NOTES * NOTE_LENGTH)
+    + 1
+    + MAX_TRIGGERED_NOTES * TRIGGERED_NOTE_LENGTH;
+
+/// A snapshot of the playback state of a [`Synth`](crate::Synth), created with
+/// [`Synth::state`](crate::Synth::state).
+///
+/// Restoring the state with [`Synth::restore`](crate::Synth::restore) continues playback with
+/// sample-identical output. The state can be stored or sent elsewhere with
+/// [`SynthState::as_bytes`], and loaded again with [`SynthState::from_bytes`]. Only notes that are
+/// playing are stored, so the state is usually around a kilobyte.
+///
+/// The state includes changes to the sequence made with
+/// [`Synth::set_sequence_entry`](crate::Synth::set_sequence_entry), pending jumps, and the
+/// history of the oversampling filters, which adds about 3 KB while oversampling. It does not
+/// include the `Song`, the tuning, or settings of the output stage like the layout, gain, and
+/// clipping. Clipping statistics and level meters are not included, either.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SynthState {
+    bytes: ArrayVec<u8, MAX_STATE_LENGTH>,
+}
+
+impl SynthState {
+    /// Get the serialized state.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Load a state that was serialized with [`SynthState::as_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the bytes are too long or have an unknown format version. The
+    /// rest of the state is validated when it is restored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.first() != Some(&STATE_VERSION) {
+            return Err(Error::InvalidState);
+        }
+        let bytes = bytes.try_into().map_err(|_| Error::InvalidState)?;
+
+        Ok(Self { bytes })
+    }
+
+    pub(crate) fn writer() -> StateWriter {
+        let mut writer = StateWriter {
+            bytes: ArrayVec::new(),
+        };
+        writer.u8(STATE_VERSION);
+
+        writer
+    }
+
+    pub(crate) fn reader(&self) -> StateReader<'_> {
+        // Skip the version, which was checked when the state was created
+        StateReader {
+            bytes: &self.bytes[1..],
+        }
+    }
+}
+
+/// Serializes a [`SynthState`].
+pub(crate) struct StateWriter {
+    bytes: ArrayVec<u8, MAX_STATE_LENGTH>,
+}
+
+impl StateWriter {
+    pub(crate) fn finish(self) -> SynthState {
+        SynthState { bytes: self.bytes }
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(u8::from(value));
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        let mut buf = [0; 4];
+        LittleEndian::write_u32(&mut buf, value);
+        self.bytes.extend(buf);
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        let mut buf = [0; 8];
+        LittleEndian::write_u64(&mut buf, value);
+        self.bytes.extend(buf);
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+}
+
+/// Deserializes a [`SynthState`]. Every method returns [`Error::InvalidState`] when the state is
+/// truncated.
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl StateReader<'_> {
+    /// Ensure the whole state was read.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidState)
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::InvalidState);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidState),
+        }
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+}
@@ -1,7 +1,24 @@
-use crate::consts::{MAX_OVERLAPPING_NOTES, NUM_CHANNELS, NUM_INSTRUMENTS, PATTERN_LENGTH};
-use crate::song::{Envelope, Filter, Instrument, Song, Waveform};
+use crate::consts::{
+    MAX_CHANNELS, MAX_OVERLAPPING_NOTES, MAX_TRIGGERED_NOTES, NUM_CHANNELS, NUM_INSTRUMENTS,
+};
+use crate::consts::{NUM_PATTERNS, PATTERN_LENGTH, SEQUENCE_LENGTH};
+use crate::events::{Event, EventSink, Position};
+use crate::layout::Layout;
+use crate::master::{ClipReport, Clipping, Master};
+use crate::meter::{Meter, TrackLevel};
+use crate::oscillator::{self, OscillatorMode};
+use crate::oversampling::{Decimator, Oversampling, MAX_FACTOR};
+use crate::param::Param;
+use crate::sample::{self, Sample};
+use crate::song::{Envelope, Error, Filter, Instrument, Song, Waveform};
+use crate::state::{StateReader, StateWriter, SynthState};
+use crate::timeline::Timeline;
+use crate::tuning::{get_frequency, Tuning, ORIGINAL_REFERENCE_PITCH};
 use arrayvec::ArrayVec;
-use core::{f32::consts::PI, num::Wrapping as w};
+use core::borrow::{Borrow, BorrowMut};
+use core::f32::consts::PI;
+use core::num::Wrapping as w;
+use core::time::Duration;
 use randomize::{Gen32 as _, PCG32};
 
 /// The main struct for audio synthesis.
@@ -9,15 +26,45 @@ use randomize::{Gen32 as _, PCG32};
 /// `Synth` implements `Iterator`, so calling the `next` method on it will generate the next
 /// sample.
 ///
-/// Currently only generates 2-channel f32 samples at the given `sample_rate`.
-#[derive(Debug)]
-pub struct Synth<'a> {
-    song: &'a Song,
+/// Cloning a `Synth` creates an independent copy that continues playback from the same position.
+/// See [`Synth::state`] for a compact snapshot that can be serialized.
+///
+/// The iterator generates 2-channel f32 samples at the given `sample_rate`. Other channel layouts
+/// are available with [`Synth::set_layout`] and [`Synth::render`].
+///
+/// The `Synth` can borrow the `Song` (`Synth<&Song>`), own it (`Synth<Song>`), or share it with
+/// anything else that implements `Borrow<Song>`, like `Arc<Song>`. An owned or shared `Song` lets
+/// the `Synth` move into audio callbacks and threads that require `'static + Send`:
+///
+/// ```
+/// use sonant::{Song, Synth};
+/// use std::sync::Arc;
+///
+/// let song = Arc::new(Song::from_slice(include_bytes!("../examples/poseidon.snt"))?);
+/// let synth = Synth::new(Arc::clone(&song), (0, 1), 44100.0);
+///
+/// let handle = std::thread::spawn(move || {
+///     for [sample_l, sample_r] in synth.take(100) {
+///         // Do something with the samples
+///     }
+/// });
+/// handle.join().unwrap();
+///
+/// // Or give the song away entirely
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let synth: Synth<Song> = Synth::new(song, (0, 1), 44100.0);
+/// std::thread::spawn(move || synth.take(100).count()).join().unwrap();
+/// # Ok::<(), sonant::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Synth<S> {
+    song: S,
     random: PCG32,
     sample_rate: f32,
-    sample_ratio: f32,
-    quarter_note_length: u32,
-    eighth_note_length: u32,
+    sample_ratio: f32,       // Output samples per 44.1 kHz sample at normal speed
+    eighth_note_length: f64, // In samples, may be fractional
+    tempo_scale: f32,
+    playback_rate: f32,
 
     // TODO: Support seamless loops
 
@@ -25,277 +72,1446 @@ pub struct Synth<'a> {
     seq_count: usize,
     note_count: usize,
     sample_count: u32,
+    row_start: u32,       // Sample count at the start of the current row
+    eighth_pending: bool, // Notes for the current eighth note have not been loaded yet
+    eighth_count: u32,    // Eighth notes elapsed since the start of the song
+    eighth_phase: f64,    // Samples elapsed since the start of the current eighth note
+    env_time: f64,        // Time elapsed for envelopes, in 44.1 kHz samples
+    echo_end: u32,        // Eighth notes elapsed when the last echo starts, after the sequence
     tracks: [TrackState; NUM_INSTRUMENTS],
+
+    // Resequencing
+    sequences: [[u8; SEQUENCE_LENGTH]; NUM_INSTRUMENTS], // Patterns for each instrument
+    jump: Option<usize>,                                 // Sequence index to play next
+    timeline: Timeline,
+
+    // Live notes
+    sequencer: bool,
+    triggered: ArrayVec<TriggeredNote, MAX_TRIGGERED_NOTES>,
+
+    // Output stage
+    tuning: Tuning,
+    reference_pitch: f32,
+    oscillator_mode: OscillatorMode,
+    oversampling: Oversampling,
+    decimators: [Decimator; NUM_INSTRUMENTS],
+    layout: Layout,
+    master: Master,
+    meter: Meter,
+    dither: Option<PCG32>,
 }
 
 /// Iterator state for a single instrument track.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct TrackState {
-    env: Envelope,
-
     // Max simultaneous notes per track
     notes: [Note; MAX_OVERLAPPING_NOTES],
 
-    delay_samples: u32,
+    delay_eighths: u32,
     delay_count: u32,
 
-    // Static frequencies
-    pan_freq: f32,
-    lfo_freq: f32,
-}
+    // Static frequencies, in cycles per quarter note
+    pan_freq: f32,
+    lfo_freq: f32,
+}
+
+/// Data structure for quarter notes, which includes the pitch and sample
+/// counter reference for waveform modulation. It also contains state for sample
+/// synthesis and filtering.
+#[derive(Clone, Debug)]
+struct Note {
+    pitch: u8,
+    env_start: f64,
+    volume: f32,
+    swap_stereo: bool,
+
+    // Iterator state
+    env: f32,
+    osc_freq: [f32; 2],
+    osc_time: [f32; 2],
+    low: f32,
+    band: f32,
+}
+
+/// A note started with [`Synth::note_on`]. It is remembered until all of its delayed echoes have
+/// been played, since there is no pattern to find it in.
+#[derive(Clone, Debug)]
+struct TriggeredNote {
+    track: usize,
+    pitch: u8,
+    velocity: f32,
+    eighths: f64, // Song position when the note was started
+    round: u32,   // Next delay round
+}
+
+/// An [`EventSink`] that ignores all events.
+struct NoEvents;
+
+impl EventSink for NoEvents {
+    fn event(&mut self, _position: &Position, _event: Event) {}
+}
+
+/// Sine wave generator
+fn osc_sin(value: f32) -> f32 {
+    libm::sinf((value + 0.5) * PI * 2.0)
+}
+
+/// Square wave generator
+fn osc_square(value: f32) -> f32 {
+    if osc_sin(value) < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+/// Saw wave generator
+fn osc_saw(value: f32) -> f32 {
+    let fract = value - libm::truncf(value);
+    (1.0 - fract) - 0.5
+}
+
+/// Triangle wave generator
+fn osc_tri(value: f32) -> f32 {
+    let fract = value - libm::truncf(value);
+    let v2 = fract * 4.0;
+
+    if v2 < 2.0 {
+        v2 - 1.0
+    } else {
+        3.0 - v2
+    }
+}
+
+/// Get the phase of a low frequency oscillator running at `freq` cycles per quarter note, at song
+/// `position` (in quarter notes). Only the fractional part is returned, which keeps the phase
+/// precise even late in a song.
+fn get_phase(freq: f32, position: f64) -> f32 {
+    let t = f64::from(freq) * position;
+    (t - libm::trunc(t)) as f32
+}
+
+/// Get a sample from the waveform generator at time `t`
+fn get_osc_output(waveform: &Waveform, t: f32) -> f32 {
+    match waveform {
+        Waveform::Sine => osc_sin(t),
+        Waveform::Square => osc_square(t),
+        Waveform::Saw => osc_saw(t),
+        Waveform::Triangle => osc_tri(t),
+    }
+}
+
+/// Get a sample from the waveform generator at time `t` using the given oscillator `mode`. The
+/// oscillator advances by `dt` per sample.
+fn get_mode_osc_output(mode: OscillatorMode, waveform: &Waveform, t: f32, dt: f32) -> f32 {
+    match (mode, waveform) {
+        (OscillatorMode::Naive, _) | (_, Waveform::Sine) => get_osc_output(waveform, t),
+        (OscillatorMode::BandLimited, Waveform::Square) => oscillator::square(t, dt),
+        (OscillatorMode::BandLimited, Waveform::Saw) => oscillator::saw(t, dt),
+        (OscillatorMode::BandLimited, Waveform::Triangle) => oscillator::triangle(t, dt),
+    }
+}
+
+impl TrackState {
+    fn new() -> Self {
+        let mut notes = ArrayVec::new();
+        for _ in 0..MAX_OVERLAPPING_NOTES {
+            notes.push(Note::new(0, 0.0, 0.0, false));
+        }
+        let notes = notes.into_inner().unwrap();
+
+        Self {
+            notes,
+            delay_eighths: 0,
+            delay_count: 0,
+            pan_freq: 0.0,
+            lfo_freq: 0.0,
+        }
+    }
+
+    /// Load the static state that depends on the instrument parameters.
+    fn configure(&mut self, inst: &Instrument) {
+        // Configure delay
+        self.delay_eighths = u32::from(inst.fx.delay_time);
+        self.delay_count = if inst.fx.delay_amount == 0.0 {
+            // Special case for zero repeats
+            0
+        } else if self.delay_eighths == 0 {
+            // Special case for zero-delay time: only repeat once, because every repeat would
+            // start at the same time
+            1
+        } else if libm::fabsf(inst.fx.delay_amount - 1.0) < f32::EPSILON {
+            // Special case for infinite repeats
+            u32::MAX
+        } else {
+            // This gets the number of iterations required for the note
+            // volume to drop below the audible threshold.
+            let base = libm::logf(1.0 / inst.fx.delay_amount);
+            (libm::logf(256.0) / base) as u32
+        };
+
+        // Set LFO and panning frequencies
+        self.lfo_freq = get_frequency(1.0, 2.0, inst.lfo.freq, 8);
+        self.pan_freq = get_frequency(1.0, 2.0, inst.fx.pan_freq, 8);
+    }
+}
+
+impl Note {
+    fn new(pitch: u8, env_start: f64, volume: f32, swap_stereo: bool) -> Self {
+        Self {
+            pitch,
+            env_start,
+            volume,
+            swap_stereo,
+            env: 0.0,
+            osc_freq: [0.0; 2],
+            osc_time: [0.0; 2],
+            low: 0.0,
+            band: 0.0,
+        }
+    }
+}
+
+impl<S: Borrow<Song>> Synth<S> {
+    /// Create a `Synth` that will play the provided `Song`.
+    /// The optional seed will be used for the noise generator.
+    /// `Synth` implements `Iterator` and generates two stereo samples at a time.
+    ///
+    /// ```no_run
+    /// use byteorder::{ByteOrder, NativeEndian};
+    /// use getrandom::getrandom;
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    ///
+    /// // Create a seed for the PRNG
+    /// let mut seed = [0_u8; 16];
+    /// getrandom(&mut seed).expect("failed to getrandom");
+    /// let seed = (
+    ///     NativeEndian::read_u64(&seed[0..8]),
+    ///     NativeEndian::read_u64(&seed[8..16]),
+    /// );
+    ///
+    /// let synth = Synth::new(&song, seed, 44100.0);
+    /// for [sample_l, sample_r] in synth {
+    ///     // Do something with the samples
+    /// }
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn new(song: S, seed: (u64, u64), sample_rate: f32) -> Self {
+        let mut synth = Self::without_sequencer(song, seed, sample_rate);
+        synth.sequencer = true;
+
+        synth
+    }
+
+    /// Create a `Synth` that only plays notes started with [`Synth::note_on`]. It never ends.
+    pub(crate) fn without_sequencer(song: S, seed: (u64, u64), sample_rate: f32) -> Self {
+        let random = PCG32::new(seed.0, seed.1);
+        let tracks = Self::load_tracks(song.borrow());
+        let sequences = Self::load_sequences(song.borrow());
+
+        let mut synth = Synth {
+            song,
+            random,
+            sample_rate,
+            sample_ratio: sample_rate / 44100.0,
+            eighth_note_length: 1.0,
+            tempo_scale: 1.0,
+            playback_rate: 1.0,
+            seq_count: 0,
+            sample_count: 0,
+            row_start: 0,
+            eighth_pending: true,
+            note_count: 0,
+            eighth_count: 0,
+            eighth_phase: 0.0,
+            env_time: 0.0,
+            echo_end: 0,
+            tracks,
+            sequences,
+            jump: None,
+            timeline: Timeline::new(),
+            sequencer: false,
+            triggered: ArrayVec::new(),
+            tuning: Tuning::default(),
+            reference_pitch: ORIGINAL_REFERENCE_PITCH,
+            oscillator_mode: OscillatorMode::default(),
+            oversampling: Oversampling::default(),
+            decimators: Self::load_decimators(),
+            layout: Layout::default(),
+            master: Master::new(),
+            meter: Meter::new(),
+            dither: None,
+        };
+        synth.retime();
+
+        synth
+    }
+
+    /// Start playing a note on instrument `track`, independently of the sequencer. This can be
+    /// used to play the instruments as sound effects, on top of the song.
+    ///
+    /// The note is mixed in starting with the next sample, and it is played just like a note from
+    /// a pattern, including delayed echoes. `pitch` uses the same scale as the patterns, where `0`
+    /// is silence. `velocity` scales the volume of the note, and is normally in the range
+    /// `0.0..=1.0`.
+    ///
+    /// Up to 16 notes (and their echoes) can be tracked at once. When more notes are started, the
+    /// echoes of the oldest are dropped.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // Fire the laser!
+    /// synth.note_on(3, 140, 1.0);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `track` is not a valid instrument index (`0..8`).
+    pub fn note_on(&mut self, track: usize, pitch: u8, velocity: f32) {
+        assert!(track < NUM_INSTRUMENTS, "invalid instrument track");
+        if pitch == 0 {
+            return;
+        }
+
+        self.start_note(&mut NoEvents, track, pitch, velocity, 0);
+
+        if self.tracks[track].delay_count > 0 {
+            if self.triggered.is_full() {
+                self.triggered.remove(0);
+            }
+            self.triggered.push(TriggeredNote {
+                track,
+                pitch,
+                velocity,
+                eighths: self.eighths(),
+                round: 1,
+            });
+
+            // Zero-delay echoes start at the same time
+            self.load_triggered_echoes(&mut NoEvents);
+        }
+    }
+
+    /// Jump to pattern `sequence` in the sequence when the current pattern ends, instead of
+    /// continuing with the next one. This can be used for adaptive music, and for looping. Calling
+    /// it again before the jump replaces the pending jump.
+    ///
+    /// Delayed echoes of notes played before the jump keep playing. Only the last 16 jumps are
+    /// remembered for this purpose; older echoes are dropped.
+    ///
+    /// ```
+    /// use sonant::{Event, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // Play the first pattern twice
+    /// synth.jump_to(0);
+    ///
+    /// let mut patterns = Vec::new();
+    /// while patterns.len() < 2 {
+    ///     synth.next_with_events(&mut |_: &_, event| {
+    ///         if let Event::Pattern { sequence } = event {
+    ///             patterns.push(sequence);
+    ///         }
+    ///     });
+    /// }
+    /// assert_eq!(patterns, [0, 0]);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sequence` is beyond the end of the song.
+    pub fn jump_to(&mut self, sequence: usize) {
+        assert!(sequence <= self.song().seq_length, "invalid sequence index");
+        self.jump = Some(sequence);
+    }
+
+    /// Cancel a jump requested with [`Synth::jump_to`] that has not happened yet.
+    pub fn cancel_jump(&mut self) {
+        self.jump = None;
+    }
+
+    /// Change the pattern that instrument `track` plays at position `sequence` in the sequence.
+    /// `pattern` is a pattern number (`1..=10`), or `0` for silence.
+    ///
+    /// The change takes effect the next time `sequence` is played. Notes of the previous pattern
+    /// that have already been played are not affected, but delayed echoes that are still due will
+    /// repeat notes from the new pattern.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // Mute the kick drum in the second pattern
+    /// synth.set_sequence_entry(0, 1, 0);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `track` is not a valid instrument index (`0..8`), `sequence` is not a valid
+    /// sequence index (`0..48`), or `pattern` is not a valid pattern number.
+    pub fn set_sequence_entry(&mut self, track: usize, sequence: usize, pattern: usize) {
+        assert!(track < NUM_INSTRUMENTS, "invalid instrument track");
+        assert!(sequence < SEQUENCE_LENGTH, "invalid sequence index");
+        assert!(pattern <= NUM_PATTERNS, "invalid pattern number");
+
+        self.sequences[track][sequence] = pattern as u8;
+    }
+
+    /// Undo all changes made with [`Synth::set_sequence_entry`].
+    pub fn reset_sequences(&mut self) {
+        self.sequences = Self::load_sequences(self.song());
+    }
+
+    /// Get the value of parameter `param` of instrument `track`. See [`Song::param`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `track` is not a valid instrument index (`0..8`).
+    #[must_use]
+    pub fn param(&self, track: usize, param: Param) -> f32 {
+        self.song().param(track, param)
+    }
+
+    /// Get the `Song` being played.
+    fn song(&self) -> &Song {
+        self.song.borrow()
+    }
+
+    /// Get the position of the next sample to be generated.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// synth.by_ref().take(44100).for_each(drop);
+    /// let position = synth.position();
+    /// assert_eq!(position.elapsed.as_secs(), 1);
+    /// println!("Pattern {} row {}", position.sequence, position.row);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn position(&self) -> Position {
+        let elapsed = f64::from(self.sample_count) / f64::from(self.sample_rate);
+
+        Position {
+            sequence: self.seq_count,
+            row: self.note_count,
+            row_sample: self.sample_count - self.row_start,
+            sample: self.sample_count,
+            elapsed: Duration::from_secs_f64(elapsed),
+        }
+    }
+
+    /// Compute the total duration of the song, from the start until the last note and its delayed
+    /// echoes have faded out. Returns `None` if the delayed echoes of any track repeat forever.
+    ///
+    /// The duration is computed from the sequence and the current tempo scale and playback rate.
+    /// Jumps and notes started with [`Synth::note_on`] are not included.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// let duration = synth.duration().unwrap();
+    /// assert_eq!(duration.as_secs(), 162);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn duration(&self) -> Option<Duration> {
+        let song = self.song();
+        let rows = (song.seq_length + 1) * PATTERN_LENGTH;
+        let mut samples = (rows * 2) as f64 * self.eighth_note_length;
+
+        for (i, (track, inst)) in self.tracks.iter().zip(&song.instruments).enumerate() {
+            let Some(row) = self.last_note_row(i) else {
+                continue;
+            };
+            if track.delay_count == u32::MAX {
+                return None;
+            }
+
+            // The last echo of the note plays for the full length of the envelope, and ends on the
+            // sample after that
+            let eighths =
+                (row * 2) as f64 + f64::from(track.delay_eighths) * f64::from(track.delay_count);
+            let env = &inst.env;
+            let length = f64::from(env.attack) + f64::from(env.sustain) + f64::from(env.release);
+            let start = libm::ceil(eighths * self.eighth_note_length);
+            samples = samples.max(start + length * f64::from(self.time_ratio()) + 1.0);
+        }
+
+        Some(Duration::from_secs_f64(
+            libm::ceil(samples) / f64::from(self.sample_rate),
+        ))
+    }
+
+    /// Find the last row of the sequence where instrument `track` plays a note.
+    fn last_note_row(&self, track: usize) -> Option<usize> {
+        let song = self.song();
+        let inst = &song.instruments[track];
+        let sequence = &self.sequences[track];
+        let rows = (song.seq_length + 1) * PATTERN_LENGTH;
+
+        (0..rows).rev().find(|&row| {
+            let p = usize::from(sequence[row / PATTERN_LENGTH]);
+
+            p != 0 && inst.pat[p - 1].notes[row % PATTERN_LENGTH] != 0
+        })
+    }
+
+    /// Get the number of eighth notes elapsed when the last delayed echo of the sequence starts.
+    /// Tracks with infinite echoes never end.
+    fn last_echo(&self) -> u32 {
+        let rows = (self.song().seq_length + 1) * PATTERN_LENGTH;
+        let Some(end) = self.timeline.elapsed((rows * 2) as u32) else {
+            return 0;
+        };
+
+        (0..NUM_INSTRUMENTS)
+            .filter_map(|i| {
+                let row = self.last_note_row(i)?;
+                let track = &self.tracks[i];
+                let echoes = u64::from(track.delay_eighths) * u64::from(track.delay_count);
+
+                Some((u64::from(end) + echoes).saturating_sub(((rows - row) * 2) as u64))
+            })
+            .max()
+            .map_or(end, |eighths| eighths.min(u64::from(u32::MAX)) as u32)
+    }
+
+    /// Compute the most notes that each instrument track plays at once, including delayed echoes.
+    /// Tracks that need more than [`MAX_OVERLAPPING_NOTES`] voices cut off their oldest notes.
+    ///
+    /// Like [`Synth::duration`], this follows the sequence at the current tempo scale and playback
+    /// rate. Echoes that repeat forever are counted until the end of the sequence.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth, MAX_OVERLAPPING_NOTES};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // The echoes of the kick drum need more voices than the synth has
+    /// let polyphony = synth.max_polyphony();
+    /// assert!(polyphony[0] > MAX_OVERLAPPING_NOTES);
+    /// assert_eq!(polyphony[5], 2);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn max_polyphony(&self) -> [usize; NUM_INSTRUMENTS] {
+        let song = self.song();
+        let rows = (song.seq_length + 1) * PATTERN_LENGTH;
+
+        // Envelopes are timed in 44.1 kHz samples
+        let eighth_note_length = self.eighth_note_length / f64::from(self.time_ratio());
+
+        let mut polyphony = [0; NUM_INSTRUMENTS];
+        let tracks = self
+            .tracks
+            .iter()
+            .zip(&song.instruments)
+            .zip(&self.sequences);
+        for (voices, ((track, inst), sequence)) in polyphony.iter_mut().zip(tracks) {
+            let env = &inst.env;
+            let length = f64::from(env.attack) + f64::from(env.sustain) + f64::from(env.release);
+            let rounds = match track.delay_count {
+                u32::MAX => (rows * 2) as u32 / track.delay_eighths + 1,
+                count => count,
+            };
+
+            // Each note and echo starts and ends a voice. Voices end before others start.
+            let mut changes = Vec::new();
+            for row in 0..rows {
+                let p = usize::from(sequence[row / PATTERN_LENGTH]);
+                if p == 0 || inst.pat[p - 1].notes[row % PATTERN_LENGTH] == 0 {
+                    continue;
+                }
+
+                for round in 0..=rounds {
+                    let eighths = (row * 2) as f64 + f64::from(track.delay_eighths * round);
+                    let start = eighths * eighth_note_length;
+                    changes.push((start, 1));
+                    changes.push((start + length, -1));
+                }
+            }
+            changes.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+            let mut playing = 0_i32;
+            for (_, change) in changes {
+                playing += change;
+                *voices = (*voices).max(playing as usize);
+            }
+        }
+
+        polyphony
+    }
+
+    /// Generate the next stereo sample like [`Iterator::next`], and send any events that occur at
+    /// the sample to `events`.
+    ///
+    /// ```
+    /// use sonant::{Event, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// let mut rows = 0;
+    /// let mut on_event = |_: &_, event| {
+    ///     if let Event::Row { .. } = event {
+    ///         rows += 1;
+    ///     }
+    /// };
+    /// while let Some([sample_l, sample_r]) = synth.next_with_events(&mut on_event) {
+    ///     // Do something with the samples
+    /// #   break;
+    /// }
+    /// # assert_eq!(rows, 1);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn next_with_events(&mut self, events: &mut impl EventSink) -> Option<[f32; NUM_CHANNELS]> {
+        let tracks = self.next_tracks(events)?;
+
+        let mut samples = [0.0; NUM_CHANNELS];
+        Layout::Stereo.mix(&tracks, &mut samples);
+
+        // Apply gain and clipping
+        for sample in &mut samples {
+            *sample = self.master.process(*sample);
+        }
+
+        Some(samples)
+    }
+
+    /// Check if any notes are playing, or have echoes waiting to be played.
+    pub(crate) fn is_playing(&self) -> bool {
+        !self.triggered.is_empty()
+            || self
+                .tracks
+                .iter()
+                .flat_map(|x| x.notes.iter())
+                .any(|x| x.pitch != 0)
+    }
+
+    /// The output sample rate, in Hz.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Scale the tempo without changing the pitch. A scale of `2.0` plays rows twice as fast, and
+    /// `0.5` plays them at half speed. The default is `1.0`.
+    ///
+    /// The change takes effect on the next sample. Delays, the LFO, and panning follow the new
+    /// tempo without skipping, because they are all timed relative to the rows.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // The action is heating up!
+    /// synth.set_tempo_scale(1.25);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is not a positive number.
+    pub fn set_tempo_scale(&mut self, scale: f32) {
+        assert!(scale > 0.0, "tempo scale must be positive");
+        self.tempo_scale = scale;
+        self.retime();
+    }
+
+    /// Change the playback speed like a turntable (varispeed). A rate of `2.0` plays the song
+    /// twice as fast and an octave higher, including the filter cutoff frequencies. The default is
+    /// `1.0`.
+    ///
+    /// The change takes effect on the next sample, including for notes that are already playing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not a positive number.
+    pub fn set_playback_rate(&mut self, rate: f32) {
+        assert!(rate > 0.0, "playback rate must be positive");
+
+        // Rescale the oscillator frequencies of playing notes
+        let scale = rate / self.playback_rate;
+        for note in self.tracks.iter_mut().flat_map(|x| x.notes.iter_mut()) {
+            for freq in &mut note.osc_freq {
+                *freq *= scale;
+            }
+        }
+
+        self.playback_rate = rate;
+        self.retime();
+    }
+
+    /// Get the number of output samples that play each 44.1 kHz sample of the song, including the
+    /// playback rate.
+    fn time_ratio(&self) -> f32 {
+        self.sample_ratio / self.playback_rate
+    }
+
+    /// Recompute the length of an eighth note from the sample rate, playback rate, and tempo.
+    ///
+    /// The position within the current eighth note is rescaled, so playback continues smoothly.
+    fn retime(&mut self) {
+        let quarter_note_length =
+            f64::from(self.time_ratio()) * f64::from(self.song().quarter_note_length);
+        let eighth_note_length = quarter_note_length / 2.0 / f64::from(self.tempo_scale);
+
+        // Keep the fractional position within the current eighth note
+        self.eighth_phase *= eighth_note_length / self.eighth_note_length;
+
+        self.eighth_note_length = eighth_note_length;
+    }
+
+    /// Get the song position in eighth notes, including the fraction of the current eighth note.
+    fn eighths(&self) -> f64 {
+        f64::from(self.eighth_count) + self.eighth_phase / self.eighth_note_length
+    }
+
+    /// Get the song position in quarter notes, including the fraction of the current quarter note.
+    fn quarter_notes(&self) -> f64 {
+        self.eighths() / 2.0
+    }
+
+    /// Set the channel layout used by [`Synth::render`]. The default is [`Layout::Stereo`].
+    ///
+    /// The `Iterator` implementation always produces stereo samples, regardless of the layout.
+    ///
+    /// ```
+    /// use sonant::{Layout, Route, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // Quadraphonic: the first four tracks in front, the last four in the back
+    /// let front = Route::Pair(0, 1);
+    /// let back = Route::Pair(2, 3);
+    /// synth.set_layout(Layout::Multichannel {
+    ///     channels: 4,
+    ///     tracks: [front, front, front, front, back, back, back, back],
+    /// })?;
+    ///
+    /// let mut buffer = [0.0; 4 * 512];
+    /// assert_eq!(synth.render(&mut buffer), 512);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the layout references a channel that does not exist, or has more
+    /// than [`MAX_CHANNELS`](crate::MAX_CHANNELS) channels.
+    pub fn set_layout(&mut self, layout: Layout) -> Result<(), Error> {
+        layout.validate()?;
+        self.layout = layout;
+
+        Ok(())
+    }
+
+    /// The channel layout used by [`Synth::render`].
+    #[must_use]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Render interleaved frames into `buffer` using the configured [`Layout`].
+    ///
+    /// Each frame contains [`Layout::channels`] samples, in any [`Sample`] format. Returns the
+    /// number of frames written, which is less than the buffer can hold only when the song has
+    /// ended. Any trailing samples that do not make up a full frame are left untouched.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// let mut buffer = [0_i16; 2 * 512];
+    /// assert_eq!(synth.render(&mut buffer), 512);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn render<T: Sample>(&mut self, buffer: &mut [T]) -> usize {
+        self.render_with_events(buffer, &mut NoEvents)
+    }
+
+    /// Render interleaved frames into `buffer` like [`Synth::render`], and send any events that
+    /// occur to `events`.
+    ///
+    /// Use [`Position::sample`] to find the frame within the buffer where each event occurs.
+    pub fn render_with_events<T: Sample>(
+        &mut self,
+        buffer: &mut [T],
+        events: &mut impl EventSink,
+    ) -> usize {
+        let layout = self.layout;
+        let channels = layout.channels();
+        let mut mixed = [0.0; MAX_CHANNELS];
+        let mut frames = 0;
+
+        for frame in buffer.chunks_exact_mut(channels) {
+            let Some(tracks) = self.next_tracks(events) else {
+                break;
+            };
+
+            layout.mix(&tracks, &mut mixed[..channels]);
+            for (output, &sample) in frame.iter_mut().zip(&mixed) {
+                let dither = self.dither.as_mut().map_or(0.0, sample::tpdf);
+                *output = T::from_f32(self.master.process(sample), dither);
+            }
+            frames += 1;
+        }
+
+        frames
+    }
+
+    /// Enable triangular (TPDF) dither for integer formats in [`Synth::render`]. The default is
+    /// disabled. Dither masks the distortion of quantizing quiet passages to few bits.
+    ///
+    /// The dither is seeded from the current state of the noise generator, so it is reproducible.
+    /// It is not included in [`Synth::state`].
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    /// synth.set_dither(true);
+    ///
+    /// let mut buffer = [0_u8; 2 * 512];
+    /// assert_eq!(synth.render(&mut buffer), 512);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn set_dither(&mut self, enabled: bool) {
+        self.dither =
+            enabled.then(|| sample::dither_generator((self.random.state, self.random.inc)));
+    }
+
+    /// Set how the instrument oscillators generate their waveforms. The default is
+    /// [`OscillatorMode::Naive`], which sounds like the original player.
+    ///
+    /// ```
+    /// use sonant::{OscillatorMode, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 96000.0);
+    /// synth.set_oscillator_mode(OscillatorMode::BandLimited);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn set_oscillator_mode(&mut self, mode: OscillatorMode) {
+        self.oscillator_mode = mode;
+    }
+
+    /// Set the tuning of the notes. The default is [`Tuning::default`], the 12-TET tuning of the
+    /// original player. The tuning applies to notes started after the change.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth, Tuning};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// // Just intonation, with a major scale on the white keys
+    /// let scale = "Just\n12\n16/15\n9/8\n6/5\n5/4\n4/3\n45/32\n3/2\n8/5\n5/3\n9/5\n15/8\n2/1\n";
+    /// synth.set_tuning(Tuning::from_scala(scale, None)?);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+    }
+
+    /// Set the reference pitch: the frequency of A4 (note `144`) in the default tuning, in Hz.
+    /// Every tuning is transposed by the same ratio, including Scala tunings that set their own
+    /// reference frequency. The original player is tuned to about 434 Hz.
+    ///
+    /// The reference pitch applies to notes started after the change.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    /// synth.set_reference_pitch(440.0);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pitch` is not a positive number.
+    pub fn set_reference_pitch(&mut self, pitch: f32) {
+        assert!(pitch > 0.0, "reference pitch must be positive");
+        self.reference_pitch = pitch;
+    }
+
+    /// Set the oversampling of the instrument oscillators and filters. The default is
+    /// [`Oversampling::Off`].
+    ///
+    /// Oversampling keeps resonant filter sweeps stable and less warped near the Nyquist frequency,
+    /// and reduces aliasing, at the cost of CPU time. The oversampled output is decimated with a
+    /// halfband filter, which delays the output by about 8 samples.
+    ///
+    /// ```
+    /// use sonant::{Oversampling, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    /// synth.set_oversampling(Oversampling::X4);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversampling = oversampling;
+        self.decimators = Self::load_decimators();
+    }
+
+    /// Set the master gain, applied to the mixed samples before clipping. The default is `1.0`.
+    ///
+    /// This scales the whole song without editing the master volume of every instrument.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.master.gain = gain;
+    }
+
+    /// Set the clipping behavior of the master output stage. The default is [`Clipping::Hard`].
+    pub fn set_clipping(&mut self, clipping: Clipping) {
+        self.master.clipping = clipping;
+    }
+
+    /// Get the clipping statistics collected since the `Synth` was created, or since the last call
+    /// to [`Synth::reset_clip_report`].
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    /// synth.set_gain(0.5);
+    ///
+    /// synth.by_ref().take(44100).for_each(drop);
+    /// let report = synth.clip_report();
+    /// println!("{} clipped samples, peak {:.2}", report.clipped, report.peak);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn clip_report(&self) -> ClipReport {
+        self.master.report
+    }
+
+    /// Reset the clipping statistics.
+    pub fn reset_clip_report(&mut self) {
+        self.master.report = ClipReport::default();
+    }
+
+    /// Set the length of the level meter blocks, in samples. The default is `1024`.
+    ///
+    /// [`Synth::track_levels`] and [`Synth::voice_envelopes`] are updated at the end of each
+    /// block. The current block is restarted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `samples` is zero.
+    pub fn set_meter_block(&mut self, samples: u32) {
+        assert!(samples > 0, "meter block must not be empty");
+
+        self.meter.set_block_length(samples);
+    }
+
+    /// Get the output level of each instrument track, measured over the last complete meter block.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    /// synth.set_meter_block(441);
+    ///
+    /// synth.by_ref().take(441).for_each(drop);
+    /// let kick = synth.track_levels()[0];
+    /// println!("Kick RMS {:.2}, peak {:.2}", kick.rms, kick.peak);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn track_levels(&self) -> [TrackLevel; NUM_INSTRUMENTS] {
+        self.meter.levels
+    }
+
+    /// Get the envelope value (`0.0..=1.0`) of each voice on each instrument track, sampled at the
+    /// end of the last complete meter block. Voices that are not playing have a value of `0.0`.
+    ///
+    /// The envelope does not include the note volume, so echoes of a note have the same envelope
+    /// as the note itself.
+    #[must_use]
+    pub fn voice_envelopes(&self) -> [[f32; MAX_OVERLAPPING_NOTES]; NUM_INSTRUMENTS] {
+        self.meter.envelopes
+    }
+
+    /// Take a snapshot of the playback state, including the position, every playing note, and the
+    /// noise generator.
+    ///
+    /// ```
+    /// use sonant::{Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    ///
+    /// synth.by_ref().take(44100).for_each(drop);
+    /// let state = synth.state();
+    /// let first = synth.by_ref().take(4410).collect::<Vec<_>>();
+    ///
+    /// // Rewind
+    /// synth.restore(&state)?;
+    /// let second = synth.by_ref().take(4410).collect::<Vec<_>>();
+    /// assert_eq!(first, second);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    #[must_use]
+    pub fn state(&self) -> SynthState {
+        let mut writer = SynthState::writer();
+
+        writer.u64(self.random.state);
+        writer.u64(self.random.inc);
+
+        writer.f32(self.sample_rate);
+        writer.f32(self.sample_ratio);
+        writer.f64(self.eighth_note_length);
+        writer.f32(self.tempo_scale);
+        writer.f32(self.playback_rate);
+
+        writer.u32(self.seq_count as u32);
+        writer.u32(self.note_count as u32);
+        writer.u32(self.sample_count);
+        writer.u32(self.row_start);
+        writer.bool(self.eighth_pending);
+        writer.u32(self.eighth_count);
+        writer.f64(self.eighth_phase);
+        writer.f64(self.env_time);
+        writer.bool(self.sequencer);
+
+        // Resequencing
+        writer.bool(self.jump.is_some());
+        writer.u8(self.jump.unwrap_or_default() as u8);
+
+        self.write_sequences(&mut writer);
+        self.timeline.write(&mut writer);
+
+        // Decimator history is only needed while oversampling
+        writer.u8(self.oversampling.factor() as u8);
+        if self.oversampling != Oversampling::Off {
+            for decimator in &self.decimators {
+                decimator.write(&mut writer);
+            }
+        }
+
+        // Only playing notes are stored, the rest are silent
+        for track in &self.tracks {
+            let playing = track
+                .notes
+                .iter()
+                .enumerate()
+                .filter(|(_, note)| note.pitch != 0)
+                .fold(0_u32, |mask, (j, _)| mask | 1 << j);
+            writer.u32(playing);
+
+            for note in track.notes.iter().filter(|note| note.pitch != 0) {
+                writer.u8(note.pitch);
+                writer.f64(note.env_start);
+                writer.f32(note.volume);
+                writer.bool(note.swap_stereo);
+                writer.f32(note.env);
+                for (freq, time) in note.osc_freq.iter().zip(note.osc_time) {
+                    writer.f32(*freq);
+                    writer.f32(time);
+                }
+                writer.f32(note.low);
+                writer.f32(note.band);
+            }
+        }
+
+        writer.u8(self.triggered.len() as u8);
+        for note in &self.triggered {
+            writer.u8(note.track as u8);
+            writer.u8(note.pitch);
+            writer.f32(note.velocity);
+            writer.f64(note.eighths);
+            writer.u32(note.round);
+        }
+
+        writer.finish()
+    }
+
+    /// Restore a snapshot of the playback state taken with [`Synth::state`]. Playback continues
+    /// exactly where the snapshot was taken, including the tempo scale, playback rate, and
+    /// oversampling.
+    ///
+    /// The state should be restored into a `Synth` playing the same `Song`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the state is invalid. The `Synth` is unchanged in that case.
+    pub fn restore(&mut self, state: &SynthState) -> Result<(), Error> {
+        let mut reader = state.reader();
+
+        let random = PCG32 {
+            state: reader.u64()?,
+            inc: reader.u64()?,
+        };
 
-/// Data structure for quarter notes, which includes the pitch and sample
-/// counter reference for waveform modulation. It also contains state for sample
-/// synthesis and filtering.
-#[derive(Debug)]
-struct Note {
-    pitch: u8,
-    sample_count: u32,
-    volume: f32,
-    swap_stereo: bool,
+        let sample_rate = reader.f32()?;
+        let sample_ratio = reader.f32()?;
+        let eighth_note_length = reader.f64()?;
+        let tempo_scale = reader.f32()?;
+        let playback_rate = reader.f32()?;
+        let positive = |x: f64| x.is_finite() && x > 0.0;
+        let rates = [sample_rate, sample_ratio, tempo_scale, playback_rate];
+        if !rates.iter().all(|&x| positive(f64::from(x))) || !positive(eighth_note_length) {
+            return Err(Error::InvalidState);
+        }
 
-    // Iterator state
-    osc_freq: [f32; 2],
-    osc_time: [f32; 2],
-    low: f32,
-    band: f32,
-}
+        let seq_count = reader.u32()? as usize;
+        let note_count = reader.u32()? as usize;
+        let sample_count = reader.u32()?;
+        let row_start = reader.u32()?;
+        let eighth_pending = reader.bool()?;
+        let eighth_count = reader.u32()?;
+        let eighth_phase = reader.f64()?;
+        let env_time = reader.f64()?;
+        let sequencer = reader.bool()?;
+        if row_start > sample_count
+            || !(0.0..eighth_note_length).contains(&eighth_phase)
+            || !env_time.is_finite()
+        {
+            return Err(Error::InvalidState);
+        }
 
-/// Sine wave generator
-fn osc_sin(value: f32) -> f32 {
-    libm::sinf((value + 0.5) * PI * 2.0)
-}
+        let has_jump = reader.bool()?;
+        let jump = usize::from(reader.u8()?);
+        let jump = has_jump.then_some(jump);
+        if jump.is_some_and(|sequence| sequence > self.song().seq_length) {
+            return Err(Error::InvalidState);
+        }
 
-/// Square wave generator
-fn osc_square(value: f32) -> f32 {
-    if osc_sin(value) < 0.0 {
-        -1.0
-    } else {
-        1.0
-    }
-}
+        let sequence_table = self.read_sequences(&mut reader)?;
+        let timeline = Timeline::read(&mut reader, eighth_count)?;
 
-/// Saw wave generator
-fn osc_saw(value: f32) -> f32 {
-    let fract = value - libm::truncf(value);
-    (1.0 - fract) - 0.5
-}
+        // The pattern and row must be the ones the timeline reached
+        let position = timeline
+            .song_position(eighth_count)
+            .ok_or(Error::InvalidState)? as usize;
+        if seq_count != position / (PATTERN_LENGTH * 2)
+            || note_count != position / 2 % PATTERN_LENGTH
+        {
+            return Err(Error::InvalidState);
+        }
 
-/// Triangle wave generator
-fn osc_tri(value: f32) -> f32 {
-    let fract = value - libm::truncf(value);
-    let v2 = fract * 4.0;
+        let oversampling =
+            Oversampling::from_factor(usize::from(reader.u8()?)).ok_or(Error::InvalidState)?;
+        let mut decimators = Self::load_decimators();
+        if oversampling != Oversampling::Off {
+            for decimator in &mut decimators {
+                decimator.read(&mut reader)?;
+            }
+        }
 
-    if v2 < 2.0 {
-        v2 - 1.0
-    } else {
-        3.0 - v2
+        let tracks = self.read_notes(&mut reader)?;
+        let triggered = self.read_triggered(&mut reader)?;
+        reader.finish()?;
+
+        self.random = random;
+        self.sample_rate = sample_rate;
+        self.sample_ratio = sample_ratio;
+        self.eighth_note_length = eighth_note_length;
+        self.tempo_scale = tempo_scale;
+        self.playback_rate = playback_rate;
+        self.seq_count = seq_count;
+        self.note_count = note_count;
+        self.sample_count = sample_count;
+        self.row_start = row_start;
+        self.eighth_pending = eighth_pending;
+        self.eighth_count = eighth_count;
+        self.eighth_phase = eighth_phase;
+        self.env_time = env_time;
+        self.sequencer = sequencer;
+        self.tracks = tracks;
+        self.triggered = triggered;
+        self.sequences = sequence_table;
+        self.jump = jump;
+        self.timeline = timeline;
+        self.oversampling = oversampling;
+        self.decimators = decimators;
+        self.echo_end = self.last_echo();
+
+        Ok(())
     }
-}
 
-/// Get a `note` frequency on the exponential scale defined by reference
-/// frequency `ref_freq` and reference pitch `ref_pitch`, using the interval
-/// `semitone`.
-fn get_frequency(ref_freq: f32, semitone: f32, note: u8, ref_pitch: u8) -> f32 {
-    ref_freq * libm::powf(semitone, f32::from(note) - f32::from(ref_pitch))
-}
+    /// Deserialize the notes that are playing on each track.
+    fn read_notes(
+        &self,
+        reader: &mut StateReader<'_>,
+    ) -> Result<[TrackState; NUM_INSTRUMENTS], Error> {
+        let mut tracks = self.tracks.clone();
+        for track in &mut tracks {
+            // A bit for each voice that is playing
+            let playing = reader.u32()?;
+            if playing
+                .checked_shr(MAX_OVERLAPPING_NOTES as u32)
+                .is_some_and(|unused| unused != 0)
+            {
+                return Err(Error::InvalidState);
+            }
 
-/// Get the absolute frequency for a note value on the 12-TET scale.
-fn get_note_frequency(note: u8) -> f32 {
-    const SEMITONE: f32 = 1.059_463_1; // Twelfth root of 2
-    get_frequency(1.0 / 256.0, SEMITONE, note, 128)
-}
+            for (j, note) in track.notes.iter_mut().enumerate() {
+                *note = Note::new(0, 0.0, 0.0, false);
+                if playing & 1 << j == 0 {
+                    continue;
+                }
 
-/// Get a sample from the waveform generator at time `t`
-fn get_osc_output(waveform: &Waveform, t: f32) -> f32 {
-    match waveform {
-        Waveform::Sine => osc_sin(t),
-        Waveform::Square => osc_square(t),
-        Waveform::Saw => osc_saw(t),
-        Waveform::Triangle => osc_tri(t),
+                note.pitch = reader.u8()?;
+                note.env_start = reader.f64()?;
+                note.volume = reader.f32()?;
+                note.swap_stereo = reader.bool()?;
+                note.env = reader.f32()?;
+                for (freq, time) in note.osc_freq.iter_mut().zip(&mut note.osc_time) {
+                    *freq = reader.f32()?;
+                    *time = reader.f32()?;
+                }
+                note.low = reader.f32()?;
+                note.band = reader.f32()?;
+                if note.pitch == 0 {
+                    return Err(Error::InvalidState);
+                }
+            }
+        }
+
+        Ok(tracks)
     }
-}
 
-impl TrackState {
-    fn new() -> Self {
-        let mut notes = ArrayVec::new();
-        for _ in 0..MAX_OVERLAPPING_NOTES {
-            notes.push(Note::new(0, 0, 0.0, false));
+    /// Deserialize the live notes that have echoes waiting to be played.
+    fn read_triggered(
+        &self,
+        reader: &mut StateReader<'_>,
+    ) -> Result<ArrayVec<TriggeredNote, MAX_TRIGGERED_NOTES>, Error> {
+        let mut triggered = ArrayVec::new();
+        for _ in 0..reader.u8()? {
+            let note = TriggeredNote {
+                track: usize::from(reader.u8()?),
+                pitch: reader.u8()?,
+                velocity: reader.f32()?,
+                eighths: reader.f64()?,
+                round: reader.u32()?,
+            };
+            if note.track >= NUM_INSTRUMENTS
+                || note.pitch == 0
+                || !note.eighths.is_finite()
+                || !(1..=self.tracks[note.track].delay_count).contains(&note.round)
+            {
+                return Err(Error::InvalidState);
+            }
+            triggered.try_push(note).map_err(|_| Error::InvalidState)?;
         }
-        let notes = notes.into_inner().unwrap();
 
-        Self {
-            env: Envelope {
-                attack: 0,
-                sustain: 0,
-                release: 0,
-                master: 0.0,
-            },
-            notes,
-            delay_samples: 0,
-            delay_count: 0,
-            pan_freq: 0.0,
-            lfo_freq: 0.0,
-        }
+        Ok(triggered)
     }
-}
 
-impl Note {
-    fn new(pitch: u8, sample_count: u32, volume: f32, swap_stereo: bool) -> Self {
-        Self {
-            pitch,
-            sample_count,
-            volume,
-            swap_stereo,
-            osc_freq: [0.0; 2],
-            osc_time: [0.0; 2],
-            low: 0.0,
-            band: 0.0,
+    /// Serialize the sequences. Only sequences that differ from the song are stored.
+    fn write_sequences(&self, writer: &mut StateWriter) {
+        let original = Self::load_sequences(self.song());
+        let changed = (0..NUM_INSTRUMENTS)
+            .filter(|&i| self.sequences[i] != original[i])
+            .fold(0, |mask, i| mask | 1 << i);
+        writer.u8(changed);
+
+        for (i, sequence) in self.sequences.iter().enumerate() {
+            if changed & 1 << i != 0 {
+                for &pattern in sequence {
+                    writer.u8(pattern);
+                }
+            }
         }
     }
-}
 
-impl<'a> Synth<'a> {
-    /// Create a `Synth` that will play the provided `Song`.
-    /// The optional seed will be used for the noise generator.
-    /// `Synth` implements `Iterator` and generates two stereo samples at a time.
-    ///
-    /// ```no_run
-    /// use byteorder::{ByteOrder, NativeEndian};
-    /// use getrandom::getrandom;
-    /// use sonant::{Song, Synth};
-    ///
-    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
-    ///
-    /// // Create a seed for the PRNG
-    /// let mut seed = [0_u8; 16];
-    /// getrandom(&mut seed).expect("failed to getrandom");
-    /// let seed = (
-    ///     NativeEndian::read_u64(&seed[0..8]),
-    ///     NativeEndian::read_u64(&seed[8..16]),
-    /// );
-    ///
-    /// let synth = Synth::new(&song, seed, 44100.0);
-    /// for [sample_l, sample_r] in synth {
-    ///     // Do something with the samples
-    /// }
-    /// # Ok::<(), sonant::Error>(())
-    /// ```
-    #[must_use]
-    pub fn new(song: &'a Song, seed: (u64, u64), sample_rate: f32) -> Self {
-        let random = PCG32::new(seed.0, seed.1);
-        let sample_ratio = sample_rate / 44100.0;
-        let quarter_note_length = (sample_ratio * song.quarter_note_length as f32) as u32;
-        let eighth_note_length = quarter_note_length / 2;
+    /// Deserialize the sequences written by [`Synth::write_sequences`].
+    fn read_sequences(
+        &self,
+        reader: &mut StateReader<'_>,
+    ) -> Result<[[u8; SEQUENCE_LENGTH]; NUM_INSTRUMENTS], Error> {
+        let mut sequences = Self::load_sequences(self.song());
+        let changed = reader.u8()?;
+
+        for (i, sequence) in sequences.iter_mut().enumerate() {
+            if changed & 1 << i == 0 {
+                continue;
+            }
+            for pattern in sequence {
+                *pattern = reader.u8()?;
+                if usize::from(*pattern) > NUM_PATTERNS {
+                    return Err(Error::InvalidState);
+                }
+            }
+        }
 
-        let mut synth = Synth {
-            song,
-            random,
-            sample_rate,
-            sample_ratio,
-            quarter_note_length,
-            eighth_note_length,
-            seq_count: 0,
-            sample_count: 0,
-            note_count: 0,
-            tracks: Self::load_tracks(
-                song,
-                sample_ratio,
-                quarter_note_length as f32,
-                eighth_note_length as f32,
-            ),
-        };
-        synth.load_notes();
+        Ok(sequences)
+    }
 
-        synth
+    /// Create a decimator for each track.
+    fn load_decimators() -> [Decimator; NUM_INSTRUMENTS] {
+        let mut decimators = ArrayVec::<_, NUM_INSTRUMENTS>::new();
+        for _ in 0..NUM_INSTRUMENTS {
+            decimators.push(Decimator::new());
+        }
+
+        decimators.into_inner().unwrap()
     }
 
     /// Load the static state for each track.
-    fn load_tracks(
-        song: &Song,
-        sample_ratio: f32,
-        quarter_note_length: f32,
-        eighth_note_length: f32,
-    ) -> [TrackState; NUM_INSTRUMENTS] {
+    fn load_tracks(song: &Song) -> [TrackState; NUM_INSTRUMENTS] {
         let mut tracks = ArrayVec::<_, NUM_INSTRUMENTS>::new();
         for _ in 0..NUM_INSTRUMENTS {
             tracks.push(TrackState::new());
         }
         let mut tracks = tracks.into_inner().unwrap();
 
-        for (i, inst) in song.instruments.iter().enumerate() {
-            // Configure attack, sustain, and release
-            tracks[i].env.attack = (inst.env.attack as f32 * sample_ratio) as u32;
-            tracks[i].env.sustain = (inst.env.sustain as f32 * sample_ratio) as u32;
-            tracks[i].env.release = (inst.env.release as f32 * sample_ratio) as u32;
-
-            // Configure delay
-            tracks[i].delay_samples = (f32::from(inst.fx.delay_time) * eighth_note_length) as u32;
-            tracks[i].delay_count = if inst.fx.delay_amount == 0.0 {
-                // Special case for zero repeats
-                0
-            } else if libm::fabsf(inst.fx.delay_amount - 1.0) < f32::EPSILON {
-                // Special case for infinite repeats
-                u32::MAX
-            } else if tracks[i].delay_samples == 0 {
-                // Special case for zero-delay time: only repeat once
-                1
-            } else {
-                // This gets the number of iterations required for the note
-                // volume to drop below the audible threshold.
-                let base = libm::logf(1.0 / inst.fx.delay_amount);
-                (libm::logf(256.0) / base) as u32
-            };
-
-            // Set LFO and panning frequencies
-            tracks[i].lfo_freq = get_frequency(1.0, 2.0, inst.lfo.freq, 8) / quarter_note_length;
-            tracks[i].pan_freq = get_frequency(1.0, 2.0, inst.fx.pan_freq, 8) / quarter_note_length;
+        for (track, inst) in tracks.iter_mut().zip(&song.instruments) {
+            track.configure(inst);
         }
 
         tracks
     }
 
+    /// Copy the sequence of each instrument, so it can be changed during playback.
+    fn load_sequences(song: &Song) -> [[u8; SEQUENCE_LENGTH]; NUM_INSTRUMENTS] {
+        let mut sequences = [[0; SEQUENCE_LENGTH]; NUM_INSTRUMENTS];
+
+        for (sequence, inst) in sequences.iter_mut().zip(&song.instruments) {
+            for (pattern, &p) in sequence.iter_mut().zip(&inst.seq) {
+                *pattern = p as u8;
+            }
+        }
+
+        sequences
+    }
+
+    /// Load the notes for the current eighth note, and send events for the pattern and row.
+    fn load_eighth(&mut self, events: &mut impl EventSink) {
+        if self.eighth_count.is_multiple_of(2) {
+            let position = Self::position(self);
+            let sequence = self.seq_count;
+            let row = self.note_count;
+
+            if sequence <= self.song().seq_length {
+                if row == 0 {
+                    events.event(&position, Event::Pattern { sequence });
+                }
+                events.event(&position, Event::Row { sequence, row });
+            } else if sequence == self.song().seq_length + 1 && row == 0 {
+                events.event(&position, Event::End);
+            }
+
+            // Fetch the next set of notes
+            self.load_delayed_notes(events);
+            self.load_notes(events);
+        } else {
+            // Fetch the next set of notes
+            self.load_delayed_notes(events);
+        }
+    }
+
     /// Load the next set of notes into the iterator state.
-    fn load_notes(&mut self) {
+    fn load_notes(&mut self, events: &mut impl EventSink) {
         let seq_count = self.seq_count;
-        if seq_count > self.song.seq_length {
+        if seq_count > self.song().seq_length {
             return;
         }
 
-        for i in 0..self.song.instruments.len() {
+        for i in 0..NUM_INSTRUMENTS {
             // Add the note
             let note_count = self.note_count;
-            self.add_note(i, seq_count, note_count, 1.0, false);
+            self.add_note(events, i, seq_count, note_count, 0);
         }
     }
 
     /// Load delayed notes into the iterator state.
-    fn load_delayed_notes(&mut self) {
-        for (i, inst) in self.song.instruments.iter().enumerate() {
-            for round in 1..=self.tracks[i].delay_count {
-                // Compute the delay position
-                let delay = self.tracks[i].delay_samples * round;
-                if delay > self.sample_count {
-                    continue;
-                }
+    fn load_delayed_notes(&mut self, events: &mut impl EventSink) {
+        for i in 0..NUM_INSTRUMENTS {
+            // Only rounds which reach back to the start of the song can produce a note
+            let delay_eighths = self.tracks[i].delay_eighths;
+            let rounds = match delay_eighths {
+                0 => self.tracks[i].delay_count,
+                _ => self.tracks[i]
+                    .delay_count
+                    .min(self.eighth_count / delay_eighths),
+            };
 
+            for round in 1..=rounds {
                 // Seek to the delayed note, and ensure it's aligned to the quarter note
-                let position = self.sample_count - delay;
-                if position % self.quarter_note_length != 0 {
+                let elapsed = self.eighth_count - delay_eighths * round;
+                let Some(position) = self.timeline.song_position(elapsed) else {
+                    continue;
+                };
+                if position % 2 != 0 {
                     continue;
                 }
 
                 // Convert position into seq_count and note_count
-                let pattern_length = self.quarter_note_length * PATTERN_LENGTH as u32;
-                let seq_count = (position / pattern_length) as usize;
-                if seq_count > self.song.seq_length {
+                let row = position as usize / 2;
+                let seq_count = row / PATTERN_LENGTH;
+                if seq_count > self.song().seq_length {
                     continue;
                 }
-                let note_count = ((position % pattern_length) / self.quarter_note_length) as usize;
+                let note_count = row % PATTERN_LENGTH;
+
+                // Add the note
+                self.add_note(events, i, seq_count, note_count, round);
+            }
+        }
+    }
 
+    /// Load the delayed echoes of notes started with [`Synth::note_on`] that are due.
+    fn load_triggered_echoes(&mut self, events: &mut impl EventSink) {
+        let eighths = self.eighths();
+        let mut k = 0;
+
+        while k < self.triggered.len() {
+            let TriggeredNote {
+                track,
+                pitch,
+                velocity,
+                eighths: start,
+                round,
+            } = self.triggered[k];
+            let delay_eighths = self.tracks[track].delay_eighths;
+            let delay_count = self.tracks[track].delay_count;
+
+            if start + f64::from(delay_eighths) * f64::from(round) <= eighths {
                 // Add the note
-                let volume = libm::powf(inst.fx.delay_amount, round as f32);
-                self.add_note(i, seq_count, note_count, volume, round % 2 == 1);
+                self.start_note(events, track, pitch, velocity, round);
+
+                if round >= delay_count {
+                    self.triggered.remove(k);
+                } else {
+                    self.triggered[k].round += 1;
+                }
+            } else {
+                k += 1;
             }
         }
     }
@@ -307,23 +1523,25 @@ impl<'a> Synth<'a> {
             i
         } else {
             let iter = notes.iter().enumerate();
-            iter.min_by_key(|(_, x)| x.sample_count).unwrap().0
+            iter.min_by(|(_, a), (_, b)| a.env_start.total_cmp(&b.env_start))
+                .unwrap()
+                .0
         }
     }
 
-    /// Add a note to track `i`.
+    /// Add a note to track `i`. Delayed echoes are added with a nonzero delay `round`.
     fn add_note(
         &mut self,
+        events: &mut impl EventSink,
         i: usize,
         seq_count: usize,
         note_count: usize,
-        volume: f32,
-        swap_stereo: bool,
+        round: u32,
     ) {
-        let inst = &self.song.instruments[i];
+        let inst = &self.song().instruments[i];
 
         // Get the pattern index
-        let p = inst.seq[seq_count];
+        let p = usize::from(self.sequences[i][seq_count]);
         if p == 0 {
             return;
         }
@@ -337,43 +1555,80 @@ impl<'a> Synth<'a> {
             return;
         }
 
-        // Create a new note
-        let j = Self::get_note_slot(&self.tracks[i].notes);
-        self.tracks[i].notes[j] = Note::new(pitch, self.sample_count, volume, swap_stereo);
+        self.start_note(events, i, pitch, 1.0, round);
+    }
 
-        // Set oscillator frequencies
-        let pitch = w(self.tracks[i].notes[j].pitch);
-        for o in 0..2 {
-            let pitch = (pitch + w(inst.osc[o].octave) + w(inst.osc[o].detune_freq)).0;
-            self.tracks[i].notes[j].osc_freq[o] =
-                get_note_frequency(pitch) * inst.osc[o].detune / self.sample_ratio;
+    /// Start playing a note with the given `pitch` on track `i`. Delayed echoes are started with a
+    /// nonzero delay `round`.
+    fn start_note(
+        &mut self,
+        events: &mut impl EventSink,
+        i: usize,
+        pitch: u8,
+        velocity: f32,
+        round: u32,
+    ) {
+        let song: &Song = self.song.borrow();
+        let inst = &song.instruments[i];
+
+        // Get the oscillator frequencies. Notes that play an unmapped key are skipped.
+        let reference = self.reference_pitch / ORIGINAL_REFERENCE_PITCH;
+        let mut osc_freq = [0.0; 2];
+        for (o, freq) in osc_freq.iter_mut().enumerate() {
+            let pitch = (w(pitch) + w(inst.osc[o].octave) + w(inst.osc[o].detune_freq)).0;
+            let Some(note_freq) = self.tuning.frequency(pitch) else {
+                return;
+            };
+            *freq = note_freq * reference * inst.osc[o].detune / self.time_ratio();
         }
+
+        let echo = round > 0;
+        events.event(
+            &Self::position(self),
+            Event::Note {
+                track: i,
+                pitch,
+                echo,
+            },
+        );
+
+        // Create a new note
+        let volume = libm::powf(inst.fx.delay_amount, round as f32) * velocity;
+        let j = Self::get_note_slot(&self.tracks[i].notes);
+        self.tracks[i].notes[j] = Note::new(pitch, self.env_time, volume, round % 2 == 1);
+        self.tracks[i].notes[j].osc_freq = osc_freq;
     }
 
     /// Envelope
-    fn env(position: u32, inst_env: &Envelope) -> Option<(f32, f32)> {
-        let attack = inst_env.attack;
-        let sustain = inst_env.sustain;
-        let release = inst_env.release;
+    fn env(position: f32, inst_env: &Envelope) -> Option<(f32, f32)> {
+        let attack = inst_env.attack as f32;
+        let sustain = inst_env.sustain as f32;
+        let release = inst_env.release as f32;
 
         let mut env = 1.0;
 
         if position < attack {
-            env = position as f32 / attack as f32;
+            env = position / attack;
         } else if position >= attack + sustain + release {
             return None;
         } else if position >= attack + sustain {
-            let pos = (position - attack - sustain) as f32;
-            env -= pos / release as f32;
+            let pos = position - attack - sustain;
+            env -= pos / release;
         }
 
         Some((env, env * env))
     }
 
-    /// Oscillator 0
-    fn osc0(&mut self, inst: &Instrument, i: usize, j: usize, lfo: f32, env_sq: f32) -> f32 {
-        let r = get_osc_output(&inst.osc[0].waveform, self.tracks[i].notes[j].osc_time[0]);
-        let mut t = self.tracks[i].notes[j].osc_freq[0];
+    /// Oscillator 0, advancing by `scale` of an output sample
+    fn osc0(
+        inst: &Instrument,
+        note: &mut Note,
+        mode: OscillatorMode,
+        scale: f32,
+        lfo: f32,
+        env_sq: f32,
+    ) -> f32 {
+        let mut t = note.osc_freq[0];
 
         if inst.lfo.osc0_freq {
             t += lfo;
@@ -381,39 +1636,56 @@ impl<'a> Synth<'a> {
         if inst.osc[0].envelope {
             t *= env_sq;
         }
-        self.tracks[i].notes[j].osc_time[0] += t;
+        t *= scale;
+        let r = get_mode_osc_output(mode, &inst.osc[0].waveform, note.osc_time[0], t);
+        note.osc_time[0] += t;
 
         r * inst.osc[0].volume
     }
 
-    /// Oscillator 1
-    fn osc1(&mut self, inst: &Instrument, i: usize, j: usize, env_sq: f32) -> f32 {
-        let r = get_osc_output(&inst.osc[1].waveform, self.tracks[i].notes[j].osc_time[1]);
-        let mut t = self.tracks[i].notes[j].osc_freq[1];
+    /// Oscillator 1, advancing by `scale` of an output sample
+    fn osc1(
+        inst: &Instrument,
+        note: &mut Note,
+        mode: OscillatorMode,
+        scale: f32,
+        env_sq: f32,
+    ) -> f32 {
+        let mut t = note.osc_freq[1];
 
         if inst.osc[1].envelope {
             t *= env_sq;
         }
-        self.tracks[i].notes[j].osc_time[1] += t;
+        t *= scale;
+        let r = get_mode_osc_output(mode, &inst.osc[1].waveform, note.osc_time[1], t);
+        note.osc_time[1] += t;
 
         r * inst.osc[1].volume
     }
 
-    /// Filters
-    fn filters(&mut self, inst: &Instrument, i: usize, j: usize, lfo: f32, sample: f32) -> f32 {
-        let mut f = inst.fx.freq * self.sample_ratio;
+    /// Filters, running at the effective `sample_rate` (including the playback rate and
+    /// oversampling)
+    fn filters(
+        inst: &Instrument,
+        note: &mut Note,
+        sample_ratio: f32,
+        sample_rate: f32,
+        lfo: f32,
+        sample: f32,
+    ) -> f32 {
+        let mut f = inst.fx.freq * sample_ratio;
 
         if inst.lfo.fx_freq {
             f *= lfo;
         }
-        f = libm::sinf(f * PI / self.sample_rate) * 1.5;
+        f = libm::sinf(f * PI / sample_rate) * 1.5;
 
-        let low = libm::fmaf(f, self.tracks[i].notes[j].band, self.tracks[i].notes[j].low);
-        let high = inst.fx.resonance * (sample - self.tracks[i].notes[j].band) - low;
-        let band = libm::fmaf(f, high, self.tracks[i].notes[j].band);
+        let low = libm::fmaf(f, note.band, note.low);
+        let high = inst.fx.resonance * (sample - note.band) - low;
+        let band = libm::fmaf(f, high, note.band);
 
-        self.tracks[i].notes[j].low = low;
-        self.tracks[i].notes[j].band = band;
+        note.low = low;
+        note.band = band;
 
         let sample = match inst.fx.filter {
             Filter::None => sample,
@@ -426,102 +1698,135 @@ impl<'a> Synth<'a> {
         sample * inst.env.master
     }
 
-    /// Generate samples for 2 channels using the given instrument.
+    /// Generate samples for 2 channels using note `j` on track `i`. One frame is generated for
+    /// each oversampled frame; the rest are silent.
     fn generate_samples(
         &mut self,
-        inst: &Instrument,
         i: usize,
         j: usize,
-        position: f32,
-    ) -> Option<[f32; NUM_CHANNELS]> {
+        position: f64,
+    ) -> Option<[[f32; NUM_CHANNELS]; MAX_FACTOR]> {
+        let song: &Song = self.song.borrow();
+        let inst = &song.instruments[i];
+        let track = &mut self.tracks[i];
+        let note = &mut track.notes[j];
+
         // Envelope
-        let note_sample_count = self.tracks[i].notes[j].sample_count;
-        let (env, env_sq) = Self::env(self.sample_count - note_sample_count, &self.tracks[i].env)?;
+        let (env, env_sq) = Self::env((self.env_time - note.env_start) as f32, &inst.env)?;
+        note.env = env;
 
         // LFO
-        let lfo_freq = self.tracks[i].lfo_freq;
+        let lfo_t = get_phase(track.lfo_freq, position);
         let lfo = libm::fmaf(
-            get_osc_output(&inst.lfo.waveform, lfo_freq * position),
+            get_osc_output(&inst.lfo.waveform, lfo_t),
             inst.lfo.amount * self.sample_ratio,
             0.5,
         );
 
-        // Oscillator 0
-        let mut sample = self.osc0(inst, i, j, lfo, env_sq);
-
-        // Oscillator 1
-        sample += self.osc1(inst, i, j, env_sq);
-
         // Noise oscillator
-        sample += osc_sin(self.random.next_f32_unit()) * inst.noise_fader * env;
-
-        // Envelope
-        sample *= env * self.tracks[i].notes[j].volume;
-
-        // Filters
-        sample += self.filters(inst, i, j, lfo, sample);
+        let noise = osc_sin(self.random.next_f32_unit()) * inst.noise_fader * env;
 
-        let pan_freq = self.tracks[i].pan_freq;
         let pan_t = libm::fmaf(
-            osc_sin(pan_freq * position),
+            osc_sin(get_phase(track.pan_freq, position)),
             inst.fx.pan_amount * self.sample_ratio,
             0.5,
         );
 
-        if self.tracks[i].notes[j].swap_stereo {
-            Some([sample * (1.0 - pan_t), sample * pan_t])
-        } else {
-            Some([sample * pan_t, sample * (1.0 - pan_t)])
+        // Run the oscillators and filters once for each oversampled frame
+        let mode = self.oscillator_mode;
+        let factor = self.oversampling.factor();
+        let scale = (factor as f32).recip();
+        let sample_rate = self.sample_rate / self.playback_rate * factor as f32;
+        let mut samples = [[0.0; NUM_CHANNELS]; MAX_FACTOR];
+
+        for frame in &mut samples[..factor] {
+            // Oscillator 0
+            let mut sample = Self::osc0(inst, note, mode, scale, lfo, env_sq);
+
+            // Oscillator 1
+            sample += Self::osc1(inst, note, mode, scale, env_sq);
+
+            // Noise oscillator
+            sample += noise;
+
+            // Envelope
+            sample *= env * note.volume;
+
+            // Filters
+            sample += Self::filters(inst, note, self.sample_ratio, sample_rate, lfo, sample);
+
+            *frame = if note.swap_stereo {
+                [sample * (1.0 - pan_t), sample * pan_t]
+            } else {
+                [sample * pan_t, sample * (1.0 - pan_t)]
+            };
         }
+
+        Some(samples)
     }
 
     /// Update the sample generator. This is the main workhorse of the
     /// synthesizer.
-    fn update(&mut self) -> [f32; NUM_CHANNELS] {
+    ///
+    /// Returns the stereo output of each track, before the master output stage.
+    fn update(&mut self) -> [[f32; NUM_CHANNELS]; NUM_INSTRUMENTS] {
         let amplitude = f32::from(i16::MAX);
-        let position = self.sample_count as f32;
+        let position = self.quarter_notes();
+
+        let factor = self.oversampling.factor();
 
         // Output samples
-        let mut samples = [0.0; NUM_CHANNELS];
+        let mut samples = [[0.0; NUM_CHANNELS]; NUM_INSTRUMENTS];
 
-        for (i, inst) in self.song.instruments.iter().enumerate() {
-            for j in 0..self.tracks[i].notes.len() {
+        for (i, track_samples) in samples.iter_mut().enumerate() {
+            let mut oversampled = [[0.0; NUM_CHANNELS]; MAX_FACTOR];
+
+            for j in 0..MAX_OVERLAPPING_NOTES {
                 if self.tracks[i].notes[j].pitch == 0 {
                     continue;
                 }
 
-                if let Some(note_samples) = self.generate_samples(inst, i, j, position) {
+                if let Some(note_samples) = self.generate_samples(i, j, position) {
                     // Mix the samples
-                    for (i, sample) in samples.iter_mut().enumerate() {
-                        *sample += note_samples[i];
+                    for (frame, note_frame) in oversampled.iter_mut().zip(note_samples) {
+                        for (sample, note_sample) in frame.iter_mut().zip(note_frame) {
+                            *sample += note_sample / amplitude;
+                        }
                     }
                 } else {
                     // Remove notes that have ended
-                    self.tracks[i].notes[j] = Note::new(0, 0, 0.0, false);
+                    self.tracks[i].notes[j] = Note::new(0, 0.0, 0.0, false);
                 }
             }
-        }
 
-        // Clip samples to [-1.0, 1.0]
-        for sample in &mut samples {
-            *sample = (*sample / amplitude).clamp(-1.0, 1.0);
+            *track_samples = match self.oversampling {
+                Oversampling::Off => oversampled[0],
+                _ => self.decimators[i].process(&oversampled[..factor]),
+            };
         }
 
         samples
     }
-}
 
-impl<'a> Iterator for Synth<'a> {
-    type Item = [f32; NUM_CHANNELS];
+    /// Generate the output of each track for the next sample, or `None` at the end of the song.
+    fn next_tracks(
+        &mut self,
+        events: &mut impl EventSink,
+    ) -> Option<[[f32; NUM_CHANNELS]; NUM_INSTRUMENTS]> {
+        // Fetch the notes that start on this sample
+        if self.eighth_pending {
+            self.eighth_pending = false;
+            if self.sequencer {
+                self.load_eighth(events);
+            }
+        }
+        self.load_triggered_echoes(events);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // Check for end of song
-        if self.seq_count > self.song.seq_length
-            && !self
-                .tracks
-                .iter()
-                .flat_map(|x| x.notes.iter())
-                .any(|x| x.pitch != 0)
+        // Check for end of song, which waits for echoes that follow a silent gap
+        if self.sequencer
+            && self.seq_count > self.song().seq_length
+            && self.eighth_count >= self.echo_end
+            && !self.is_playing()
         {
             return None;
         }
@@ -529,27 +1834,84 @@ impl<'a> Iterator for Synth<'a> {
         // Generate the next sample
         let samples = self.update();
 
+        // Measure the track levels
+        if self.meter.process(&samples) {
+            for (envelopes, track) in self.meter.envelopes.iter_mut().zip(&self.tracks) {
+                for (envelope, note) in envelopes.iter_mut().zip(&track.notes) {
+                    *envelope = note.env;
+                }
+            }
+        }
+
         // Advance to next sample
         self.sample_count += 1;
-        let sample_in_quarter_note = self.sample_count % self.quarter_note_length;
-        if sample_in_quarter_note == 0 {
-            // Advance to next note
-            self.note_count += 1;
-            if self.note_count >= PATTERN_LENGTH {
-                self.note_count = 0;
-
-                // Advance to next pattern
-                self.seq_count += 1;
+        self.env_time += f64::from(self.time_ratio()).recip();
+        self.eighth_phase += 1.0;
+        if self.eighth_phase >= self.eighth_note_length {
+            // Advance to next eighth note, keeping the fractional remainder
+            self.eighth_phase -= self.eighth_note_length;
+            self.eighth_count += 1;
+            self.eighth_pending = true;
+
+            if self.eighth_count.is_multiple_of(2) {
+                // Advance to next note
+                self.note_count += 1;
+                self.row_start = self.sample_count;
+                if self.note_count >= PATTERN_LENGTH {
+                    self.note_count = 0;
+
+                    // Advance to next pattern
+                    if let Some(sequence) = self.jump.take() {
+                        self.seq_count = sequence;
+                        self.timeline.jump(self.eighth_count, sequence);
+                    } else {
+                        self.seq_count += 1;
+                        if self.seq_count == self.song().seq_length + 1 {
+                            self.echo_end = self.last_echo();
+                        }
+                    }
+                }
             }
-
-            // Fetch the next set of notes
-            self.load_delayed_notes();
-            self.load_notes();
-        } else if sample_in_quarter_note == self.eighth_note_length {
-            // Fetch the next set of notes
-            self.load_delayed_notes();
         }
 
         Some(samples)
     }
 }
+
+impl<S: BorrowMut<Song>> Synth<S> {
+    /// Set parameter `param` of instrument `track`, like [`Song::set_param`]. The change applies
+    /// to notes that are already playing, so parameters can be automated during playback. The
+    /// `Synth` must own the `Song`, or borrow it mutably.
+    ///
+    /// ```
+    /// use sonant::{Param, Song, Synth};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut synth = Synth::new(song, (0, 1), 44100.0);
+    ///
+    /// // Sweep the filter of the bass down during the first second
+    /// for i in 0..44100 {
+    ///     synth.set_param(1, Param::FxFreq, 11025.0 - i as f32 / 4.0);
+    ///     synth.next();
+    /// }
+    /// assert_eq!(synth.param(1, Param::FxFreq), 11025.0 - 44099.0 / 4.0);
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `track` is not a valid instrument index (`0..8`).
+    pub fn set_param(&mut self, track: usize, param: Param, value: f32) {
+        let song = self.song.borrow_mut();
+        song.set_param(track, param, value);
+        self.tracks[track].configure(&song.instruments[track]);
+    }
+}
+
+impl<S: Borrow<Song>> Iterator for Synth<S> {
+    type Item = [f32; NUM_CHANNELS];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_events(&mut NoEvents)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::consts::{MAX_JUMPS, PATTERN_LENGTH, SEQUENCE_LENGTH};
+use crate::song::Error;
+use crate::state::{StateReader, StateWriter};
+use arrayvec::ArrayVec;
+
+/// A stretch of the song that was played in order, starting at a pattern boundary.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Segment {
+    pub(crate) start: u32,      // Eighth notes elapsed when the segment started
+    pub(crate) sequence: usize, // Sequence index of the first pattern
+}
+
+/// Maps the time that has elapsed to positions in the song, so delayed echoes can find the notes
+/// they repeat after the sequence was changed with [`Synth::jump_to`](crate::Synth::jump_to).
+///
+/// Only the most recent jumps are remembered. Echoes that reach back further are dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct Timeline {
+    segments: ArrayVec<Segment, MAX_JUMPS>,
+}
+
+impl Timeline {
+    pub(crate) fn new() -> Self {
+        let mut segments = ArrayVec::new();
+        segments.push(Segment {
+            start: 0,
+            sequence: 0,
+        });
+
+        Self { segments }
+    }
+
+    /// Start a new segment at pattern `sequence`, `start` eighth notes into playback.
+    pub(crate) fn jump(&mut self, start: u32, sequence: usize) {
+        if self.segments.is_full() {
+            self.segments.remove(0);
+        }
+        self.segments.push(Segment { start, sequence });
+    }
+
+    /// Get the song position (in eighth notes from the start of the sequence) that was played
+    /// `eighths` eighth notes into playback, or `None` if it has been forgotten or is out of range.
+    pub(crate) fn song_position(&self, eighths: u32) -> Option<u32> {
+        let segment = self.segments.iter().rev().find(|x| x.start <= eighths)?;
+        let start = (segment.sequence * PATTERN_LENGTH * 2) as u32;
+
+        start.checked_add(eighths - segment.start)
+    }
+
+    /// Get the number of eighth notes elapsed when the most recent segment reached song
+    /// `position` (in eighth notes from the start of the sequence), or `None` if the segment
+    /// started after it.
+    pub(crate) fn elapsed(&self, position: u32) -> Option<u32> {
+        let segment = self.segments.last()?;
+        let start = (segment.sequence * PATTERN_LENGTH * 2) as u32;
+
+        segment.start.checked_add(position.checked_sub(start)?)
+    }
+
+    pub(crate) fn write(&self, writer: &mut StateWriter) {
+        writer.u8(self.segments.len() as u8);
+        for segment in &self.segments {
+            writer.u32(segment.start);
+            writer.u8(segment.sequence as u8);
+        }
+    }
+
+    /// Deserialize a timeline written by [`Timeline::write`], after `eighth_count` eighth notes of
+    /// playback. Segments must start at pattern boundaries, in the order they were played.
+    pub(crate) fn read(reader: &mut StateReader<'_>, eighth_count: u32) -> Result<Self, Error> {
+        let mut segments = ArrayVec::<Segment, MAX_JUMPS>::new();
+
+        for _ in 0..reader.u8()? {
+            let segment = Segment {
+                start: reader.u32()?,
+                sequence: usize::from(reader.u8()?),
+            };
+            if segment.sequence >= SEQUENCE_LENGTH
+                || segment.start > eighth_count
+                || !segment.start.is_multiple_of(PATTERN_LENGTH as u32 * 2)
+                || segments
+                    .last()
+                    .is_some_and(|last| last.start >= segment.start)
+            {
+                return Err(Error::InvalidState);
+            }
+            segments
+                .try_push(segment)
+                .map_err(|_| Error::InvalidState)?;
+        }
+        if segments.is_empty() {
+            return Err(Error::InvalidState);
+        }
+
+        Ok(Self { segments })
+    }
+}
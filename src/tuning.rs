@@ -0,0 +1,323 @@
+use crate::song::Error;
+use arrayvec::ArrayVec;
+
+/// Number of note values, including the octave and detune offsets of the oscillators.
+const NUM_NOTES: usize = 256;
+
+/// The note that plays A4 (MIDI note 69).
+const A4: i32 = 144;
+
+/// Difference between note values and MIDI note numbers.
+const MIDI_OFFSET: i32 = A4 - 69;
+
+/// Frequency of A4 in the original tuning, in Hz.
+pub(crate) const ORIGINAL_REFERENCE_PITCH: f32 = 434.082_17;
+
+/// The largest number of pitches in a Scala scale, or keys in a keyboard mapping.
+const MAX_SCALE_LENGTH: usize = 256;
+
+/// Get a `note` frequency on the exponential scale defined by reference
+/// frequency `ref_freq` and reference pitch `ref_pitch`, using the interval
+/// `semitone`.
+pub(crate) fn get_frequency(ref_freq: f32, semitone: f32, note: u8, ref_pitch: u8) -> f32 {
+    ref_freq * libm::powf(semitone, f32::from(note) - f32::from(ref_pitch))
+}
+
+/// Get the absolute frequency for a note value on the 12-TET scale.
+fn get_note_frequency(note: u8) -> f32 {
+    const SEMITONE: f32 = 1.059_463_1; // Twelfth root of 2
+    get_frequency(1.0 / 256.0, SEMITONE, note, 128)
+}
+
+/// Get the frequency of a pitch `cents` away from A4, relative to the 44.1 kHz sample rate.
+fn get_cents_frequency(cents: f64) -> f32 {
+    (f64::from(ORIGINAL_REFERENCE_PITCH) / 44100.0 * libm::exp2(cents / 1200.0)) as f32
+}
+
+/// The pitch of every note value.
+///
+/// The default tuning is 12-TET, like the original player. Tunings can also be created from a
+/// table of cents, or imported from Scala files.
+///
+/// All tunings are relative to the reference pitch set with
+/// [`Synth::set_reference_pitch`](crate::Synth::set_reference_pitch).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    // Frequency of each note, relative to the 44.1 kHz sample rate. Unmapped notes are zero.
+    frequencies: [f32; NUM_NOTES],
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        let mut frequencies = [0.0; NUM_NOTES];
+        for (note, frequency) in (0..=u8::MAX).zip(&mut frequencies) {
+            *frequency = get_note_frequency(note);
+        }
+
+        Self { frequencies }
+    }
+}
+
+impl Tuning {
+    /// Create a tuning with an arbitrary pitch for every note value, in cents relative to A4
+    /// (note `144`) at the reference pitch. In the default tuning, note `n` is
+    /// `(n - 144) * 100` cents.
+    ///
+    /// ```
+    /// use sonant::Tuning;
+    ///
+    /// // 12-TET, with every other note detuned by a quarter tone
+    /// let cents = core::array::from_fn(|n| {
+    ///     let detune = if n % 2 == 0 { 0.0 } else { 50.0 };
+    ///     (n as f32 - 144.0) * 100.0 + detune
+    /// });
+    /// let tuning = Tuning::from_cents(&cents);
+    /// ```
+    #[must_use]
+    pub fn from_cents(cents: &[f32; NUM_NOTES]) -> Self {
+        let mut frequencies = [0.0; NUM_NOTES];
+        for (frequency, &cents) in frequencies.iter_mut().zip(cents) {
+            *frequency = get_cents_frequency(f64::from(cents));
+        }
+
+        Self { frequencies }
+    }
+
+    /// Import a tuning from the contents of a Scala scale file (`.scl`), and an optional keyboard
+    /// mapping file (`.kbm`).
+    ///
+    /// Key numbers in the keyboard mapping are MIDI note numbers, where MIDI note 69 is note `144`
+    /// (A4). Without a keyboard mapping, the first degree of the scale is mapped to middle C
+    /// (note `135`), which keeps its pitch from the default tuning. Notes outside of the range of
+    /// the keyboard mapping keep their pitch from the default tuning, and notes that are mapped
+    /// to `x` are not played.
+    ///
+    /// Scales and keyboard mappings can have up to 256 entries, and the keys of a keyboard
+    /// mapping must be in the MIDI range (`0..=127`).
+    ///
+    /// ```
+    /// use sonant::Tuning;
+    ///
+    /// let scale = "\
+    /// ! slendro.scl
+    /// !
+    /// Approximate slendro, five equal steps
+    /// 5
+    /// !
+    /// 240.0
+    /// 480.0
+    /// 720.0
+    /// 960.0
+    /// 2/1
+    /// ";
+    /// let tuning = Tuning::from_scala(scale, None)?;
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InvalidTuning`] is returned when either file cannot be parsed, when a key of the
+    /// keyboard mapping is out of range, or when its reference key is not mapped.
+    pub fn from_scala(scl: &str, kbm: Option<&str>) -> Result<Self, Error> {
+        let scale = parse_scale(scl)?;
+        let mapping = match kbm {
+            Some(kbm) => parse_mapping(kbm)?,
+            None => Mapping::standard(),
+        };
+
+        // Pitch of each key in cents, relative to the first degree of the scale
+        let key_cents = |key: i32| -> Option<f64> {
+            let offset = key - mapping.middle;
+            if mapping.keys.is_empty() {
+                return Some(scale.degree_cents(offset));
+            }
+
+            let size = i32::from(mapping.keys.len() as u16);
+            let octave = f64::from(offset.div_euclid(size));
+            let degree = mapping.keys[offset.rem_euclid(size) as usize]?;
+
+            Some(octave * scale.degree_cents(mapping.octave) + scale.degree_cents(degree))
+        };
+
+        let reference = key_cents(mapping.reference).ok_or(Error::InvalidTuning)?;
+        let reference_cents = match mapping.frequency {
+            Some(frequency) => 1200.0 * libm::log2(frequency / f64::from(ORIGINAL_REFERENCE_PITCH)),
+            None => f64::from((mapping.reference - A4) * 100),
+        };
+
+        let mut tuning = Self::default();
+        for (key, frequency) in (-MIDI_OFFSET..).zip(&mut tuning.frequencies) {
+            if key < mapping.first || key > mapping.last {
+                continue;
+            }
+
+            *frequency = key_cents(key + MIDI_OFFSET).map_or(0.0, |cents| {
+                get_cents_frequency(cents - reference + reference_cents)
+            });
+        }
+
+        Ok(tuning)
+    }
+
+    /// Get the frequency of a `note`, relative to the 44.1 kHz sample rate. Returns `None` when
+    /// the note is not mapped.
+    pub(crate) fn frequency(&self, note: u8) -> Option<f32> {
+        let frequency = self.frequencies[usize::from(note)];
+
+        (frequency > 0.0).then_some(frequency)
+    }
+}
+
+/// Pitches of a Scala scale, in cents. The first degree (`0` cents) is implied, and the last
+/// pitch is the period of the scale.
+struct Scale {
+    pitches: ArrayVec<f64, MAX_SCALE_LENGTH>,
+}
+
+impl Scale {
+    /// Get the pitch of a scale `degree` in cents, repeating the scale every period.
+    fn degree_cents(&self, degree: i32) -> f64 {
+        let size = i32::from(self.pitches.len() as u16);
+        let period = self.pitches[self.pitches.len() - 1];
+        let octave = f64::from(degree.div_euclid(size));
+        let index = degree.rem_euclid(size) as usize;
+        let cents = if index == 0 {
+            0.0
+        } else {
+            self.pitches[index - 1]
+        };
+
+        octave * period + cents
+    }
+}
+
+/// A Scala keyboard mapping. Key numbers are note values (not MIDI note numbers), except for the
+/// range of keys to retune.
+struct Mapping {
+    first: i32,
+    last: i32,
+    middle: i32,
+    reference: i32,
+    frequency: Option<f64>, // In Hz, or `None` to keep the default tuning of the reference key
+    octave: i32,
+    keys: ArrayVec<Option<i32>, MAX_SCALE_LENGTH>, // Scale degree of each key, or empty for linear
+}
+
+impl Mapping {
+    /// A linear mapping with the first degree of the scale on middle C.
+    fn standard() -> Self {
+        let middle = 60 + MIDI_OFFSET;
+
+        Self {
+            first: -MIDI_OFFSET,
+            last: i32::from(u8::MAX) - MIDI_OFFSET,
+            middle,
+            reference: middle,
+            frequency: None,
+            octave: 0,
+            keys: ArrayVec::new(),
+        }
+    }
+}
+
+/// Get the first word of each line in a Scala file, skipping comments.
+fn scala_lines(file: &str) -> impl Iterator<Item = &str> {
+    file.lines()
+        .filter(|line| !line.starts_with('!'))
+        .map(|line| line.split_whitespace().next().unwrap_or_default())
+}
+
+/// Parse a number from a Scala file.
+fn parse_number<T: core::str::FromStr>(word: Option<&str>) -> Result<T, Error> {
+    word.and_then(|word| word.parse().ok())
+        .ok_or(Error::InvalidTuning)
+}
+
+/// Parse a MIDI key number from a Scala keyboard mapping, which must be in `0..=127`.
+fn parse_key(word: Option<&str>) -> Result<i32, Error> {
+    let key: u8 = parse_number(word)?;
+    if key > 127 {
+        return Err(Error::InvalidTuning);
+    }
+
+    Ok(i32::from(key))
+}
+
+/// Parse a pitch from a Scala scale, either in cents (with a period) or as a ratio.
+fn parse_pitch(word: &str) -> Result<f64, Error> {
+    let cents = if word.contains('.') {
+        parse_number(Some(word))?
+    } else {
+        let (numerator, denominator) = word.split_once('/').unwrap_or((word, "1"));
+        let numerator: f64 = parse_number::<u32>(Some(numerator))?.into();
+        let denominator: f64 = parse_number::<u32>(Some(denominator))?.into();
+        if numerator == 0.0 || denominator == 0.0 {
+            return Err(Error::InvalidTuning);
+        }
+
+        1200.0 * libm::log2(numerator / denominator)
+    };
+
+    if cents.is_finite() {
+        Ok(cents)
+    } else {
+        Err(Error::InvalidTuning)
+    }
+}
+
+/// Parse a Scala scale file.
+fn parse_scale(scl: &str) -> Result<Scale, Error> {
+    // The description may be empty, so it is the only line that is not skipped when empty
+    let mut lines = scala_lines(scl);
+    lines.next().ok_or(Error::InvalidTuning)?;
+    let mut lines = lines.filter(|word| !word.is_empty());
+
+    let count: usize = parse_number(lines.next())?;
+    if count == 0 || count > MAX_SCALE_LENGTH {
+        return Err(Error::InvalidTuning);
+    }
+
+    let mut pitches = ArrayVec::new();
+    for _ in 0..count {
+        let word = lines.next().ok_or(Error::InvalidTuning)?;
+        pitches.push(parse_pitch(word)?);
+    }
+
+    Ok(Scale { pitches })
+}
+
+/// Parse a Scala keyboard mapping file.
+fn parse_mapping(kbm: &str) -> Result<Mapping, Error> {
+    let mut lines = scala_lines(kbm).filter(|word| !word.is_empty());
+
+    let size: usize = parse_number(lines.next())?;
+    let first = parse_key(lines.next())?;
+    let last = parse_key(lines.next())?;
+    let middle = parse_key(lines.next())? + MIDI_OFFSET;
+    let reference = parse_key(lines.next())? + MIDI_OFFSET;
+    let frequency: f64 = parse_number(lines.next())?;
+    let octave = parse_number(lines.next())?;
+    if size > MAX_SCALE_LENGTH || !(frequency.is_finite() && frequency > 0.0) {
+        return Err(Error::InvalidTuning);
+    }
+
+    // Missing entries at the end of the mapping are unmapped
+    let mut keys = ArrayVec::new();
+    for _ in 0..size {
+        let key = match lines.next() {
+            None | Some("x") => None,
+            word => Some(parse_number(word)?),
+        };
+        keys.push(key);
+    }
+
+    Ok(Mapping {
+        first,
+        last,
+        middle,
+        reference,
+        frequency: Some(frequency),
+        octave,
+        keys,
+    })
+}
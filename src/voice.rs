@@ -0,0 +1,217 @@
+use crate::consts::{NUM_CHANNELS, NUM_INSTRUMENTS};
+use crate::oscillator::OscillatorMode;
+use crate::param::Param;
+use crate::song::{Error, Song};
+use crate::state::SynthState;
+use crate::synth::Synth;
+use crate::tuning::Tuning;
+use core::borrow::{Borrow, BorrowMut};
+
+/// Longest note rendered by [`render_note`], in seconds.
+#[cfg(feature = "std")]
+const MAX_NOTE_LENGTH: f32 = 60.0;
+
+/// Plays a single instrument from a `Song` without the sequencer, e.g. for sound effects.
+///
+/// `InstrumentVoice` implements `Iterator` just like [`Synth`], but the iterator never ends. It
+/// produces silence until a note is started with [`InstrumentVoice::note_on`].
+///
+/// ```
+/// use sonant::{InstrumentVoice, Song};
+///
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let mut laser = InstrumentVoice::new(&song, 3, (0, 1), 44100.0);
+///
+/// laser.note_on(140, 1.0);
+/// while laser.is_playing() {
+///     let [sample_l, sample_r] = laser.next().unwrap();
+///     // Do something with the samples
+/// }
+/// # Ok::<(), sonant::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct InstrumentVoice<S> {
+    synth: Synth<S>,
+    instrument: usize,
+}
+
+impl<S: Borrow<Song>> InstrumentVoice<S> {
+    /// Create an `InstrumentVoice` for the given `instrument` index of the `Song`. Like [`Synth`],
+    /// the voice can borrow, own, or share the `Song`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instrument` is not a valid instrument index (`0..8`).
+    #[must_use]
+    pub fn new(song: S, instrument: usize, seed: (u64, u64), sample_rate: f32) -> Self {
+        assert!(instrument < NUM_INSTRUMENTS, "invalid instrument");
+
+        Self {
+            synth: Synth::without_sequencer(song, seed, sample_rate),
+            instrument,
+        }
+    }
+
+    /// Start playing a note. See [`Synth::note_on`].
+    pub fn note_on(&mut self, pitch: u8, velocity: f32) {
+        self.synth.note_on(self.instrument, pitch, velocity);
+    }
+
+    /// Set how the oscillators generate their waveforms. See [`Synth::set_oscillator_mode`].
+    pub fn set_oscillator_mode(&mut self, mode: OscillatorMode) {
+        self.synth.set_oscillator_mode(mode);
+    }
+
+    /// Set the tuning of the notes. See [`Synth::set_tuning`].
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.synth.set_tuning(tuning);
+    }
+
+    /// Set the frequency of A4 in Hz. See [`Synth::set_reference_pitch`].
+    pub fn set_reference_pitch(&mut self, pitch: f32) {
+        self.synth.set_reference_pitch(pitch);
+    }
+
+    /// Check if any notes are playing, or have echoes waiting to be played.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.synth.is_playing()
+    }
+
+    /// Take a snapshot of the playing notes and the noise generator. See [`Synth::state`].
+    #[must_use]
+    pub fn state(&self) -> SynthState {
+        self.synth.state()
+    }
+
+    /// Restore a snapshot taken with [`InstrumentVoice::state`]. See [`Synth::restore`].
+    ///
+    /// ```
+    /// use sonant::{InstrumentVoice, Song};
+    ///
+    /// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+    /// let mut voice = InstrumentVoice::new(&song, 0, (0, 1), 44100.0);
+    /// let silent = voice.state();
+    ///
+    /// // Stop every note, without creating a new voice
+    /// voice.note_on(140, 1.0);
+    /// voice.restore(&silent)?;
+    /// assert!(!voice.is_playing());
+    /// # Ok::<(), sonant::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned when the state is invalid. The voice is unchanged in that case.
+    pub fn restore(&mut self, state: &SynthState) -> Result<(), Error> {
+        self.synth.restore(state)
+    }
+
+    /// Get the value of an instrument parameter. See [`Song::param`].
+    #[must_use]
+    pub fn param(&self, param: Param) -> f32 {
+        self.synth.param(self.instrument, param)
+    }
+}
+
+impl<S: BorrowMut<Song>> InstrumentVoice<S> {
+    /// Set an instrument parameter, including for notes that are already playing. See
+    /// [`Synth::set_param`].
+    pub fn set_param(&mut self, param: Param, value: f32) {
+        self.synth.set_param(self.instrument, param, value);
+    }
+}
+
+impl<S: Borrow<Song>> Iterator for InstrumentVoice<S> {
+    type Item = [f32; NUM_CHANNELS];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.synth.next()
+    }
+}
+
+/// Render a single note of an instrument, including its envelope and delayed echoes, until it is
+/// silent. This can be used to bake sound effects authored as Sonant instruments.
+///
+/// Instruments that repeat their delay forever never fall silent; they are cut off after
+/// 60 seconds.
+///
+/// ```
+/// use sonant::{render_note, Song};
+///
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let laser = render_note(&song, 3, 140, 44100.0, (0, 1));
+/// assert!(!laser.is_empty());
+/// # Ok::<(), sonant::Error>(())
+/// ```
+///
+/// # Panics
+///
+/// Panics if `instrument` is not a valid instrument index (`0..8`).
+#[cfg(feature = "std")]
+#[must_use]
+pub fn render_note(
+    song: &Song,
+    instrument: usize,
+    pitch: u8,
+    sample_rate: f32,
+    seed: (u64, u64),
+) -> Vec<[f32; NUM_CHANNELS]> {
+    let mut voice = InstrumentVoice::new(song, instrument, seed, sample_rate);
+    voice.note_on(pitch, 1.0);
+
+    let max_length = (sample_rate * MAX_NOTE_LENGTH) as usize;
+    let mut samples = Vec::new();
+    while voice.is_playing() && samples.len() < max_length {
+        samples.extend(voice.next());
+    }
+
+    samples
+}
+
+/// Render a single note of an instrument into `buffer`, like [`render_note`] but without
+/// allocating.
+///
+/// Returns the number of frames written, which is less than the length of the buffer if the
+/// note became silent before the buffer was filled. There is no time limit; instruments that
+/// repeat their delay forever fill the whole buffer.
+///
+/// ```
+/// use sonant::{render_note_into, Song};
+///
+/// let song = Song::from_slice(include_bytes!("../examples/poseidon.snt"))?;
+/// let mut buffer = [[0.0; 2]; 44100];
+/// let len = render_note_into(&song, 3, 140, 44100.0, (0, 1), &mut buffer);
+/// let laser = &buffer[..len];
+/// # Ok::<(), sonant::Error>(())
+/// ```
+///
+/// # Panics
+///
+/// Panics if `instrument` is not a valid instrument index (`0..8`).
+pub fn render_note_into(
+    song: &Song,
+    instrument: usize,
+    pitch: u8,
+    sample_rate: f32,
+    seed: (u64, u64),
+    buffer: &mut [[f32; NUM_CHANNELS]],
+) -> usize {
+    let mut voice = InstrumentVoice::new(song, instrument, seed, sample_rate);
+    voice.note_on(pitch, 1.0);
+
+    let mut len = 0;
+    for frame in buffer {
+        if !voice.is_playing() {
+            break;
+        }
+        let Some(samples) = voice.next() else {
+            break;
+        };
+
+        *frame = samples;
+        len += 1;
+    }
+
+    len
+}
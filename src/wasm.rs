@@ -0,0 +1,90 @@
+// wasm-bindgen generates unsafe glue code for the exported types
+#![allow(unsafe_code)]
+
+use crate::song::Song;
+use crate::synth::Synth;
+use wasm_bindgen::prelude::*;
+
+/// A song for JavaScript, exported as `Song`.
+///
+/// ```js
+/// const data = new Uint8Array(await (await fetch("poseidon.snt")).arrayBuffer());
+/// const song = new Song(data);
+/// ```
+#[wasm_bindgen(js_name = Song)]
+#[derive(Debug)]
+pub struct WasmSong {
+    song: Song,
+}
+
+/// A block renderer for JavaScript, exported as `Renderer`. It fills the separate channel buffers
+/// that an `AudioWorkletProcessor` gets for its output.
+///
+/// ```js
+/// const renderer = new Renderer(song, sampleRate, 0n, 1n);
+///
+/// // In `AudioWorkletProcessor.process`
+/// const [left, right] = outputs[0];
+/// const frames = renderer.render(left, right);
+/// return frames === left.length;
+/// ```
+#[wasm_bindgen(js_name = Renderer)]
+#[derive(Debug)]
+pub struct WasmRenderer {
+    synth: Synth<Song>,
+}
+
+#[wasm_bindgen(js_class = Song)]
+impl WasmSong {
+    /// Load a song from the contents of a `.snt` file.
+    ///
+    /// # Errors
+    ///
+    /// An error is thrown when the song data cannot be parsed.
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<WasmSong, JsError> {
+        let song = Song::from_slice(data)?;
+
+        Ok(Self { song })
+    }
+}
+
+#[wasm_bindgen(js_class = Renderer)]
+impl WasmRenderer {
+    /// Create a renderer that plays `song` at `sample_rate` Hz. The seed is used for the noise
+    /// generator. The renderer has its own copy of the song.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new(song: &WasmSong, sample_rate: f32, seed0: u64, seed1: u64) -> WasmRenderer {
+        let synth = Synth::new(song.song.clone(), (seed0, seed1), sample_rate);
+
+        Self { synth }
+    }
+
+    /// Render the next frames into the `left` and `right` channel buffers.
+    ///
+    /// Returns the number of frames written, which is less than the length of the shorter buffer
+    /// only when the song has ended. The rest of the buffers are filled with silence.
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) -> usize {
+        let mut frames = 0;
+
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            [*left, *right] = match self.synth.next() {
+                Some(samples) => {
+                    frames += 1;
+                    samples
+                }
+                None => [0.0; 2],
+            };
+        }
+
+        frames
+    }
+
+    /// The total duration of the song in seconds, or `undefined` if it never ends.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn duration(&self) -> Option<f64> {
+        self.synth.duration().map(|duration| duration.as_secs_f64())
+    }
+}
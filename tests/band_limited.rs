@@ -0,0 +1,148 @@
+//! Spectral tests for the band-limited oscillators and oversampling.
+//!
+//! A single oscillator is played at high pitches, and the energy that does not belong to a
+//! harmonic of the note is measured. For a perfect oscillator, that energy is all aliasing.
+
+use common::{oscillator_song, SAW, SQUARE, TRIANGLE};
+use sonant::{OscillatorMode, Oversampling, Synth};
+use std::f64::consts::PI;
+
+mod common;
+
+const SAMPLE_RATE: f64 = 44100.0;
+const FFT_LENGTH: usize = 16384;
+
+/// Bins around each harmonic that belong to the harmonic (the main lobe of the window).
+const HARMONIC_BINS: f64 = 6.0;
+
+/// Frequency of a note at 44.1 kHz, in Hz.
+fn frequency(pitch: u8) -> f64 {
+    SAMPLE_RATE / 256.0 * 2_f64.powf((f64::from(pitch) - 128.0) / 12.0)
+}
+
+/// In-place radix-2 FFT.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Measure the aliasing of a note, in dB relative to the total energy.
+fn aliasing(waveform: f32, pitch: u8, mode: OscillatorMode, oversampling: Oversampling) -> f64 {
+    let song = oscillator_song(waveform);
+    let mut synth = Synth::new(&song, (0, 1), SAMPLE_RATE as f32);
+    synth.set_oscillator_mode(mode);
+    synth.set_oversampling(oversampling);
+    synth.note_on(0, pitch, 1.0);
+
+    // Skip the start of the note, and apply a Blackman-Harris window
+    let mut re = synth
+        .skip(1000)
+        .take(FFT_LENGTH)
+        .enumerate()
+        .map(|(i, [left, _])| {
+            let x = 2.0 * PI * i as f64 / FFT_LENGTH as f64;
+            let window =
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos();
+            f64::from(left) * window
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(re.len(), FFT_LENGTH);
+    let mut im = vec![0.0; FFT_LENGTH];
+    fft(&mut re, &mut im);
+
+    let bin_width = SAMPLE_RATE / FFT_LENGTH as f64;
+    let f0 = frequency(pitch) / bin_width;
+    let (mut total, mut alias) = (0.0, 0.0);
+    for bin in 0..FFT_LENGTH / 2 {
+        let power = re[bin] * re[bin] + im[bin] * im[bin];
+        let bin = bin as f64;
+
+        // Distance to the nearest harmonic (or DC)
+        let harmonic = (bin / f0).round() * f0;
+        total += power;
+        if (bin - harmonic).abs() > HARMONIC_BINS {
+            alias += power;
+        }
+    }
+
+    10.0 * (alias / total).log10()
+}
+
+#[test]
+fn band_limited_top_notes() {
+    for (name, waveform, threshold) in [
+        ("square", SQUARE, -28.0),
+        ("saw", SAW, -25.0),
+        ("triangle", TRIANGLE, -45.0),
+    ] {
+        for pitch in [160, 172, 184] {
+            let naive = aliasing(waveform, pitch, OscillatorMode::Naive, Oversampling::Off);
+            let band_limited = aliasing(
+                waveform,
+                pitch,
+                OscillatorMode::BandLimited,
+                Oversampling::Off,
+            );
+            assert!(
+                band_limited < threshold,
+                "{name} pitch {pitch} aliasing {band_limited:.1} dB"
+            );
+            assert!(
+                band_limited < naive - 10.0,
+                "{name} pitch {pitch} aliasing {band_limited:.1} dB, naive {naive:.1} dB"
+            );
+        }
+    }
+}
+
+#[test]
+fn oversampling_reduces_aliasing() {
+    for (name, waveform) in [("square", SQUARE), ("saw", SAW)] {
+        for pitch in [160, 172] {
+            let naive = aliasing(waveform, pitch, OscillatorMode::Naive, Oversampling::Off);
+            let x2 = aliasing(waveform, pitch, OscillatorMode::Naive, Oversampling::X2);
+            let x4 = aliasing(waveform, pitch, OscillatorMode::Naive, Oversampling::X4);
+
+            // The oversampled oscillators fold back less energy than the original ones
+            assert!(
+                x2 < naive - 4.0,
+                "{name} {pitch}: {naive:.1} dB, x2 {x2:.1} dB"
+            );
+            assert!(
+                x4 < naive - 4.0,
+                "{name} {pitch}: {naive:.1} dB, x4 {x4:.1} dB"
+            );
+        }
+    }
+}
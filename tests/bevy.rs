@@ -0,0 +1,52 @@
+//! Headless test for the Bevy asset loader and audio source.
+
+#![cfg(feature = "bevy")]
+
+use bevy::asset::LoadState;
+use bevy::audio::{AudioPlugin, Decodable as _, Source as _};
+use bevy::prelude::*;
+use sonant::{SonantPlugin, Song, Synth};
+
+#[test]
+fn bevy_load_song() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: "examples".into(),
+            ..default()
+        },
+        AudioPlugin::default(),
+        SonantPlugin,
+    ));
+
+    let handle: Handle<Song> = app.world().resource::<AssetServer>().load("poseidon.snt");
+
+    // Loading happens on the task pools
+    for _ in 0..1000 {
+        app.update();
+
+        let state = app.world().resource::<AssetServer>().load_state(&handle);
+        match state {
+            LoadState::Loaded => break,
+            LoadState::Failed(err) => panic!("failed to load song: {err}"),
+            _ => std::thread::sleep(std::time::Duration::from_millis(1)),
+        }
+    }
+
+    let songs = app.world().resource::<Assets<Song>>();
+    let song = songs.get(&handle).expect("song is not loaded");
+
+    // The decoder plays the song like a `Synth`
+    let decoder = song.decoder();
+    assert_eq!(decoder.channels(), 2);
+    assert_eq!(decoder.sample_rate(), 44100);
+    assert_eq!(decoder.total_duration().unwrap().as_secs(), 162);
+
+    let expected: Vec<_> = Synth::new(song, (0, 1), 44100.0)
+        .take(4410)
+        .flatten()
+        .collect();
+    let samples: Vec<_> = decoder.take(expected.len()).collect();
+    assert_eq!(samples, expected);
+}
@@ -0,0 +1,249 @@
+//! Tests for the `sonant` command-line tool.
+
+#![cfg(feature = "cli")]
+
+use common::POSEIDON;
+use sonant::{Song, Synth};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+mod common;
+
+/// Run the tool with `args`, without colored output.
+fn sonant(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sonant"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+/// Create an empty directory for the files of a test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// Write the first `patterns` patterns of Poseidon, followed by the delayed echoes, to `dir`.
+fn first_patterns(dir: &Path, patterns: u8) -> (Song, PathBuf) {
+    let data = common::first_patterns(patterns);
+    let path = dir.join("first_patterns.snt");
+    std::fs::write(&path, &data).unwrap();
+
+    (Song::from_slice(&data).unwrap(), path)
+}
+
+/// Read the samples of a 32-bit float WAV file written by the tool.
+fn read_wav(path: &Path) -> Vec<f32> {
+    let file = std::fs::read(path).unwrap();
+    assert_eq!(&file[..4], b"RIFF");
+    assert_eq!(&file[50..54], b"data");
+
+    file[58..]
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn cli_render_matches_synth() {
+    let dir = test_dir("render");
+    let (song, input) = first_patterns(&dir, 1);
+    let output = dir.join("out.wav");
+
+    let args = [
+        Path::new("render"),
+        &input,
+        &output,
+        Path::new("--bits=32"),
+        Path::new("--rate=48000"),
+    ];
+    assert!(sonant(&args).status.success());
+
+    let expected: Vec<_> = Synth::new(&song, (0, 1), 48000.0).flatten().collect();
+    assert_eq!(read_wav(&output), expected);
+}
+
+#[test]
+fn cli_render_loops() {
+    let dir = test_dir("loops");
+    let (song, input) = first_patterns(&dir, 1);
+    let output = dir.join("out.wav");
+
+    let args = [
+        Path::new("render"),
+        &input,
+        &output,
+        Path::new("--bits=32"),
+        Path::new("--loops=2"),
+    ];
+    assert!(sonant(&args).status.success());
+
+    // Find the length of the pattern in a song that continues after it
+    let (longer, _) = first_patterns(&dir, 2);
+    let mut synth = Synth::new(&longer, (0, 1), 44100.0);
+    while synth.position().sequence == 0 {
+        synth.next();
+    }
+    let pattern = synth.position().sample as usize * 2;
+
+    // The second pass starts at the end of the first one, over its delayed echoes
+    let samples = read_wav(&output);
+    let once: Vec<_> = Synth::new(&song, (0, 1), 44100.0).flatten().collect();
+    assert_eq!(samples.len(), once.len() + pattern);
+    assert_eq!(samples[..pattern], once[..pattern]);
+}
+
+#[test]
+fn cli_render_loops_doubles_the_length() {
+    let dir = test_dir("loops_length");
+
+    for patterns in [1, 2] {
+        let (_, input) = first_patterns(&dir, patterns);
+        let output = dir.join("out.wav");
+        let frames = |loops: &str| {
+            let loops = format!("--loops={loops}");
+            let args = [Path::new("render"), &input, &output, Path::new(&loops)];
+            assert!(sonant(&args).status.success());
+
+            (std::fs::metadata(&output).unwrap().len() as usize - 44) / 4
+        };
+        let once = frames("1");
+        let twice = frames("2");
+
+        // The second pass adds the length of the sequence, and shares the echoes at the end
+        let row_length = common::row_length(&common::first_patterns(patterns)) as usize;
+        let sequence = usize::from(patterns) * 32 * row_length;
+        assert_eq!(twice, once + sequence, "{patterns} patterns");
+        assert!(
+            twice > once * 3 / 2 && twice < once * 2,
+            "{patterns} patterns"
+        );
+    }
+}
+
+#[test]
+fn cli_render_stems() {
+    let dir = test_dir("stems");
+    let (song, input) = first_patterns(&dir, 2);
+
+    let args = [
+        Path::new("render"),
+        &input,
+        &dir.join("out.wav"),
+        Path::new("--bits=32"),
+        Path::new("--stems"),
+    ];
+    assert!(sonant(&args).status.success());
+    assert!(!dir.join("out.wav").exists());
+
+    // Only the first two tracks play in the first two patterns
+    let polyphony = Synth::new(&song, (0, 1), 44100.0).max_polyphony();
+    assert_eq!(polyphony.iter().filter(|&&voices| voices > 0).count(), 2);
+
+    for (track, voices) in polyphony.into_iter().enumerate() {
+        let path = dir.join(format!("out-{}.wav", track + 1));
+        assert_eq!(path.exists(), voices > 0, "track {track}");
+        if voices == 0 {
+            continue;
+        }
+
+        // Each stem is the song with the other tracks muted
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        for other in (0..8).filter(|&other| other != track) {
+            for sequence in 0..song.sequence_length() {
+                synth.set_sequence_entry(other, sequence, 0);
+            }
+        }
+        let expected: Vec<_> = synth.flatten().collect();
+        let stem = read_wav(&path);
+        assert!(stem == expected, "track {track}");
+        assert!(stem.iter().any(|&sample| sample != 0.0), "track {track}");
+    }
+}
+
+#[test]
+fn cli_render_rejects_unknown_format() {
+    let dir = test_dir("unknown_format");
+    let (_, input) = first_patterns(&dir, 1);
+
+    let output = sonant(&[Path::new("render"), &input, &dir.join("out.mp3")]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: Unknown format"), "{stderr}");
+}
+
+#[test]
+fn cli_info() {
+    let output = sonant(&[Path::new("info"), Path::new("examples/poseidon.snt")]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Tempo:       144.0 BPM"), "{stdout}");
+    assert!(stdout.contains("Duration:    2:42.9"), "{stdout}");
+    assert!(stdout.contains("Instruments: 1 2 3 4 5 6 7 8"), "{stdout}");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("warning: examples/poseidon.snt: track 1 plays up to 26 notes"));
+}
+
+#[test]
+fn cli_validate() {
+    let dir = test_dir("validate");
+    let invalid = dir.join("invalid.snt");
+    std::fs::write(&invalid, &POSEIDON[1..]).unwrap();
+
+    let output = sonant(&[
+        Path::new("validate"),
+        Path::new("examples/poseidon.snt"),
+        &invalid,
+    ]);
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "ok: examples/poseidon.snt\n");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = format!(
+        "error: Invalid song {}\ncaused by: Incorrect file length\n\
+         error: 1 of 2 files are not valid songs\n",
+        invalid.display(),
+    );
+    assert!(stderr.ends_with(&expected), "{stderr}");
+}
+
+#[test]
+fn cli_convert() {
+    let dir = test_dir("convert");
+    let header = dir.join("poseidon.h");
+    let source = dir.join("poseidon.rs");
+
+    for output in [&header, &source] {
+        let args = [
+            Path::new("convert"),
+            Path::new("examples/poseidon.snt"),
+            output,
+        ];
+        assert!(sonant(&args).status.success());
+    }
+
+    let header = std::fs::read_to_string(header).unwrap();
+    assert!(header.contains("static const unsigned char poseidon[3333] = {\n    0xf1, 0x11,"));
+
+    // The Rust array holds the bytes of the song
+    let source = std::fs::read_to_string(source).unwrap();
+    let (_, array) = source
+        .split_once("pub const POSEIDON: [u8; 3333] = [")
+        .unwrap();
+    let bytes: Vec<u8> = array
+        .split(',')
+        .filter_map(|byte| byte.trim().strip_prefix("0x"))
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect();
+    assert_eq!(bytes, POSEIDON);
+}
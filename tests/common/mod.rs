@@ -0,0 +1,63 @@
+//! Song fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use sonant::{Param, Song};
+
+pub const POSEIDON: &[u8] = include_bytes!("../../examples/poseidon.snt");
+
+/// Offset of the first instrument, and the length of each instrument in the file.
+pub const INSTRUMENTS: usize = 4;
+pub const INSTRUMENT_LENGTH: usize = 0x1a0;
+
+/// Offset of the sequence length, which is one less than the number of patterns played.
+const SEQUENCE_LENGTH: usize = INSTRUMENTS + INSTRUMENT_LENGTH * 8;
+
+/// Length of a song file with every instrument and pattern.
+const SONG_LENGTH: usize = 3333;
+
+/// Oscillator waveforms, as stored in the file.
+pub const SINE: f32 = 0.0;
+pub const SQUARE: f32 = 1.0;
+pub const SAW: f32 = 2.0;
+pub const TRIANGLE: f32 = 3.0;
+
+/// Read the row length (in samples at 44.1 kHz) of a song file.
+pub fn row_length(data: &[u8]) -> u32 {
+    u32::from_le_bytes(data[..4].try_into().unwrap())
+}
+
+/// The first `patterns` patterns of Poseidon, followed by the delayed echoes.
+pub fn first_patterns(patterns: u8) -> Vec<u8> {
+    let mut data = POSEIDON.to_vec();
+    data[SEQUENCE_LENGTH] = patterns - 1;
+
+    data
+}
+
+/// A song with silent instruments and no patterns, where each row is `row_length` samples long.
+pub fn empty_song(row_length: u32) -> Song {
+    let mut data = vec![0; SONG_LENGTH];
+    data[..4].copy_from_slice(&row_length.to_le_bytes());
+
+    Song::from_slice(&data).unwrap()
+}
+
+/// A song with no patterns, where instrument 0 plays oscillator 0 with `waveform` at the pitch of
+/// the note, and holds live notes for about two seconds.
+pub fn oscillator_song(waveform: f32) -> Song {
+    let mut song = empty_song(5513);
+    for (param, value) in [
+        (Param::Osc0Octave, 8.0),
+        (Param::Osc0Volume, 255.0),
+        (Param::Osc0Waveform, waveform),
+        (Param::EnvAttack, 0.0),
+        (Param::EnvSustain, 100_000.0),
+        (Param::EnvRelease, 1.0),
+        (Param::EnvMaster, 100.0),
+    ] {
+        song.set_param(0, param, value);
+    }
+
+    song
+}
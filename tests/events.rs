@@ -0,0 +1,86 @@
+//! Tests for playback events and the playback position.
+
+use sonant::{Event, Position, Song, Synth};
+
+mod common;
+
+/// Render the first two patterns of Poseidon, returning every event with the index of the frame
+/// that was generated when it was sent.
+fn events(synth: &mut Synth<&Song>) -> Vec<(usize, Position, Event)> {
+    let mut frame = 0;
+    let mut events = Vec::new();
+    loop {
+        let more = synth
+            .next_with_events(&mut |position: &Position, event| {
+                events.push((frame, *position, event));
+            })
+            .is_some();
+        if !more {
+            break;
+        }
+        frame += 1;
+
+        // The position is reported for the next sample
+        assert_eq!(Synth::position(synth).sample as usize, frame);
+    }
+
+    events
+}
+
+#[test]
+fn event_positions_match_row_starts() {
+    let data = common::first_patterns(2);
+    let row_length = common::row_length(&data);
+    let song = Song::from_slice(&data).unwrap();
+
+    for rate in [44100.0, 48000.0] {
+        let mut synth = Synth::new(&song, (0, 1), rate);
+        let events = events(&mut synth);
+
+        let mut rows = 0;
+        let mut row_start = 0;
+        for &(frame, position, event) in &events {
+            // Events are sent at the sample they occur on
+            assert_eq!(position.sample as usize, frame);
+            let elapsed = f64::from(position.sample) / f64::from(rate);
+            assert!((position.elapsed.as_secs_f64() - elapsed).abs() < 1e-6);
+
+            match event {
+                Event::Row { sequence, row } => {
+                    // Rows start on the first sample at or after their exact start time
+                    let start =
+                        (rows * 2) as f64 * f64::from(rate / 44100.0) * f64::from(row_length) / 2.0;
+                    assert_eq!(f64::from(position.sample), start.ceil(), "row {rows}");
+                    assert_eq!((sequence, row), (rows / 32, rows % 32));
+                    assert_eq!((position.sequence, position.row), (sequence, row));
+                    assert_eq!(position.row_sample, 0);
+
+                    row_start = position.sample;
+                    rows += 1;
+                }
+                Event::Pattern { sequence } => {
+                    assert_eq!(sequence, rows / 32);
+                    assert_eq!(position.row, 0);
+                    assert_eq!(position.row_sample, 0);
+                }
+                Event::Note { echo: false, .. } => {
+                    // Notes from the patterns start with their row
+                    assert_eq!(position.sample, row_start);
+                }
+                Event::Note { echo: true, .. } => {
+                    assert!(position.sample >= row_start);
+                }
+                Event::End => {
+                    assert_eq!(rows, 64);
+                    assert_eq!(position.sequence, 2);
+                    assert_eq!(position.row, 0);
+                }
+            }
+        }
+        assert_eq!(rows, 64);
+        assert!(matches!(
+            events.last(),
+            Some((_, _, Event::Note { .. } | Event::End))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+//! Round trip test for the FLAC encoder, decoding with `claxon`.
+
+#![cfg(feature = "flac")]
+
+use sonant::export::flac::{self, Encoder, Format, Options};
+use sonant::{Song, Synth};
+use std::io::Cursor;
+
+mod common;
+
+const SAMPLE_RATE: u32 = 44100;
+
+fn poseidon() -> Song {
+    Song::from_slice(common::POSEIDON).unwrap()
+}
+
+/// Encode `frames` with the given `options`.
+fn encode(frames: &[[f32; 2]], options: &Options) -> Vec<u8> {
+    let mut encoder = Encoder::new(Cursor::new(Vec::new()), SAMPLE_RATE, options).unwrap();
+    encoder.write(frames).unwrap();
+
+    encoder.finish().unwrap().into_inner()
+}
+
+/// Decode a FLAC file, and check its stream info.
+fn decode(file: &[u8], format: Format, frames: usize) -> Vec<i32> {
+    let mut reader = claxon::FlacReader::new(file).unwrap();
+    let info = reader.streaminfo();
+    assert_eq!(info.sample_rate, SAMPLE_RATE);
+    assert_eq!(info.channels, 2);
+    assert_eq!(info.bits_per_sample, bits(format));
+    assert_eq!(info.samples.unwrap_or(0), frames as u64); // Zero means unknown
+
+    reader.samples().map(Result::unwrap).collect()
+}
+
+fn bits(format: Format) -> u32 {
+    match format {
+        Format::Int16 => 16,
+        Format::Int24 => 24,
+    }
+}
+
+/// Quantize samples without dither, like the encoder.
+fn quantize(frames: &[[f32; 2]], format: Format) -> Vec<i32> {
+    let max = ((1 << (bits(format) - 1)) - 1) as f32;
+
+    frames
+        .iter()
+        .flatten()
+        .map(|sample| (sample * max).round().clamp(-max - 1.0, max) as i32)
+        .collect()
+}
+
+#[test]
+fn flac_round_trip() {
+    let song = poseidon();
+
+    // A few seconds that do not fill the last block
+    let frames: Vec<_> = Synth::new(&song, (0, 1), SAMPLE_RATE as f32)
+        .take(3 * 44100 + 123)
+        .collect();
+
+    for format in [Format::Int16, Format::Int24] {
+        let options = Options {
+            format,
+            ..Options::default()
+        };
+        let file = encode(&frames, &options);
+        let decoded = decode(&file, format, frames.len());
+        assert!(decoded == quantize(&frames, format), "{format:?} mismatch");
+
+        let raw_length = frames.len() * 2 * bits(format) as usize / 8;
+        assert!(file.len() < raw_length);
+    }
+}
+
+#[test]
+fn flac_write_synth() {
+    // A short, silent song
+    let song = common::empty_song(16);
+    let synth = Synth::new(&song, (0, 1), SAMPLE_RATE as f32);
+
+    let mut file = Cursor::new(Vec::new());
+    let frames = flac::write(synth, &mut file, &Options::default()).unwrap();
+    assert_eq!(frames, 512);
+
+    let decoded = decode(file.get_ref(), Format::Int16, 512);
+    assert!(decoded.iter().all(|&sample| sample == 0));
+}
+
+#[test]
+fn flac_short_streams() {
+    let options = Options::default();
+
+    for len in [0, 1, 5, 4096, 4097] {
+        let frames: Vec<_> = (0..len)
+            .map(|i| {
+                let t = i as f32 / 50.0;
+                [t.sin() * 0.5, 0.25]
+            })
+            .collect();
+
+        let file = encode(&frames, &options);
+        let decoded = decode(&file, Format::Int16, len);
+        assert!(decoded == quantize(&frames, Format::Int16), "length {len}");
+    }
+}
+
+#[test]
+fn flac_dither_is_reproducible() {
+    let song = poseidon();
+    let frames: Vec<_> = Synth::new(&song, (0, 1), SAMPLE_RATE as f32)
+        .take(10000)
+        .collect();
+
+    let options = Options {
+        dither: true,
+        ..Options::default()
+    };
+    let file = encode(&frames, &options);
+    assert_eq!(file, encode(&frames, &options));
+
+    // Dither stays within one step of the quantized samples
+    let decoded = decode(&file, Format::Int16, frames.len());
+    let expected = quantize(&frames, Format::Int16);
+    assert!(decoded
+        .iter()
+        .zip(&expected)
+        .all(|(a, b)| (a - b).abs() <= 1));
+    assert!(decoded != expected);
+}
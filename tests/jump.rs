@@ -0,0 +1,81 @@
+//! Tests for delayed echoes across `Synth::jump_to`.
+
+use common::POSEIDON;
+use sonant::{Clipping, Event, Param, Position, Song, Synth};
+
+mod common;
+
+/// Poseidon at a fast tempo, where instrument 0 has delayed echoes.
+fn song(row_length: u32, delay_time: f32, delay_amount: f32) -> Song {
+    let mut data = POSEIDON.to_vec();
+    data[..4].copy_from_slice(&row_length.to_le_bytes());
+
+    let mut song = Song::from_slice(&data).unwrap();
+    song.set_param(0, Param::FxDelayTime, delay_time);
+    song.set_param(0, Param::FxDelayAmount, delay_amount);
+
+    song
+}
+
+/// A synth that only plays instrument 0.
+fn solo(song: &Song) -> Synth<&Song> {
+    let mut synth = Synth::new(song, (0, 1), 44100.0);
+    synth.set_clipping(Clipping::Off);
+    for track in 1..8 {
+        for sequence in 0..48 {
+            synth.set_sequence_entry(track, sequence, 0);
+        }
+    }
+
+    synth
+}
+
+#[test]
+fn jump_echoes_continue_across_the_boundary() {
+    let song = song(500, 4.0, 128.0);
+    let pattern = 16_000;
+
+    // Both synths play a silent pattern after the first one, so only echoes are heard
+    let mut jumped = solo(&song);
+    jumped.set_sequence_entry(0, 5, 0);
+    jumped.jump_to(5);
+    let mut reference = solo(&song);
+    reference.set_sequence_entry(0, 1, 0);
+
+    let jumped: Vec<_> = jumped.take(2 * pattern).collect();
+    let reference: Vec<_> = reference.take(2 * pattern).collect();
+    assert!(jumped == reference);
+
+    let energy: f32 = jumped[pattern..].iter().flatten().map(|x| x * x).sum();
+    assert!(energy > 1.0);
+}
+
+#[test]
+fn jump_history_drops_the_oldest_echoes() {
+    // Only the first pattern has notes, and they echo forever
+    let song = song(100, 16.0, 255.0);
+    let mut synth = solo(&song);
+    synth.set_sequence_entry(0, 1, 0);
+
+    let mut echoes = Vec::new();
+    let mut on_event = |_: &Position, event| match event {
+        Event::Pattern { .. } => echoes.push(0),
+        Event::Note { echo: true, .. } => *echoes.last_mut().unwrap() += 1,
+        _ => (),
+    };
+
+    // Play the first pattern twice, then jump to the silent pattern over and over
+    for jump in 0..20 {
+        synth.jump_to(usize::from(jump > 0));
+        for _ in 0..3200 {
+            synth.next_with_events(&mut on_event);
+        }
+    }
+    assert_eq!(echoes.len(), 20);
+
+    // Only the last 16 jumps are remembered. The 16th jump drops the echoes of the start of the
+    // song, and the 17th drops the echoes of the first jump, which leaves nothing to echo.
+    assert!(echoes[1..=16].iter().all(|&count| count > 0), "{echoes:?}");
+    assert!(echoes[15] > echoes[16], "{echoes:?}");
+    assert!(echoes[17..].iter().all(|&count| count == 0), "{echoes:?}");
+}
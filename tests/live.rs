@@ -0,0 +1,106 @@
+//! Tests for notes started with `Synth::note_on`.
+
+use common::POSEIDON;
+use sonant::{Clipping, Event, Param, Song, Synth};
+
+mod common;
+
+/// Frames rendered before the live note starts.
+const DELAY: usize = 10_000;
+
+/// Poseidon without the noise oscillators, so the output does not depend on how many notes draw
+/// from the noise generator.
+fn poseidon_without_noise() -> Song {
+    let mut song = Song::from_slice(POSEIDON).unwrap();
+    for track in 0..8 {
+        song.set_param(track, Param::NoiseVolume, 0.0);
+    }
+
+    song
+}
+
+/// Render `frames` frames, starting a note on track 3 after `DELAY` frames if `live` is set.
+fn render(synth: &mut Synth<&Song>, frames: usize, live: bool) -> Vec<[f32; 2]> {
+    synth.set_clipping(Clipping::Off);
+
+    let mut output: Vec<_> = synth.by_ref().take(DELAY).collect();
+    if live {
+        synth.note_on(3, 140, 0.8);
+    }
+    output.extend(synth.by_ref().take(frames - DELAY));
+
+    output
+}
+
+#[test]
+fn live_notes_mix_with_the_sequence() {
+    let song = poseidon_without_noise();
+    let frames = 60_000;
+    let sequenced = render(&mut Synth::new(&song, (0, 1), 44100.0), frames, false);
+    let mixed = render(&mut Synth::new(&song, (0, 1), 44100.0), frames, true);
+
+    // The same note, with every pattern of the sequence silenced
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    for track in 0..8 {
+        for sequence in 0..48 {
+            synth.set_sequence_entry(track, sequence, 0);
+        }
+    }
+    let live = render(&mut synth, frames, true);
+
+    // Nothing changes before the note starts, and the note is mixed in on the next sample
+    assert!(mixed[..DELAY] == sequenced[..DELAY]);
+    assert!(live[..DELAY].iter().all(|&frame| frame == [0.0; 2]));
+    assert!(live[DELAY..DELAY + 100]
+        .iter()
+        .any(|&frame| frame != [0.0; 2]));
+
+    for (i, ((mixed, sequenced), live)) in mixed.iter().zip(&sequenced).zip(&live).enumerate() {
+        for channel in 0..2 {
+            let difference = mixed[channel] - sequenced[channel];
+            assert!((difference - live[channel]).abs() < 1e-5, "frame {i}");
+        }
+    }
+}
+
+/// Start `count` notes with different pitches on a track with delayed echoes, and count the echoes
+/// of each pitch.
+fn count_echoes(count: u8) -> Vec<usize> {
+    let mut song = common::oscillator_song(common::SINE);
+    song.set_param(0, Param::FxDelayTime, 1.0);
+    song.set_param(0, Param::FxDelayAmount, 128.0);
+
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    let pitches = 100..100 + count;
+    for pitch in pitches.clone() {
+        synth.note_on(0, pitch, 1.0);
+    }
+
+    let mut echoes = vec![0; usize::from(count)];
+    let mut on_event = |_: &sonant::Position, event| {
+        if let Event::Note {
+            pitch, echo: true, ..
+        } = event
+        {
+            echoes[usize::from(pitch - 100)] += 1;
+        }
+    };
+    for _ in 0..44100 {
+        synth.next_with_events(&mut on_event);
+    }
+
+    echoes
+}
+
+#[test]
+fn live_note_limit_drops_the_oldest_echoes() {
+    // Every note has all of its echoes
+    let echoes = count_echoes(16);
+    assert!(echoes[0] > 0);
+    assert!(echoes.iter().all(|&count| count == echoes[0]));
+
+    // The 17th note replaces the echoes of the first
+    let limited = count_echoes(17);
+    assert_eq!(limited[0], 0);
+    assert!(limited[1..].iter().all(|&count| count == echoes[0]));
+}
@@ -0,0 +1,102 @@
+//! Tests for the master output stage: gain, clipping curves, and the clip report.
+
+use common::POSEIDON;
+use sonant::{ClipReport, Clipping, Song, Synth};
+
+mod common;
+
+/// Length of the rendered excerpts, in frames.
+const FRAMES: usize = 44100;
+
+/// Render the start of Poseidon with `gain` and `clipping`, flattened to interleaved samples.
+fn render(gain: f32, clipping: Clipping) -> (Vec<f32>, ClipReport) {
+    let song = Song::from_slice(POSEIDON).unwrap();
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_gain(gain);
+    synth.set_clipping(clipping);
+
+    let samples = synth.by_ref().take(FRAMES).flatten().collect();
+
+    (samples, synth.clip_report())
+}
+
+/// The soft clipping curve: linear up to -6 dBFS, then a `tanh` knee up to full scale.
+fn soft_clip(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= 0.5 {
+        sample
+    } else {
+        (0.5 + 0.5 * ((magnitude - 0.5) / 0.5).tanh()).copysign(sample)
+    }
+}
+
+#[test]
+fn master_clipping_curves() {
+    let (unclipped, _) = render(4.0, Clipping::Off);
+    let (hard, _) = render(4.0, Clipping::Hard);
+    let (soft, _) = render(4.0, Clipping::Soft);
+
+    // The excerpt covers quiet samples, the knee, and samples far over full scale
+    assert!(unclipped.iter().any(|sample| sample.abs() < 0.5));
+    assert!(unclipped
+        .iter()
+        .any(|sample| (0.5..1.0).contains(&sample.abs())));
+    assert!(unclipped.iter().any(|sample| sample.abs() > 1.5));
+
+    for ((&input, &hard), &soft) in unclipped.iter().zip(&hard).zip(&soft) {
+        assert_eq!(hard, input.clamp(-1.0, 1.0));
+        assert!((soft - soft_clip(input)).abs() < 1e-6, "{input}: {soft}");
+        assert!(soft.abs() < 1.0);
+    }
+}
+
+#[test]
+fn master_clipping_curve_levels() {
+    // Compare the curves at a few levels by scaling the same song
+    let (reference, _) = render(1.0, Clipping::Off);
+    let index = reference
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .unwrap()
+        .0;
+    let peak = reference[index].abs();
+
+    for level in [0.25, 0.5, 0.75, 1.0, 2.0, 8.0] {
+        let gain = level / peak;
+        let sample = |clipping| render(gain, clipping).0[index].abs();
+
+        assert!((sample(Clipping::Off) - level).abs() < 1e-5, "{level}");
+        assert!(
+            (sample(Clipping::Hard) - level.min(1.0)).abs() < 1e-5,
+            "{level}"
+        );
+        assert!(
+            (sample(Clipping::Soft) - soft_clip(level)).abs() < 1e-5,
+            "{level}"
+        );
+    }
+}
+
+#[test]
+fn master_clip_report() {
+    let (hard, report) = render(4.0, Clipping::Hard);
+    let (expected, _) = render(4.0, Clipping::Off);
+    assert!(hard.iter().all(|sample| sample.abs() <= 1.0));
+
+    let clipped = expected.iter().filter(|sample| sample.abs() > 1.0).count();
+    let peak = expected
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    assert!(clipped > 0);
+    assert_eq!(report.clipped, clipped as u64);
+    assert_eq!(report.peak, peak);
+
+    // Quiet songs don't clip, but still report their peak
+    let (quiet, report) = render(0.1, Clipping::Hard);
+    let peak = quiet
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    assert_eq!(report.clipped, 0);
+    assert_eq!(report.peak, peak);
+}
@@ -0,0 +1,64 @@
+//! Tests for the per-track level meters and voice envelopes.
+
+use sonant::{Clipping, Synth};
+
+mod common;
+
+/// Length of each meter block, in samples.
+const BLOCK: usize = 441;
+
+#[test]
+fn meter_measures_a_sine_wave() {
+    let song = common::oscillator_song(common::SINE);
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_clipping(Clipping::Off);
+    synth.set_meter_block(BLOCK as u32);
+    synth.note_on(0, 140, 1.0);
+
+    for block in 0..10 {
+        let frames: Vec<_> = synth.by_ref().take(BLOCK).collect();
+        let levels = synth.track_levels();
+
+        // The note is the only sound, so the track level is the level of the output
+        let sum_sq: f32 = frames.iter().flatten().map(|sample| sample * sample).sum();
+        let rms = (sum_sq / (BLOCK * 2) as f32).sqrt();
+        let peak = frames
+            .iter()
+            .flatten()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!((levels[0].rms - rms).abs() < 1e-6, "block {block}");
+        assert_eq!(levels[0].peak, peak, "block {block}");
+        assert!(levels[1..]
+            .iter()
+            .all(|level| level.rms == 0.0 && level.peak == 0.0));
+
+        // A sine wave spanning several cycles has an RMS of its peak over the square root of two
+        assert!(peak > 0.1);
+        let ratio = levels[0].rms / levels[0].peak;
+        assert!(
+            (ratio - 0.5_f32.sqrt()).abs() < 0.02,
+            "block {block}: {ratio}"
+        );
+
+        // The note is sustained at full volume
+        let envelopes = synth.voice_envelopes()[0];
+        assert_eq!(envelopes[0], 1.0);
+        assert!(envelopes[1..].iter().all(|&envelope| envelope == 0.0));
+    }
+}
+
+#[test]
+fn meter_publishes_at_the_end_of_each_block() {
+    let song = common::oscillator_song(common::SINE);
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_meter_block(BLOCK as u32);
+    synth.note_on(0, 140, 1.0);
+
+    synth.by_ref().take(BLOCK - 1).for_each(drop);
+    assert_eq!(synth.track_levels()[0].peak, 0.0);
+    assert_eq!(synth.voice_envelopes()[0][0], 0.0);
+
+    synth.next();
+    assert!(synth.track_levels()[0].peak > 0.0);
+    assert_eq!(synth.voice_envelopes()[0][0], 1.0);
+}
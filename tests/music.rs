@@ -0,0 +1,178 @@
+//! Tests for switching songs with `MusicPlayer`.
+
+use common::POSEIDON;
+use sonant::{Event, MusicPlayer, Position, Song, Synth, Transition};
+use std::f32::consts::FRAC_PI_2;
+
+mod common;
+
+/// Frames played before switching songs.
+const START: usize = 1000;
+
+/// Poseidon at a faster tempo, with 16000 samples in each pattern.
+fn fast_poseidon() -> Song {
+    let mut data = POSEIDON.to_vec();
+    data[..4].copy_from_slice(&500_u32.to_le_bytes());
+
+    Song::from_slice(&data).unwrap()
+}
+
+/// Another song, which plays Poseidon with a different seed.
+fn other(song: &Song) -> Synth<&Song> {
+    Synth::new(song, (2, 3), 44100.0)
+}
+
+/// A silent song that ends after 512 samples.
+fn short_song() -> Song {
+    common::empty_song(16)
+}
+
+fn assert_close(actual: [f32; 2], expected: [f32; 2], frame: usize) {
+    for (actual, expected) in actual.into_iter().zip(expected) {
+        assert!((actual - expected).abs() < 1e-6, "frame {frame}");
+    }
+}
+
+#[test]
+fn music_crossfade_is_equal_power() {
+    let song = Song::from_slice(POSEIDON).unwrap();
+    let length = 1000;
+    let current: Vec<_> = Synth::new(&song, (0, 1), 44100.0)
+        .take(START + 2 * length)
+        .collect();
+    let incoming: Vec<_> = other(&song).take(2 * length).collect();
+
+    let mut player = MusicPlayer::new();
+    player.play(Synth::new(&song, (0, 1), 44100.0), Transition::Cut);
+    assert!(player
+        .by_ref()
+        .take(START)
+        .eq(current[..START].iter().copied()));
+
+    player.play(
+        other(&song),
+        Transition::Crossfade {
+            samples: length as u32,
+        },
+    );
+    let output: Vec<_> = player.take(2 * length).collect();
+
+    let gains = |k: usize| {
+        let t = (k + 1) as f32 / length as f32 * FRAC_PI_2;
+        (t.cos(), t.sin())
+    };
+    let mix = |k: usize, (gain_out, gain_in): (f32, f32)| {
+        let [out_l, out_r] = current[START + k];
+        let [in_l, in_r] = incoming[k];
+        [
+            out_l * gain_out + in_l * gain_in,
+            out_r * gain_out + in_r * gain_in,
+        ]
+    };
+
+    // The gains start near the current song, meet at -3 dB, and end on the incoming song
+    let (gain_out, gain_in) = gains(0);
+    assert!(gain_out > 0.999 && gain_in < 0.002);
+    let middle = length / 2 - 1;
+    let (gain_out, gain_in) = gains(middle);
+    assert!((gain_out - 0.5_f32.sqrt()).abs() < 1e-6);
+    assert!((gain_in - 0.5_f32.sqrt()).abs() < 1e-6);
+    assert_close(output[length - 1], incoming[length - 1], length - 1);
+
+    for (k, &frame) in output[..length].iter().enumerate() {
+        assert_close(frame, mix(k, gains(k)), k);
+    }
+    assert!(output[length..] == incoming[length..]);
+}
+
+/// Find the frame where the second pattern of `song` starts.
+fn second_pattern(song: &Song) -> usize {
+    let mut synth = Synth::new(song, (0, 1), 44100.0);
+    let mut frame = 0;
+    loop {
+        let mut found = false;
+        synth.next_with_events(&mut |_: &Position, event| {
+            found |= event == Event::Pattern { sequence: 1 };
+        });
+        if found {
+            return frame;
+        }
+        frame += 1;
+    }
+}
+
+#[test]
+fn music_next_pattern_waits_for_the_boundary() {
+    let song = fast_poseidon();
+    let boundary = second_pattern(&song);
+    assert_eq!(boundary, 16000);
+
+    let current: Vec<_> = Synth::new(&song, (0, 1), 44100.0).take(boundary).collect();
+    let incoming: Vec<_> = other(&song).take(1000).collect();
+
+    let mut player = MusicPlayer::new();
+    player.play(Synth::new(&song, (0, 1), 44100.0), Transition::Cut);
+    player.by_ref().take(START).for_each(drop);
+    player.play(other(&song), Transition::NextPattern { samples: 0 });
+    let output: Vec<_> = player.take(boundary - START + 1000).collect();
+
+    // The current song plays to the end of its pattern, then the new song starts from the top
+    assert!(output[..boundary - START] == current[START..]);
+    for (k, &frame) in output[boundary - START..].iter().enumerate() {
+        assert_close(frame, incoming[k], k);
+    }
+}
+
+#[test]
+fn music_stinger_resumes_with_a_fade_in() {
+    let song = Song::from_slice(POSEIDON).unwrap();
+    let stinger = short_song();
+    let length = 100;
+    let stinger_length = 512;
+    let current: Vec<_> = Synth::new(&song, (0, 1), 44100.0)
+        .take(START + stinger_length + 2 * length)
+        .collect();
+
+    let mut player = MusicPlayer::new();
+    player.play(Synth::new(&song, (0, 1), 44100.0), Transition::Cut);
+    player.by_ref().take(START).for_each(drop);
+
+    let transition = Transition::Crossfade {
+        samples: length as u32,
+    };
+    player.play_stinger(Synth::new(&stinger, (0, 1), 44100.0), transition);
+    let output: Vec<_> = player.by_ref().take(stinger_length).collect();
+
+    // The song fades out into the silent stinger, and pauses when the crossfade ends
+    for (k, &frame) in output[..length].iter().enumerate() {
+        let gain = ((k + 1) as f32 / length as f32 * FRAC_PI_2).cos();
+        let [left, right] = current[START + k];
+        assert_close(frame, [left * gain, right * gain], k);
+    }
+    assert!(output[length..].iter().all(|&frame| frame == [0.0; 2]));
+
+    // It resumes where it paused, fading in over the same length
+    let resumed: Vec<_> = player.by_ref().take(2 * length).collect();
+    for (k, &frame) in resumed.iter().enumerate() {
+        let gain = ((k + 1) as f32 / length as f32).min(1.0) * FRAC_PI_2;
+        let [left, right] = current[START + length + k];
+        assert_close(frame, [left * gain.sin(), right * gain.sin()], k);
+    }
+}
+
+#[test]
+fn music_play_during_a_stinger_drops_the_resume() {
+    let song = Song::from_slice(POSEIDON).unwrap();
+    let short = short_song();
+
+    let mut player = MusicPlayer::new();
+    player.play(Synth::new(&song, (0, 1), 44100.0), Transition::Cut);
+    player.by_ref().take(START).for_each(drop);
+    player.play_stinger(Synth::new(&short, (0, 1), 44100.0), Transition::Cut);
+    player.by_ref().take(100).for_each(drop);
+
+    // The new song replaces the stinger, and the interrupted song never comes back
+    player.play(Synth::new(&short, (0, 1), 44100.0), Transition::Cut);
+    assert!(player.by_ref().take(1000).all(|frame| frame == [0.0; 2]));
+    assert!(!player.is_playing());
+}
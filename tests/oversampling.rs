@@ -0,0 +1,49 @@
+//! Tests for oversampling the oscillators and filters.
+
+use sonant::{Oversampling, Param, Song, Synth};
+
+mod common;
+
+/// A saw wave through a resonant low-pass filter, swept up to its highest frequency by the LFO.
+fn filter_sweep() -> Song {
+    let mut song = common::oscillator_song(common::SAW);
+    for (param, value) in [
+        (Param::FxFilter, 2.0),
+        (Param::FxFreq, 11025.0),
+        (Param::FxResonance, 16.0),
+        (Param::LfoFxFreq, 1.0),
+        (Param::LfoAmount, 255.0),
+        (Param::LfoFreq, 8.0),
+    ] {
+        song.set_param(0, param, value);
+    }
+
+    song
+}
+
+#[test]
+fn oversampling_keeps_filter_sweeps_finite() {
+    let song = filter_sweep();
+
+    let peak = |oversampling| {
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        synth.set_oversampling(oversampling);
+        synth.note_on(0, 150, 1.0);
+
+        synth.take(88200).flatten().fold(0.0_f32, |peak, sample| {
+            assert!(sample.is_finite(), "{oversampling:?}");
+            peak.max(sample.abs())
+        })
+    };
+
+    // The oversampled filter has about the same level as the original
+    let expected = peak(Oversampling::Off);
+    assert!(expected > 0.0);
+    for oversampling in [Oversampling::X2, Oversampling::X4] {
+        let peak = peak(oversampling);
+        assert!(
+            (peak / expected - 1.0).abs() < 0.25,
+            "{oversampling:?}: {peak}"
+        );
+    }
+}
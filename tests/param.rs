@@ -0,0 +1,96 @@
+//! Tests for reading and changing instrument parameters.
+
+use common::{INSTRUMENTS, INSTRUMENT_LENGTH};
+use sonant::{InstrumentVoice, Param, Song, Synth};
+
+mod common;
+
+const SONGS: [&[u8]; 4] = [
+    include_bytes!("../examples/ambidumbi.snt"),
+    include_bytes!("../examples/lovely_drive.snt"),
+    include_bytes!("../examples/microscope.snt"),
+    include_bytes!("../examples/poseidon.snt"),
+];
+
+/// Read the value of `param` from the instrument at `data`.
+fn file_param(data: &[u8], param: Param) -> f32 {
+    let byte = |i: usize| f32::from(data[i]);
+    let word = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap()) as f32;
+
+    let index = Param::ALL.iter().position(|&p| p == param).unwrap();
+    match param {
+        Param::EnvAttack | Param::EnvSustain | Param::EnvRelease => word(16 + (index - 13) * 4),
+        Param::EnvMaster => byte(28),
+        Param::FxFilter => byte(29),
+        Param::FxFreq => f32::from_le_bytes(data[32..36].try_into().unwrap()),
+        _ if index < 13 => byte(index),
+        _ => byte(36 + index - 19),
+    }
+}
+
+#[test]
+fn params_match_file() {
+    for data in SONGS {
+        let song = Song::from_slice(data).unwrap();
+
+        for instrument in 0..8 {
+            let start = INSTRUMENTS + instrument * INSTRUMENT_LENGTH;
+            for param in Param::ALL {
+                let expected = file_param(&data[start..], param);
+                assert_eq!(song.param(instrument, param), expected, "{param:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn set_param_round_trip() {
+    for data in SONGS {
+        let song = Song::from_slice(data).unwrap();
+        let mut copy = song.clone();
+        for instrument in 0..8 {
+            for param in Param::ALL {
+                copy.set_param(instrument, param, song.param(instrument, param));
+            }
+        }
+
+        let expected = Synth::new(&song, (0, 1), 44100.0).take(44100);
+        assert!(Synth::new(&copy, (0, 1), 44100.0).take(44100).eq(expected));
+    }
+}
+
+#[test]
+fn set_param_clamps_and_rounds() {
+    let mut song = Song::from_slice(SONGS[3]).unwrap();
+
+    song.set_param(0, Param::Osc0Waveform, 7.0);
+    assert_eq!(song.param(0, Param::Osc0Waveform), 3.0);
+    song.set_param(0, Param::Osc0Octave, -1.0);
+    assert_eq!(song.param(0, Param::Osc0Octave), 0.0);
+    song.set_param(0, Param::Osc0Volume, 99.6);
+    assert_eq!(song.param(0, Param::Osc0Volume), 100.0);
+    song.set_param(0, Param::FxFreq, 1234.5);
+    assert_eq!(song.param(0, Param::FxFreq), 1234.5);
+
+    for param in Param::ALL {
+        let range = param.range();
+        song.set_param(1, param, *range.end());
+        assert_eq!(song.param(1, param), *range.end(), "{param:?}");
+        song.set_param(1, param, *range.start());
+        assert_eq!(song.param(1, param), *range.start(), "{param:?}");
+    }
+}
+
+#[test]
+fn set_param_changes_playing_notes() {
+    let song = Song::from_slice(SONGS[3]).unwrap();
+    let mut voice = InstrumentVoice::new(song, 1, (0, 1), 44100.0);
+    voice.note_on(144, 1.0);
+    let mut muted = voice.clone();
+
+    muted.set_param(Param::Osc0Volume, 0.0);
+    muted.set_param(Param::Osc1Volume, 0.0);
+    assert_eq!(muted.param(Param::Osc1Volume), 0.0);
+    assert!(muted.take(4410).all(|frame| frame == [0.0; 2]));
+    assert!(voice.take(4410).any(|frame| frame != [0.0; 2]));
+}
@@ -0,0 +1,55 @@
+//! Tests for the `rodio::Source` adapter.
+
+#![cfg(feature = "rodio")]
+
+use rodio::Source as _;
+use sonant::{Song, Synth, SynthSource};
+
+mod common;
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// The first pattern of Poseidon, followed by the delayed echoes.
+fn first_pattern() -> Song {
+    Song::from_slice(&common::first_patterns(1)).unwrap()
+}
+
+#[test]
+fn source_matches_synth() {
+    let song = first_pattern();
+    let synth = Synth::new(&song, (0, 1), SAMPLE_RATE as f32);
+    let source = SynthSource::new(synth.clone());
+    assert_eq!(source.channels(), 2);
+    assert_eq!(source.sample_rate(), SAMPLE_RATE);
+
+    let expected: Vec<_> = synth.flatten().collect();
+    let samples: Vec<_> = source.collect();
+    assert_eq!(samples, expected);
+}
+
+#[test]
+fn source_total_duration() {
+    let song = first_pattern();
+    let source = SynthSource::new(Synth::new(&song, (0, 1), SAMPLE_RATE as f32));
+    let duration = source.total_duration().unwrap();
+
+    let frames = source.count() / 2;
+    assert_eq!(
+        (duration.as_secs_f64() * f64::from(SAMPLE_RATE)).round() as usize,
+        frames
+    );
+}
+
+#[test]
+fn source_combinators() {
+    let song = first_pattern();
+    let source = SynthSource::new(Synth::new(&song, (0, 1), SAMPLE_RATE as f32));
+    let duration = source.total_duration().unwrap();
+
+    // Combinators keep track of the duration
+    let fast = source.clone().speed(2.0).total_duration().unwrap();
+    assert!((fast.as_secs_f64() - duration.as_secs_f64() / 2.0).abs() < 1e-6);
+
+    let mixed = source.clone().mix(source.amplify(0.5));
+    assert_eq!(mixed.total_duration(), Some(duration));
+}
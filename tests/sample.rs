@@ -0,0 +1,143 @@
+//! Tests for converting samples with the `Sample` trait.
+
+use sonant::{Sample, Song, Synth, I24};
+
+mod common;
+
+/// Full scale, clipped, and tiny samples for a format whose largest value is `max`.
+fn limits(max: f64) -> [(f32, f64); 8] {
+    [
+        (1.0, max),
+        (-1.0, -max),
+        (2.0, max),
+        (-2.0, -max - 1.0),
+        (f32::INFINITY, max),
+        (f32::NEG_INFINITY, -max - 1.0),
+        (0.0, 0.0),
+        (-0.0, 0.0),
+    ]
+}
+
+/// Check an integer format without dither, including rounding to the nearest step. `convert`
+/// returns the sample as a signed value.
+fn check_integer(max: f64, convert: impl Fn(f32, f32) -> f64) {
+    for (sample, expected) in limits(max) {
+        assert_eq!(convert(sample, 0.0), expected, "{sample}");
+    }
+
+    // A quarter of a step rounds down, and three quarters round up
+    let step = (1.0 / max) as f32;
+    for (sample, expected) in [
+        (step * 0.25, 0.0),
+        (step * 0.75, 1.0),
+        (step * 10.25, 10.0),
+        (-step * 0.25, 0.0),
+        (-step * 0.75, -1.0),
+        (-step * 10.75, -11.0),
+    ] {
+        assert_eq!(convert(sample, 0.0), expected, "{sample}");
+    }
+
+    // Dither is added before rounding, in units of the least significant bit
+    for (sample, dither, expected) in [
+        (0.0, 0.4, 0.0),
+        (0.0, 0.6, 1.0),
+        (0.0, -0.6, -1.0),
+        (step * 10.0, 0.9, 11.0),
+        (step * 10.0, -0.9, 9.0),
+        (1.0, 1.0, max),
+        (-1.0, -1.0, -max - 1.0),
+        (-1.0, -2.0, -max - 1.0),
+    ] {
+        assert_eq!(convert(sample, dither), expected, "{sample} {dither}");
+    }
+}
+
+#[test]
+fn sample_signed_integers() {
+    check_integer(127.0, |sample, dither| i8::from_f32(sample, dither).into());
+    check_integer(32767.0, |sample, dither| {
+        i16::from_f32(sample, dither).into()
+    });
+    check_integer(8_388_607.0, |sample, dither| {
+        I24::from_f32(sample, dither).0.into()
+    });
+    check_integer(2_147_483_647.0, |sample, dither| {
+        i32::from_f32(sample, dither).into()
+    });
+}
+
+#[test]
+fn sample_unsigned_integers() {
+    // Silence is in the middle of the range
+    check_integer(127.0, |sample, dither| {
+        f64::from(u8::from_f32(sample, dither)) - 128.0
+    });
+    check_integer(32767.0, |sample, dither| {
+        f64::from(u16::from_f32(sample, dither)) - 32768.0
+    });
+
+    assert_eq!(u8::from_f32(-1.0, -1.0), 0);
+    assert_eq!(u8::from_f32(1.0, 0.0), 255);
+    assert_eq!(u16::from_f32(-1.0, -1.0), 0);
+    assert_eq!(u16::from_f32(1.0, 0.0), 65535);
+}
+
+#[test]
+fn sample_floats_ignore_dither() {
+    for sample in [0.0, -0.0, 0.25, -1.0, 1.5, f32::MIN_POSITIVE, f32::INFINITY] {
+        for dither in [0.0, 0.5, -1.0] {
+            assert_eq!(f32::from_f32(sample, dither).to_bits(), sample.to_bits());
+            assert_eq!(f64::from_f32(sample, dither), f64::from(sample));
+        }
+    }
+    assert!(f32::from_f32(f32::NAN, 0.0).is_nan());
+    assert!(f64::from_f32(f32::NAN, 1.0).is_nan());
+}
+
+/// Render the start of Poseidon to `T`, optionally with dither, and compare it with the samples
+/// from the iterator. `convert` returns a sample as a signed value in steps.
+fn check_render<T: Sample + Default>(dither: bool, convert: impl Fn(T) -> f64) {
+    let song = Song::from_slice(common::POSEIDON).unwrap();
+    let expected: Vec<_> = Synth::new(&song, (0, 1), 44100.0)
+        .take(4096)
+        .flatten()
+        .map(|sample| convert(T::from_f32(sample, 0.0)))
+        .collect();
+
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_dither(dither);
+    let mut buffer = vec![T::default(); expected.len()];
+    assert_eq!(synth.render(&mut buffer), 4096);
+    let rendered: Vec<_> = buffer.into_iter().map(convert).collect();
+
+    // Dither stays within one step of the rounded samples
+    assert!(rendered
+        .iter()
+        .zip(&expected)
+        .all(|(a, b)| (a - b).abs() <= 1.0));
+    assert_eq!(rendered == expected, !dither);
+}
+
+#[test]
+fn sample_render_with_dither() {
+    for dither in [false, true] {
+        check_render(dither, |sample: i8| sample.into());
+        check_render(dither, |sample: i16| sample.into());
+        check_render(dither, |sample: I24| sample.0.into());
+        check_render(dither, |sample: u8| f64::from(sample) - 128.0);
+        check_render(dither, |sample: u16| f64::from(sample) - 32768.0);
+    }
+
+    // Floating point samples are the same with dither
+    let song = Song::from_slice(common::POSEIDON).unwrap();
+    let expected: Vec<_> = Synth::new(&song, (0, 1), 44100.0)
+        .take(4096)
+        .flatten()
+        .collect();
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_dither(true);
+    let mut buffer = vec![0.0_f32; expected.len()];
+    synth.render(&mut buffer);
+    assert!(buffer == expected);
+}
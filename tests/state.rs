@@ -0,0 +1,203 @@
+//! Tests for saving and restoring the playback state.
+
+use common::POSEIDON;
+use sonant::{Error, Oversampling, Param, Song, Synth, SynthState, MAX_OVERLAPPING_NOTES};
+
+mod common;
+
+/// Poseidon with a live note on a track with delayed echoes, and a changed sequence. Returns the
+/// synth after rendering one second.
+fn playing(song: &Song) -> Synth<&Song> {
+    let mut synth = Synth::new(song, (0, 1), 44100.0);
+    synth.set_sequence_entry(1, 2, 0);
+    synth.jump_to(0);
+    synth.by_ref().take(22050).for_each(drop);
+    synth.note_on(0, 140, 0.8);
+    synth.by_ref().take(22050).for_each(drop);
+
+    synth
+}
+
+/// The song played by [`playing`], where instrument 0 has a few delayed echoes.
+fn song() -> Song {
+    let mut song = Song::from_slice(POSEIDON).unwrap();
+    song.set_param(0, Param::FxDelayTime, 4.0);
+    song.set_param(0, Param::FxDelayAmount, 128.0);
+
+    song
+}
+
+#[test]
+fn state_round_trip_through_bytes() {
+    let song = song();
+
+    for oversampling in [Oversampling::Off, Oversampling::X4] {
+        let mut synth = playing(&song);
+        synth.set_oversampling(oversampling);
+        synth.by_ref().take(1000).for_each(drop);
+
+        let bytes = synth.state().as_bytes().to_vec();
+        let expected: Vec<_> = synth.take(88200).collect();
+
+        // A new synth continues with the same samples, including the echoes of the live note
+        let state = SynthState::from_bytes(&bytes).unwrap();
+        let mut restored = Synth::new(&song, (1, 2), 44100.0);
+        restored.set_oversampling(oversampling);
+        restored.restore(&state).unwrap();
+        assert_eq!(restored.state().as_bytes(), bytes);
+        assert!(restored.take(88200).eq(expected));
+    }
+}
+
+#[test]
+fn state_rejects_invalid_bytes() {
+    let song = song();
+    let synth = playing(&song);
+    let bytes = synth.state().as_bytes().to_vec();
+
+    // Unknown versions
+    assert!(matches!(
+        SynthState::from_bytes(&[]),
+        Err(Error::InvalidState)
+    ));
+    let mut version = bytes.clone();
+    version[0] += 1;
+    assert!(matches!(
+        SynthState::from_bytes(&version),
+        Err(Error::InvalidState)
+    ));
+
+    // Truncated and extended states
+    let mut restored = Synth::new(&song, (0, 1), 44100.0);
+    for len in 1..bytes.len() {
+        let state = SynthState::from_bytes(&bytes[..len]).unwrap();
+        assert!(restored.restore(&state).is_err(), "{len} bytes");
+    }
+    let mut longer = bytes.clone();
+    longer.push(0);
+    let state = SynthState::from_bytes(&longer).unwrap();
+    assert!(restored.restore(&state).is_err());
+
+    // The live note is stored last, and its next echo round must be one of the 8 that the track
+    // plays
+    let round = bytes.len() - 4;
+    for (value, valid) in [
+        (0, false),
+        (1, true),
+        (8, true),
+        (9, false),
+        (u32::MAX, false),
+    ] {
+        let mut bytes = bytes.clone();
+        bytes[round..].copy_from_slice(&value.to_le_bytes());
+        let state = SynthState::from_bytes(&bytes).unwrap();
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        assert_eq!(synth.restore(&state).is_ok(), valid, "round {value}");
+    }
+
+    // The sample rate follows the version and the noise generator
+    for value in [0.0, -44100.0, f32::NAN] {
+        let mut bytes = bytes.clone();
+        bytes[17..21].copy_from_slice(&value.to_le_bytes());
+        let state = SynthState::from_bytes(&bytes).unwrap();
+        assert!(restored.restore(&state).is_err(), "sample rate {value}");
+    }
+
+    // Failed restores leave the synth unchanged
+    let expected: Vec<_> = Synth::new(&song, (0, 1), 44100.0).take(1000).collect();
+    assert!(restored.take(1000).eq(expected));
+}
+
+#[test]
+fn state_round_trip_with_every_voice() {
+    // One held live note for each voice of the track
+    let song = common::oscillator_song(common::SINE);
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.set_meter_block(1000);
+    for k in 0..MAX_OVERLAPPING_NOTES {
+        synth.note_on(0, 120 + k as u8, 0.1);
+    }
+    synth.by_ref().take(1000).for_each(drop);
+    assert!(synth.voice_envelopes()[0].iter().all(|&env| env > 0.0));
+
+    let state = synth.state();
+    let expected: Vec<_> = synth.take(22050).collect();
+    let mut restored = Synth::new(&song, (0, 1), 44100.0);
+    restored.restore(&state).unwrap();
+    assert!(restored.by_ref().take(22050).eq(expected));
+
+    // The playing voices of the first track follow the empty sequence table and the timeline. Bits
+    // for voices that the track doesn't have are invalid.
+    let bytes = state.as_bytes();
+    let voices = 89..93;
+    let playing = u32::MAX >> (32 - MAX_OVERLAPPING_NOTES);
+    assert_eq!(bytes[voices.clone()], playing.to_le_bytes());
+    for value in [playing + 1, playing << 1, u32::MAX] {
+        let mut bytes = bytes.to_vec();
+        bytes[voices.clone()].copy_from_slice(&value.to_le_bytes());
+        let state = SynthState::from_bytes(&bytes).unwrap();
+        assert!(
+            matches!(restored.restore(&state), Err(Error::InvalidState)),
+            "voices {value:#x}"
+        );
+    }
+}
+
+#[test]
+fn state_rejects_invalid_positions() {
+    // Poseidon with short rows, after jumping from the first pattern to the second
+    let mut data = POSEIDON.to_vec();
+    data[..4].copy_from_slice(&16_u32.to_le_bytes());
+    let song = Song::from_slice(&data).unwrap();
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+    synth.jump_to(1);
+    synth.by_ref().take(1000).for_each(drop);
+    let position = synth.position();
+    assert_eq!((position.sequence, position.row), (1, 30));
+    let bytes = synth.state().as_bytes().to_vec();
+
+    // Offsets of the position, and of the two timeline segments that follow the unchanged
+    // sequences
+    let sequence = 41..45;
+    let row = 45..49;
+    let eighths = 58..62;
+    assert_eq!(bytes[82], 2);
+    let segments = [83..87, 88..92];
+    let segment_sequence = 92;
+    assert_eq!(bytes[segments[1].clone()], 64_u32.to_le_bytes());
+    assert_eq!(bytes[segment_sequence], 1);
+
+    let mut restored = Synth::new(&song, (0, 1), 44100.0);
+    let mut check = |range: core::ops::Range<usize>, value: u32, valid: bool| {
+        let mut bytes = bytes.clone();
+        bytes[range.clone()].copy_from_slice(&value.to_le_bytes()[..range.len()]);
+        let state = SynthState::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            restored.restore(&state).is_ok(),
+            valid,
+            "{range:?} = {value}"
+        );
+    };
+
+    // The pattern and row must match the timeline
+    check(sequence.clone(), 1, true);
+    for value in [0, 2, 47, u32::MAX] {
+        check(sequence.clone(), value, false);
+    }
+    check(row.clone(), 30, true);
+    for value in [0, 31, 32, u32::MAX] {
+        check(row.clone(), value, false);
+    }
+
+    // Segments start on pattern boundaries, in order, and no later than the current eighth note
+    for value in [1, 63, 65, 128, u32::MAX] {
+        check(segments[1].clone(), value, false);
+    }
+    check(segments[0].clone(), 64, false);
+    check(segment_sequence..segment_sequence + 1, 48, false);
+
+    // Positions far into playback don't overflow
+    for value in [u32::MAX - 1, u32::MAX] {
+        check(eighths.clone(), value, false);
+    }
+}
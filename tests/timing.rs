@@ -0,0 +1,199 @@
+//! Tests for row timing, song duration, tempo scale, and playback rate.
+
+use sonant::{Event, Param, Song, Synth};
+use std::f64::consts::PI;
+
+mod common;
+
+/// The first pattern of Poseidon, followed by the delayed echoes. Its rows are an odd number of
+/// samples long.
+fn first_pattern() -> (Song, u32) {
+    let data = common::first_patterns(1);
+    let row_length = common::row_length(&data);
+    assert_eq!(row_length % 2, 1);
+
+    (Song::from_slice(&data).unwrap(), row_length)
+}
+
+/// Render the whole song, returning the number of frames and the sample index of each row.
+fn render(synth: &mut Synth<&Song>) -> (usize, Vec<u32>) {
+    let mut rows = Vec::new();
+    let mut on_event = |position: &sonant::Position, event| {
+        if let Event::Row { .. } = event {
+            rows.push(position.sample);
+        }
+    };
+
+    let mut frames = 0;
+    while synth.next_with_events(&mut on_event).is_some() {
+        frames += 1;
+    }
+
+    (frames, rows)
+}
+
+#[test]
+fn rows_round_up_to_the_next_sample() {
+    let (song, row_length) = first_pattern();
+
+    for rate in [44100.0, 48000.0] {
+        let mut synth = Synth::new(&song, (0, 1), rate);
+        let (_, rows) = render(&mut synth);
+        assert_eq!(rows.len(), 32);
+
+        let eighth_note_length = f64::from(rate / 44100.0) * f64::from(row_length) / 2.0;
+        for (row, &sample) in rows.iter().enumerate() {
+            let start = (row * 2) as f64 * eighth_note_length;
+            assert_eq!(f64::from(sample), start.ceil(), "row {row} at {rate} Hz");
+        }
+    }
+}
+
+#[test]
+fn duration_matches_rendered_frames() {
+    let (song, _) = first_pattern();
+
+    let mut durations = Vec::new();
+    for rate in [44100.0, 48000.0] {
+        let mut synth = Synth::new(&song, (0, 1), rate);
+        let duration = synth.duration().unwrap();
+        let (frames, _) = render(&mut synth);
+
+        let expected = (duration.as_secs_f64() * f64::from(rate)).round() as usize;
+        assert_eq!(frames, expected, "{rate} Hz");
+        durations.push(duration.as_secs_f64());
+    }
+
+    // Rounding doesn't accumulate, so the duration is the same at any sample rate
+    assert!((durations[0] - durations[1]).abs() < 2.0 / 44100.0);
+}
+
+#[test]
+fn tempo_scale_and_playback_rate_change_row_timing() {
+    let (song, row_length) = first_pattern();
+    let mut reference = Synth::new(&song, (0, 1), 44100.0);
+    let (frames, _) = render(&mut reference);
+
+    for (tempo_scale, playback_rate) in [(2.0, 1.0), (0.5, 1.0), (1.0, 2.0), (1.0, 0.5)] {
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        synth.set_tempo_scale(tempo_scale);
+        synth.set_playback_rate(playback_rate);
+        let duration = synth.duration().unwrap();
+        let (scaled_frames, rows) = render(&mut synth);
+
+        // Both speed up the rows
+        let speed = f64::from(tempo_scale * playback_rate);
+        let eighth_note_length = f64::from(row_length) / 2.0 / speed;
+        for (row, &sample) in rows.iter().enumerate() {
+            let start = (row * 2) as f64 * eighth_note_length;
+            assert_eq!(f64::from(sample), start.ceil(), "row {row}");
+        }
+        assert_eq!(
+            (duration.as_secs_f64() * 44100.0).round() as usize,
+            scaled_frames,
+            "tempo scale {tempo_scale}, playback rate {playback_rate}"
+        );
+
+        // Only the playback rate also speeds up the envelopes of the last notes
+        if tempo_scale == 1.0 {
+            let expected = frames as f64 / speed;
+            assert!(
+                (scaled_frames as f64 - expected).abs() <= 2.0,
+                "{playback_rate}"
+            );
+        }
+    }
+}
+
+/// Measure the frequency of the left channel over `frames` frames at 44.1 kHz, by counting rising
+/// zero crossings.
+fn measure_frequency(synth: &mut Synth<&Song>, frames: usize) -> f64 {
+    let samples: Vec<_> = synth.by_ref().take(frames).map(|[left, _]| left).collect();
+    let crossings = samples
+        .windows(2)
+        .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+        .count();
+
+    crossings as f64 * 44100.0 / frames as f64
+}
+
+#[test]
+fn tempo_scale_keeps_pitch_and_playback_rate_changes_it() {
+    let song = common::oscillator_song(common::SINE);
+
+    // Pitch 140 is one octave above 44100 / 256 Hz
+    let expected = 44100.0 / 128.0;
+    for (tempo_scale, playback_rate) in [(1.0, 1.0), (2.0, 1.0), (0.5, 1.0), (1.0, 2.0)] {
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        synth.set_tempo_scale(tempo_scale);
+        synth.note_on(0, 140, 1.0);
+
+        // Notes that are already playing follow the playback rate
+        synth.by_ref().take(1000).for_each(drop);
+        synth.set_playback_rate(playback_rate);
+
+        let frequency = measure_frequency(&mut synth, 22050);
+        let expected = expected * f64::from(playback_rate);
+        assert!(
+            (frequency - expected).abs() < expected * 0.01,
+            "tempo scale {tempo_scale}, playback rate {playback_rate}: {frequency} Hz"
+        );
+    }
+}
+
+/// Compute the spectral centroid of the left channel, in Hz at 44.1 kHz. The samples are windowed
+/// with a Hann window.
+fn spectral_centroid(samples: &[[f32; 2]]) -> f64 {
+    let len = samples.len();
+    let (sin, cos): (Vec<_>, Vec<_>) = (0..len)
+        .map(|n| (2.0 * PI * n as f64 / len as f64).sin_cos())
+        .unzip();
+    let windowed: Vec<_> = samples
+        .iter()
+        .zip(&cos)
+        .map(|([left, _], cos)| f64::from(*left) * (0.5 - 0.5 * cos))
+        .collect();
+
+    let (mut weighted, mut total) = (0.0, 0.0);
+    for bin in 1..len / 2 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (n, sample) in windowed.iter().enumerate() {
+            let k = bin * n % len;
+            re += sample * cos[k];
+            im -= sample * sin[k];
+        }
+        let power = re * re + im * im;
+        weighted += power * bin as f64;
+        total += power;
+    }
+
+    weighted / total * 44100.0 / len as f64
+}
+
+#[test]
+fn playback_rate_scales_the_spectrum() {
+    // A saw wave through a resonant band-pass filter
+    let mut song = common::oscillator_song(common::SAW);
+    song.set_param(0, Param::FxFilter, 3.0);
+    song.set_param(0, Param::FxFreq, 2000.0);
+    song.set_param(0, Param::FxResonance, 50.0);
+
+    let centroid = |playback_rate: f32| {
+        let mut synth = Synth::new(&song, (0, 1), 44100.0);
+        synth.set_playback_rate(playback_rate);
+        synth.note_on(0, 128, 1.0);
+        let samples: Vec<_> = synth.skip(2048).take(4096).collect();
+
+        spectral_centroid(&samples)
+    };
+
+    // Both the harmonics and the filter cutoff follow the playback rate, like a tape played faster
+    let expected = centroid(1.0);
+    for playback_rate in [0.5, 1.5, 2.0] {
+        let scaled = centroid(playback_rate) / expected;
+        assert!(
+            (scaled / f64::from(playback_rate) - 1.0).abs() < 0.05,
+            "playback rate {playback_rate}: centroid scaled by {scaled}"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Tests for Scala tunings and the default 12-TET tuning.
+
+use sonant::{Error, InstrumentVoice, Param, Song, Tuning};
+
+mod common;
+
+/// Length of each measurement, in samples. The phase of the oscillators is an `f32`, so the
+/// measured pitch drifts slightly over longer notes.
+const FRAMES: usize = 22050;
+
+/// A sine wave at the pitch of the note. Both oscillators play the same key, so a note is only
+/// skipped when that key is unmapped.
+fn sine_song() -> Song {
+    let mut song = common::oscillator_song(common::SINE);
+    song.set_param(0, Param::Osc1Octave, 8.0);
+
+    song
+}
+
+/// Play `note` with `tuning`, and measure its frequency in Hz from the first and last rising zero
+/// crossings. Returns `None` when the note is silent.
+fn measure(song: &Song, tuning: &Tuning, note: u8) -> Option<f64> {
+    let mut voice = InstrumentVoice::new(song, 0, (0, 1), 44100.0);
+    voice.set_tuning(tuning.clone());
+    voice.note_on(note, 1.0);
+
+    let samples: Vec<_> = voice.take(FRAMES).map(|[left, _]| left).collect();
+    let crossings: Vec<_> = samples
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+        .map(|(k, pair)| k as f64 + f64::from(pair[0] / (pair[0] - pair[1])))
+        .collect();
+    let (first, last) = (crossings.first()?, crossings.last()?);
+
+    Some((crossings.len() - 1) as f64 * 44100.0 / (last - first))
+}
+
+fn assert_frequency(actual: Option<f64>, expected: f64) {
+    let actual = actual.expect("the note is silent");
+    let cents = 1200.0 * (actual / expected).log2();
+    assert!(cents.abs() < 0.5, "{actual} Hz, expected {expected} Hz");
+}
+
+/// Frequency of a note value in the 12-TET tuning of the original player.
+fn twelve_tet(note: u8) -> f64 {
+    44100.0 / 256.0 * 2_f64.powf((f64::from(note) - 128.0) / 12.0)
+}
+
+#[test]
+fn tuning_default_is_twelve_tet() {
+    let song = sine_song();
+    let tuning = Tuning::default();
+    for note in [110, 128, 135, 144, 170] {
+        assert_frequency(measure(&song, &tuning, note), twelve_tet(note));
+    }
+
+    // A4 is the original reference pitch
+    assert_frequency(measure(&song, &tuning, 144), 434.082_17);
+}
+
+#[test]
+fn tuning_ratios_match_cents() {
+    let ratios = "\
+! just.scl
+Just fifths and octaves
+ 2
+!
+3/2
+2
+";
+    let cents = "\
+! fifths.scl
+Fifths and octaves in cents
+2
+701.955000865387
+1200.0 the octave
+";
+    let ratios = Tuning::from_scala(ratios, None).unwrap();
+    assert_eq!(ratios, Tuning::from_scala(cents, None).unwrap());
+
+    // Middle C keeps its pitch, and the scale repeats every octave
+    let song = sine_song();
+    let middle_c = twelve_tet(135);
+    for (note, expected) in [
+        (133, middle_c / 2.0),
+        (134, middle_c * 0.75),
+        (135, middle_c),
+        (136, middle_c * 1.5),
+        (137, middle_c * 2.0),
+        (138, middle_c * 3.0),
+    ] {
+        assert_frequency(measure(&song, &ratios, note), expected);
+    }
+}
+
+#[test]
+fn tuning_keyboard_mapping_skips_unmapped_keys() {
+    let scale = "\
+Major scale
+7
+200.0
+400.0
+500.0
+700.0
+900.0
+1100.0
+2/1
+";
+    // The white keys from C4 to C5 play the scale, and the black keys are unmapped
+    let kbm = "\
+! major.kbm
+12
+60
+72
+60
+69
+440.0
+7
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+    let tuning = Tuning::from_scala(scale, Some(kbm)).unwrap();
+
+    // The reference key plays the reference frequency
+    let song = sine_song();
+    assert_frequency(measure(&song, &tuning, 144), 440.0);
+
+    // MIDI note 60 is note 135
+    let c4 = 440.0 * 2_f64.powf(-900.0 / 1200.0);
+    for (key, cents) in [
+        (60, 0.0),
+        (62, 200.0),
+        (67, 700.0),
+        (71, 1100.0),
+        (72, 1200.0),
+    ] {
+        let expected = c4 * 2_f64.powf(cents / 1200.0);
+        assert_frequency(measure(&song, &tuning, key + 75), expected);
+    }
+    for key in [61, 63, 66, 68, 70] {
+        assert_eq!(measure(&song, &tuning, key + 75), None, "key {key}");
+    }
+
+    // Keys outside of the mapping keep the default tuning
+    for note in [134, 148] {
+        assert_frequency(measure(&song, &tuning, note), twelve_tet(note));
+    }
+}
+
+#[test]
+fn tuning_rejects_malformed_files() {
+    let scale = |count: usize, pitch: &str| {
+        let mut scl = format!("Test\n{count}\n");
+        for _ in 0..count {
+            scl += pitch;
+            scl += "\n";
+        }
+        scl
+    };
+    assert!(Tuning::from_scala(&scale(1, "2/1"), None).is_ok());
+    assert!(Tuning::from_scala(&scale(256, "100.0"), None).is_ok());
+
+    for scl in [
+        String::new(),
+        "Empty\n0\n".to_string(),
+        scale(257, "100.0"),
+        "Missing\n3\n100.0\n200.0\n".to_string(),
+        scale(1, "0/1"),
+        scale(1, "1/0"),
+        scale(1, "-3/2"),
+        scale(1, "cents"),
+        scale(1, "1e400."),
+    ] {
+        assert!(
+            matches!(Tuning::from_scala(&scl, None), Err(Error::InvalidTuning)),
+            "{scl:?}"
+        );
+    }
+
+    // Keyboard mappings with keys outside of the MIDI range, or an unmapped reference key
+    let octave = scale(12, "100.0");
+    let mapping = |first: &str, last: &str, middle: &str, reference: &str, key: &str| {
+        format!("1\n{first}\n{last}\n{middle}\n{reference}\n440.0\n1\n{key}\n")
+    };
+    assert!(Tuning::from_scala(&octave, Some(&mapping("0", "127", "60", "69", "0"))).is_ok());
+    for kbm in [
+        mapping("-1", "127", "60", "69", "0"),
+        mapping("0", "128", "60", "69", "0"),
+        mapping("0", "127", "2147483647", "69", "0"),
+        mapping("0", "127", "60", "-2147483648", "0"),
+        mapping("0", "127", "60", "69", "x"),
+        "257\n0\n127\n60\n69\n440.0\n12\n".to_string(),
+        "1\n0\n127\n60\n69\n0.0\n1\n0\n".to_string(),
+    ] {
+        assert!(
+            matches!(
+                Tuning::from_scala(&octave, Some(&kbm)),
+                Err(Error::InvalidTuning)
+            ),
+            "{kbm:?}"
+        );
+    }
+}
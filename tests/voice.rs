@@ -0,0 +1,74 @@
+//! Tests for rendering single notes of an instrument.
+
+use sonant::{render_note, render_note_into, Param, Song};
+
+mod common;
+
+/// An instrument that plays a sine wave for about two seconds, with delayed echoes.
+fn delayed(delay_time: f32, delay_amount: f32) -> Song {
+    let mut song = common::oscillator_song(common::SINE);
+    song.set_param(0, Param::FxDelayTime, delay_time);
+    song.set_param(0, Param::FxDelayAmount, delay_amount);
+
+    song
+}
+
+#[test]
+fn render_note_until_silent() {
+    // The note plays for the length of its envelope, in 44.1 kHz samples
+    let song = common::oscillator_song(common::SINE);
+    let length = 100_001;
+    assert_eq!(
+        render_note(&song, 0, 140, 44100.0, (0, 1)).len(),
+        length + 1
+    );
+    assert_eq!(
+        render_note(&song, 0, 140, 22050.0, (0, 1)).len(),
+        length.div_ceil(2) + 1
+    );
+
+    // Each echo starts one row (two eighth notes) later
+    let echoes = render_note(&delayed(2.0, 128.0), 0, 140, 44100.0, (0, 1));
+    assert!(echoes.len() > length + 5513);
+    assert!(echoes.len() < length + 5513 * 10);
+}
+
+#[test]
+fn render_note_infinite_echoes() {
+    // Endless echoes are cut off after a minute
+    let song = delayed(1.0, 255.0);
+    assert_eq!(render_note(&song, 0, 140, 8000.0, (0, 1)).len(), 60 * 8000);
+
+    // Without a delay time, the echoes would all start at once, so only one is played
+    let song = delayed(0.0, 255.0);
+    let expected = render_note(
+        &common::oscillator_song(common::SINE),
+        0,
+        140,
+        44100.0,
+        (0, 1),
+    );
+    assert_eq!(
+        render_note(&song, 0, 140, 44100.0, (0, 1)).len(),
+        expected.len()
+    );
+}
+
+#[test]
+fn render_note_into_matches_render_note() {
+    let song = delayed(2.0, 128.0);
+    let expected = render_note(&song, 0, 140, 44100.0, (0, 1));
+
+    // A buffer that is long enough holds the whole note
+    let mut buffer = vec![[1.0; 2]; expected.len() + 100];
+    let len = render_note_into(&song, 0, 140, 44100.0, (0, 1), &mut buffer);
+    assert_eq!(len, expected.len());
+    assert!(buffer[..len] == expected[..]);
+    assert!(buffer[len..].iter().all(|&frame| frame == [1.0; 2]));
+
+    // A shorter buffer is filled with the start of the note
+    let mut buffer = vec![[0.0; 2]; 1000];
+    let len = render_note_into(&song, 0, 140, 44100.0, (0, 1), &mut buffer);
+    assert_eq!(len, buffer.len());
+    assert!(buffer[..] == expected[..len]);
+}
@@ -0,0 +1,42 @@
+//! Tests for the JavaScript bindings, run in a headless browser with:
+//!
+//! ```bash
+//! wasm-pack test --headless --firefox -- --features wasm
+//! ```
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use sonant::{Song, Synth, WasmRenderer, WasmSong};
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const POSEIDON: &[u8] = include_bytes!("../examples/poseidon.snt");
+
+/// The size of an `AudioWorklet` render quantum.
+const BLOCK: usize = 128;
+
+#[wasm_bindgen_test]
+fn wasm_song_rejects_invalid_data() {
+    assert!(WasmSong::new(&POSEIDON[1..]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn wasm_render_matches_synth() {
+    let song = WasmSong::new(POSEIDON).unwrap();
+    let mut renderer = WasmRenderer::new(&song, 44100.0, 0, 1);
+    assert_eq!(renderer.duration().map(|x| x as u64), Some(162));
+
+    let song = Song::from_slice(POSEIDON).unwrap();
+    let mut synth = Synth::new(&song, (0, 1), 44100.0);
+
+    for _ in 0..100 {
+        let mut left = [0.0; BLOCK];
+        let mut right = [0.0; BLOCK];
+        assert_eq!(renderer.render(&mut left, &mut right), BLOCK);
+
+        for (&sample_l, &sample_r) in left.iter().zip(&right) {
+            assert_eq!(Some([sample_l, sample_r]), synth.next());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Tests for the WAV encoder, checking the header fields and the sample data.
+
+#![cfg(feature = "std")]
+
+use sonant::export::wav::{self, Encoder, Format, Options};
+use sonant::{Song, Synth};
+use std::io::Cursor;
+
+mod common;
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// Read a little-endian `u16` at `offset`.
+fn u16_at(file: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(file[offset..offset + 2].try_into().unwrap())
+}
+
+/// Read a little-endian `u32` at `offset`.
+fn u32_at(file: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap())
+}
+
+/// Encode `frames` with the given `options`.
+fn encode(frames: &[[f32; 2]], options: &Options) -> Vec<u8> {
+    let mut encoder = Encoder::new(Cursor::new(Vec::new()), options).unwrap();
+    encoder.write(frames).unwrap();
+
+    encoder.finish().unwrap().into_inner()
+}
+
+/// Check the header of a WAV file with `frames` stereo frames, and return the sample data.
+fn parse(file: &[u8], format: Format, frames: usize) -> &[u8] {
+    let (format_tag, bits, fmt_length) = match format {
+        Format::Int16 => (1, 16, 16),
+        Format::Int24 => (1, 24, 16),
+        Format::Float32 => (3, 32, 18),
+    };
+    let block_align = 2 * bits / 8;
+    let data_length = frames * usize::from(block_align);
+
+    assert_eq!(&file[..4], b"RIFF");
+    assert_eq!(u32_at(file, 4) as usize, file.len() - 8);
+    assert_eq!(&file[8..12], b"WAVE");
+
+    // The format chunk
+    assert_eq!(&file[12..16], b"fmt ");
+    assert_eq!(u32_at(file, 16), fmt_length);
+    assert_eq!(u16_at(file, 20), format_tag);
+    assert_eq!(u16_at(file, 22), 2);
+    assert_eq!(u32_at(file, 24), SAMPLE_RATE);
+    assert_eq!(u32_at(file, 28), SAMPLE_RATE * u32::from(block_align));
+    assert_eq!(u16_at(file, 32), block_align);
+    assert_eq!(u16_at(file, 34), bits);
+
+    // Floating point files have an empty extension, and a fact chunk with the number of frames
+    let mut data = 20 + fmt_length as usize;
+    if format == Format::Float32 {
+        assert_eq!(u16_at(file, 36), 0);
+        assert_eq!(&file[data..data + 4], b"fact");
+        assert_eq!(u32_at(file, data + 4), 4);
+        assert_eq!(u32_at(file, data + 8) as usize, frames);
+        data += 12;
+    }
+
+    assert_eq!(&file[data..data + 4], b"data");
+    assert_eq!(u32_at(file, data + 4) as usize, data_length);
+    assert_eq!(file.len(), data + 8 + data_length);
+
+    &file[data + 8..]
+}
+
+/// Quantize samples without dither, like the encoder.
+fn quantize(frames: &[[f32; 2]], bits: u32) -> Vec<i32> {
+    let max = ((1 << (bits - 1)) - 1) as f32;
+
+    frames
+        .iter()
+        .flatten()
+        .map(|sample| (sample * max).round().clamp(-max - 1.0, max) as i32)
+        .collect()
+}
+
+/// Decode integer samples of `bytes` bytes each, sign extending them.
+fn decode(data: &[u8], bytes: usize) -> Vec<i32> {
+    let shift = 32 - 8 * bytes as u32;
+
+    data.chunks_exact(bytes)
+        .map(|sample| {
+            let mut word = [0; 4];
+            word[..bytes].copy_from_slice(sample);
+            i32::from_le_bytes(word) << shift >> shift
+        })
+        .collect()
+}
+
+/// A few seconds of Poseidon, with a few samples that are clipped by each format.
+fn frames() -> Vec<[f32; 2]> {
+    let song = Song::from_slice(common::POSEIDON).unwrap();
+    let mut frames: Vec<_> = Synth::new(&song, (0, 1), SAMPLE_RATE as f32)
+        .take(2 * 48000 + 123)
+        .collect();
+    frames.extend([[1.0, -1.0], [1.5, -1.5], [0.0, -0.0]]);
+
+    frames
+}
+
+#[test]
+fn wav_integer_formats() {
+    let frames = frames();
+
+    for (format, bits) in [(Format::Int16, 16), (Format::Int24, 24)] {
+        let options = Options {
+            sample_rate: SAMPLE_RATE,
+            format,
+            ..Options::default()
+        };
+        let file = encode(&frames, &options);
+        let data = parse(&file, format, frames.len());
+        assert!(
+            decode(data, bits / 8) == quantize(&frames, bits as u32),
+            "{format:?} mismatch"
+        );
+
+        // Full scale and silence
+        let max = (1 << (bits - 1)) - 1;
+        let tail = &decode(data, bits / 8)[frames.len() * 2 - 6..];
+        assert_eq!(tail, [max, -max, max, -max - 1, 0, 0], "{format:?}");
+    }
+}
+
+#[test]
+fn wav_float_format() {
+    let frames = frames();
+    let options = Options {
+        sample_rate: SAMPLE_RATE,
+        format: Format::Float32,
+        dither: true,
+        ..Options::default()
+    };
+    let file = encode(&frames, &options);
+    let data = parse(&file, Format::Float32, frames.len());
+
+    // Samples are written as they are, without dither or clipping
+    let decoded: Vec<_> = data
+        .chunks_exact(4)
+        .map(|sample| f32::from_le_bytes(sample.try_into().unwrap()))
+        .collect();
+    let expected: Vec<_> = frames.iter().flatten().copied().collect();
+    assert_eq!(decoded.len(), expected.len());
+    assert!(decoded
+        .iter()
+        .zip(&expected)
+        .all(|(a, b)| a.to_bits() == b.to_bits()));
+}
+
+#[test]
+fn wav_dither_is_reproducible() {
+    let frames = frames();
+    let options = Options {
+        sample_rate: SAMPLE_RATE,
+        dither: true,
+        ..Options::default()
+    };
+    let file = encode(&frames, &options);
+    assert_eq!(file, encode(&frames, &options));
+
+    // Dither stays within one step of the quantized samples
+    let decoded = decode(parse(&file, Format::Int16, frames.len()), 2);
+    let expected = quantize(&frames, 16);
+    assert!(decoded
+        .iter()
+        .zip(&expected)
+        .all(|(a, b)| (a - b).abs() <= 1));
+    assert!(decoded != expected);
+}
+
+#[test]
+fn wav_write_song() {
+    // A short, silent song
+    let song = common::empty_song(16);
+
+    for format in [Format::Int16, Format::Int24, Format::Float32] {
+        let options = Options {
+            sample_rate: SAMPLE_RATE,
+            format,
+            ..Options::default()
+        };
+        let mut file = Cursor::new(Vec::new());
+        let frames = wav::write(&song, &mut file, &options).unwrap();
+
+        // The 512 samples of the song at 44.1 kHz
+        assert_eq!(frames, 558);
+        let data = parse(file.get_ref(), format, 558);
+        assert!(data.iter().all(|&byte| byte == 0), "{format:?}");
+    }
+}